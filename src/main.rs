@@ -1,13 +1,147 @@
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum, ValueHint};
+use clap_complete::Shell;
 use colored::{ColoredString, Colorize};
 use indexmap::IndexMap;
+use log::{debug, trace};
 use regex::Regex;
 use serde_json::Value;
+use std::cell::OnceCell;
+use std::ffi::{OsStr, OsString};
 use std::fmt::{Display, Formatter};
-use std::path::PathBuf;
-use std::process::Command;
+use std::hash::BuildHasher;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 const MANIFEST_NAME: &str = ".clasp.json";
+const SETTINGS_NAME: &str = ".clubrc";
+const DEPLOY_INFO_NAME: &str = "DEPLOY_INFO";
+const ISOLATED_ROOT_DIR: &str = "root";
+const RECENT_IDS_NAME: &str = ".club_recent_ids";
+const PUSHED_STATE_NAME: &str = ".club_pushed.json";
+const HISTORY_NAME: &str = ".club_history.jsonl";
+/// Where clasp keeps the credentials `clasp login` saves, in the home directory.
+const CLASPRC_NAME: &str = ".clasprc.json";
+const RECENT_IDS_LIMIT: usize = 20;
+/// The longest remote description `set --description-from-file` accepts, in characters.
+const DESCRIPTION_LIMIT: usize = 2000;
+const CLASP_BIN_ENV: &str = "CLUB_CLASP_BIN";
+const REMOTES_ENV: &str = "CLUB_REMOTES";
+
+/// Examples for `club examples`, keyed by command name, as (what it does, command line) pairs.
+const EXAMPLES: &[(&str, &[(&str, &str)])] = &[
+    (
+        "init",
+        &[
+            (
+                "Set up club, making the manifest's scriptId the main remote",
+                "club init",
+            ),
+            (
+                "Also add a remote for each old manifest in backups/",
+                "club init --from-existing 'backups/*.json'",
+            ),
+        ],
+    ),
+    (
+        "set",
+        &[
+            (
+                "Add or update the staging remote",
+                "club set staging <scriptId>",
+            ),
+            (
+                "Mark prod as protected",
+                "club set prod <scriptId> --protected true",
+            ),
+            ("Pick a recently used script ID", "club set staging --pick"),
+            (
+                "Add remotes from a file of `name scriptId` lines",
+                "club set --stdin < remotes.txt",
+            ),
+        ],
+    ),
+    (
+        "push",
+        &[
+            ("Push to the default remote", "club push"),
+            ("Push to staging", "club push staging"),
+            ("Push to every remote", "club push --all"),
+            (
+                "Push to every remote in the qa group",
+                "club push --group qa",
+            ),
+            (
+                "Record a deploy message",
+                "club push prod -m \"Fix invoice totals\"",
+            ),
+            (
+                "Show the clasp command without running it",
+                "club push staging --print-cmd",
+            ),
+        ],
+    ),
+    (
+        "switch",
+        &[(
+            "Point the manifest at staging for clasp commands",
+            "club switch staging",
+        )],
+    ),
+    (
+        "list",
+        &[
+            (
+                "List remotes, marking the active one",
+                "club list --with-active-marker",
+            ),
+            ("List remotes as JSON", "club list --json"),
+        ],
+    ),
+    (
+        "import",
+        &[
+            (
+                "Merge remotes from a shared file",
+                "club import remotes.json",
+            ),
+            ("Preview an import", "club import remotes.json --dry-run"),
+            (
+                "Import remotes piped in from another tool, in any supported format",
+                "cat remotes | club import --stdin",
+            ),
+        ],
+    ),
+    (
+        "clasp",
+        &[("Tail logs for staging", "club clasp staging -- logs")],
+    ),
+];
+/// The functions `club shell-aliases` defines: each name, the club arguments it runs before any
+/// arguments it's given, and what it's for.
+const SHELL_ALIASES: &[(&str, &str, &str)] = &[
+    ("cpush", "push", "push to a remote, main by default"),
+    ("cpa", "push --all", "push to every remote"),
+    ("cls", "list", "list remotes and their script IDs"),
+    ("csw", "switch", "point the manifest at a remote"),
+    ("cset", "set", "set a remote's script ID"),
+];
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+const REQUIRE_CLEAN_GIT_KEY: &str = "requireCleanGit";
+const GROUPS_KEY: &str = "__groups__";
+const ACTIVE_REMOTE_KEY: &str = "activeRemote";
+const VERSION_KEY: &str = "__version__";
+const RESERVED_REMOTE_NAMES: &[&str] = &[
+    REQUIRE_CLEAN_GIT_KEY,
+    GROUPS_KEY,
+    ACTIVE_REMOTE_KEY,
+    VERSION_KEY,
+];
+/// The `__club__` schema version `club config migrate` upgrades to. A `__club__` without a
+/// `__version__` is version 1. Version 2 only allows remote names of letters, digits, hyphens, and
+/// underscores, where version 1 allowed any name containing one of them.
+const CLUB_SCHEMA_VERSION: u64 = 2;
 
 #[derive(Parser)]
 #[command(
@@ -20,34 +154,564 @@ const MANIFEST_NAME: &str = ".clasp.json";
 struct ClubArgs {
     #[command(subcommand)]
     command: ClubCommand,
+    #[clap(
+        long,
+        global = true,
+        value_parser = parse_path,
+        value_hint = ValueHint::FilePath,
+        help = "Path to the clasp manifest to use. Defaults to .clasp.json in the current directory."
+    )]
+    manifest: Option<PathBuf>,
+    #[clap(
+        short = 'P',
+        long,
+        global = true,
+        conflicts_with = "manifest",
+        help = "Use the .clasp.<PROJECT>.json manifest, falling back to .clasp.json if it does not exist."
+    )]
+    project: Option<String>,
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        help = "When to color output. Overrides the `color` setting in .clubrc."
+    )]
+    color: Option<ColorChoice>,
+    #[clap(
+        short,
+        long,
+        global = true,
+        help = "Only print errors and requested data. Overrides the `quiet` setting in .clubrc."
+    )]
+    quiet: bool,
+    #[clap(
+        long,
+        global = true,
+        value_hint = ValueHint::CommandName,
+        help = "The clasp executable to run. Overrides $CLUB_CLASP_BIN and the `claspBin` setting in .clubrc."
+    )]
+    clasp_bin: Option<String>,
+    #[clap(
+        long,
+        global = true,
+        help = "Print machine-readable JSON instead of text, where supported."
+    )]
+    json: bool,
+    #[clap(
+        long,
+        global = true,
+        requires = "json",
+        help = "With --json, print each JSON document on a single line instead of indented."
+    )]
+    compact: bool,
+    #[clap(
+        long,
+        global = true,
+        conflicts_with = "strict",
+        help = "Accept legacy script IDs of any length, as long as they only contain alphanumeric characters, hyphens, and underscores."
+    )]
+    no_validate_ids: bool,
+    #[clap(
+        long,
+        global = true,
+        help = "Don't take the manifest lock that keeps concurrent club commands from changing the manifest at the same time."
+    )]
+    no_lock: bool,
+    #[clap(
+        long,
+        global = true,
+        help = "Treat warnings, such as duplicate script IDs or a failed after-push hook, as errors. Overrides the `strict` setting in .clubrc."
+    )]
+    strict: bool,
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        default_value = "merge",
+        help = "How remotes from $CLUB_REMOTES combine with the manifest's: merged over them, or used instead of them, in which case commands that change remotes are refused."
+    )]
+    remotes_env_mode: RemotesEnvMode,
+    #[clap(
+        long,
+        global = true,
+        value_name = "PATH",
+        value_parser = parse_path,
+        value_hint = ValueHint::FilePath,
+        help = "Keep remotes in this JSON file instead of under __club__ in the manifest. Overrides the `remoteFile` setting in .clubrc."
+    )]
+    remote_file: Option<PathBuf>,
+    #[clap(
+        long,
+        global = true,
+        help = "Print the manifest a command would write instead of writing it. Commands that run clasp against the manifest are refused."
+    )]
+    no_write: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+enum RemotesEnvMode {
+    #[default]
+    Merge,
+    Override,
 }
 
+// Parsed once per run, so the size of push's many options doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum ClubCommand {
+    Audit(AuditCommand),
+    CheckManifest(CheckManifestCommand),
+    Clasp(ClaspCommand),
+    Completions(CompletionsCommand),
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    Current(CurrentCommand),
+    DiffConfig(DiffConfigCommand),
+    Doctor(DoctorCommand),
+    #[command(long_flag = "dump-config", hide = true)]
+    DumpConfig(DumpConfigCommand),
+    Ensure(EnsureCommand),
+    Env(EnvCommand),
+    Examples(ExamplesCommand),
+    Export(ExportCommand),
+    Gc(GcCommand),
+    #[command(subcommand)]
+    Group(GroupCommand),
     Init(InitCommand),
+    Import(ImportCommand),
     List(ListCommand),
+    Log(LogCommand),
+    MvManifest(MvManifestCommand),
+    NormalizeNames(NormalizeNamesCommand),
+    Open(OpenCommand),
     Push(PushCommand),
+    Reassign(ReassignCommand),
     Remove(RemoveCommand),
     Rename(RenameCommand),
+    Repair(RepairCommand),
     Set(SetCommand),
+    ShellAliases(ShellAliasesCommand),
+    Show(ShowCommand),
+    Status(StatusCommand),
+    Swap(SwapCommand),
+    Switch(SwitchCommand),
+    Touch(TouchCommand),
+    ValidateName(ValidateNameCommand),
+    ValidateUrl(ValidateUrlCommand),
+    Verify(VerifyCommand),
+    Version(VersionCommand),
+    Whoami(WhoamiCommand),
     Login(LoginCommand),
 }
 
+#[derive(Args)]
+#[clap(
+    about = "Report duplicate IDs, off-format IDs, dangling group members, and other remote consistency problems. Never writes."
+)]
+struct AuditCommand {}
+
+#[derive(Args)]
+#[clap(
+    about = "Check that the clasp manifest can be parsed. Exits with a non-zero status if it cannot."
+)]
+struct CheckManifestCommand {}
+
+#[derive(Args)]
+#[clap(
+    about = "Compare this project's remotes against a JSON file mapping remote names to script IDs. Never writes."
+)]
+struct DiffConfigCommand {
+    #[clap(
+        value_parser = parse_path,
+        value_hint = ValueHint::FilePath,
+        help = "The JSON file to compare against."
+    )]
+    file: PathBuf,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Check for common problems, such as a manifest left pointing at another remote or clasp not being installed, and fix what can be fixed with --fix. Exits with a non-zero status if any problem is left unfixed."
+)]
+struct DoctorCommand {
+    #[clap(
+        long,
+        help = "Offer to fix the problems that can be fixed: restoring a swapped scriptId, seeding an empty __club__ from scriptId, and recovering script IDs pasted with whitespace or as URLs."
+    )]
+    fix: bool,
+    #[clap(
+        short,
+        long,
+        requires = "fix",
+        help = "With --fix, fix without asking for confirmation."
+    )]
+    yes: bool,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Print the resolved manifest, settings, and clasp version as JSON, for bug reports. Script IDs are redacted."
+)]
+struct DumpConfigCommand {
+    #[clap(long, help = "Show script IDs in full.")]
+    no_redact: bool,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Remove remotes with invalid script IDs and merge remotes that share a script ID into the first of them."
+)]
+struct GcCommand {
+    #[clap(long, help = "Prune without asking for confirmation.")]
+    yes: bool,
+    #[clap(
+        long,
+        help = "Also prune the default remote and protected remotes, which are otherwise left alone."
+    )]
+    force: bool,
+}
+
+#[derive(Args)]
+#[clap(about = "Print copy-pasteable examples of common club commands.")]
+struct ExamplesCommand {
+    #[clap(help = "Only show examples for this command, e.g. `push`.")]
+    command: Option<String>,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Print the manifest's `__club__` block as JSON: every remote with its settings, the groups, and club's other settings."
+)]
+struct ExportCommand {
+    #[clap(
+        long,
+        help = "Mask each script ID except its last four characters, for sharing the layout publicly. The result can't be imported."
+    )]
+    redact: bool,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Run an arbitrary clasp command against a remote, e.g. `club clasp staging -- logs`."
+)]
+struct ClaspCommand {
+    #[clap(help = "The name of the remote to run clasp against.")]
+    remote: String,
+    #[clap(
+        last = true,
+        required = true,
+        help = "The arguments to pass to clasp, after `--`."
+    )]
+    args: Vec<String>,
+}
+
+#[derive(Args)]
+#[clap(about = "Print a shell completion script, or install it with --install.")]
+struct CompletionsCommand {
+    #[clap(
+        value_enum,
+        help = "The shell to generate completions for. Defaults to the shell in $SHELL."
+    )]
+    shell: Option<Shell>,
+    #[clap(
+        long,
+        help = "Write the script to the shell's conventional completions directory instead of printing it."
+    )]
+    install: bool,
+    #[clap(
+        long,
+        requires = "install",
+        help = "Print where --install would write the script without writing it."
+    )]
+    dry_run: bool,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Print shell functions for common club commands, such as `cpush` for `club push`, to eval in your shell's startup file."
+)]
+struct ShellAliasesCommand {
+    #[clap(
+        value_enum,
+        help = "The shell to generate aliases for. Defaults to the shell in $SHELL."
+    )]
+    shell: Option<Shell>,
+}
+
+#[derive(Args)]
+#[clap(about = "Print the remote the manifest's scriptId currently points at, if any.")]
+struct CurrentCommand {}
+
+#[derive(Args)]
+#[clap(
+    about = "Print shell exports of CLUB_REMOTE and CLUB_SCRIPT_ID, for use with `eval \"$(club env)\"`."
+)]
+struct EnvCommand {
+    #[clap(
+        short,
+        long,
+        help = "The remote to export. Defaults to the active remote, or the default remote."
+    )]
+    remote: Option<String>,
+    #[clap(long, conflicts_with = "powershell", help = "Print fish shell syntax.")]
+    fish: bool,
+    #[clap(long, help = "Print PowerShell syntax.")]
+    powershell: bool,
+}
+
+#[derive(Subcommand)]
+#[clap(about = "Manage named groups of remotes that can be pushed together.")]
+enum GroupCommand {
+    Add(GroupAddCommand),
+    Remove(GroupRemoveCommand),
+    List(GroupListCommand),
+}
+
+#[derive(Args)]
+#[clap(about = "Add remotes to a group, creating the group if it does not exist.")]
+struct GroupAddCommand {
+    #[clap(help = "The name of the group.")]
+    group: String,
+    #[clap(required = true, help = "The remotes to add to the group.")]
+    remotes: Vec<String>,
+}
+
+#[derive(Args)]
+#[clap(about = "Remove remotes from a group. If no remotes are given, the whole group is removed.")]
+struct GroupRemoveCommand {
+    #[clap(help = "The name of the group.")]
+    group: String,
+    #[clap(help = "The remotes to remove from the group.")]
+    remotes: Vec<String>,
+}
+
+#[derive(Args)]
+#[clap(about = "List all groups and their remotes.")]
+struct GroupListCommand {}
+
+#[derive(Subcommand)]
+#[clap(about = "Manage the `__club__` configuration itself.")]
+enum ConfigCommand {
+    Migrate(ConfigMigrateCommand),
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Upgrade the manifest's `__club__` to the current schema version, rewriting its entries in their current form and renaming remotes with off-format names."
+)]
+struct ConfigMigrateCommand {}
+
 #[derive(Args)]
 #[clap(
     about = "Initialize club for a clasp project. The .clasp file must already exist in the directory."
 )]
-struct InitCommand {}
+struct InitCommand {
+    #[clap(
+        long,
+        value_name = "GLOB",
+        help = "Also add a remote for each manifest-like file matching GLOB (e.g. 'backups/*.json'), named after the file"
+    )]
+    from_existing: Option<String>,
+    #[clap(
+        long,
+        help = "Also offer to add a historical-N remote for each script ID the manifest has had in git history"
+    )]
+    detect_remotes: bool,
+    #[clap(
+        long,
+        requires = "detect_remotes",
+        help = "With --detect-remotes, add the detected remotes without asking for confirmation."
+    )]
+    yes: bool,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Import remotes from a JSON, YAML, or TOML file mapping remote names to script IDs."
+)]
+struct ImportCommand {
+    #[clap(
+        value_parser = parse_path,
+        value_hint = ValueHint::FilePath,
+        required_unless_present = "stdin",
+        help = "The file to import remotes from."
+    )]
+    file: Option<PathBuf>,
+    #[clap(
+        long,
+        conflicts_with = "file",
+        help = "Read the remotes from stdin instead of a file."
+    )]
+    stdin: bool,
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = InputFormat::Auto,
+        help = "The format of the remotes. `auto` goes by the file's extension, or by its content if the extension is missing or unknown."
+    )]
+    input_format: InputFormat,
+    #[clap(
+        long,
+        help = "Replace all existing remotes instead of merging into them."
+    )]
+    replace: bool,
+    #[clap(
+        long,
+        help = "Print the changes the import would make without writing them."
+    )]
+    dry_run: bool,
+    #[clap(
+        long,
+        help = "When merging, replace existing remotes whose script ID differs from the file's instead of keeping them."
+    )]
+    overwrite: bool,
+}
+
+#[derive(Args)]
+#[clap(about = "Show the pushes club has made from this project, oldest first.")]
+struct LogCommand {
+    #[clap(
+        long,
+        value_name = "TIME",
+        value_parser = parse_since,
+        help = "Only show pushes after TIME: an RFC 3339 timestamp such as 2024-03-09T17:04:05Z, or a time ago such as 7d, 2h, or 30m."
+    )]
+    since: Option<SystemTime>,
+    #[clap(long, value_name = "NAME", help = "Only show pushes to this remote.")]
+    remote: Option<String>,
+    #[clap(long, help = "Only show pushes that failed.")]
+    failed_only: bool,
+}
 
 #[derive(Args)]
 #[clap(about = "List all remotes and their script IDs.")]
-struct ListCommand {}
+struct ListCommand {
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = ListFormat::Text,
+        conflicts_with = "names_only",
+        help = "The output format."
+    )]
+    format: ListFormat,
+    #[clap(long, help = "Omit the header row from table and CSV output.")]
+    no_header: bool,
+    #[clap(long, help = "Print only remote names.")]
+    names_only: bool,
+    #[clap(
+        long,
+        help = "Only list the remotes the manifest's scriptId currently points at."
+    )]
+    active_only: bool,
+    #[clap(
+        long,
+        help = "Mark the active remote with `*` in text output and an `active` field in JSON output."
+    )]
+    with_active_marker: bool,
+    #[clap(
+        long,
+        conflicts_with_all = ["format", "names_only", "active_only"],
+        help = "Show each group with its member remotes nested beneath it, after the remotes in no group."
+    )]
+    tree: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum InputFormat {
+    Auto,
+    Json,
+    Yaml,
+    Toml,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ListFormat {
+    Text,
+    Table,
+    Csv,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Make sure a remote has a given script ID, setting it only if it's missing or different. Prints `unchanged` or `updated`."
+)]
+struct EnsureCommand {
+    #[clap(help = "The name of the remote.")]
+    name: String,
+    #[clap(help = "The script ID the remote should have.")]
+    id: String,
+    #[clap(
+        long,
+        help = "Only check: report any difference and exit with a non-zero status, without writing."
+    )]
+    check: bool,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Change the script ID of an existing remote, keeping its settings. Fails if the remote does not exist."
+)]
+struct ReassignCommand {
+    #[clap(help = "The name of the remote to reassign.")]
+    name: String,
+    #[clap(help = "The new script ID for the remote.")]
+    id: String,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Exchange the script IDs of two existing remotes, keeping their names, order, and settings."
+)]
+struct SwapCommand {
+    #[clap(help = "The first remote.")]
+    a: String,
+    #[clap(help = "The second remote.")]
+    b: String,
+}
 
 #[derive(Args)]
 #[clap(about = "Remove a remote.")]
 struct RemoveCommand {
     #[clap(help = "The name of the remote to remove.")]
     name: String,
+    #[clap(long, help = "Print the removal without writing it.")]
+    dry_run: bool,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Move the manifest to another directory, updating rootDir so it still points at the same source."
+)]
+struct MvManifestCommand {
+    #[clap(
+        value_parser = parse_path,
+        value_hint = ValueHint::DirPath,
+        help = "The directory to move the manifest into. Created if it doesn't exist."
+    )]
+    new_dir: PathBuf,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Rewrite every remote name in lowercase or uppercase. Nothing is changed if two remotes would end up with the same name."
+)]
+struct NormalizeNamesCommand {
+    #[clap(
+        long,
+        conflicts_with = "upper",
+        required_unless_present = "upper",
+        help = "Lowercase every remote name."
+    )]
+    lower: bool,
+    #[clap(long, help = "Uppercase every remote name.")]
+    upper: bool,
 }
 
 #[derive(Args)]
@@ -57,29 +721,412 @@ struct RenameCommand {
     old_name: String,
     #[clap(help = "The new name for the remote.")]
     new_name: String,
+    #[clap(long, help = "Print the rename without writing it.")]
+    dry_run: bool,
+    #[clap(
+        long,
+        help = "If the new name belongs to another remote, exchange the two remotes' names instead of failing."
+    )]
+    swap: bool,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Restore the manifest's scriptId to the active remote (or main) if an interrupted push left it pointing at another remote."
+)]
+struct RepairCommand {
+    #[clap(short, long, help = "Repair without asking for confirmation.")]
+    yes: bool,
 }
 
 #[derive(Args)]
 #[clap(about = "Set or create a remote with a given name and ID.")]
 struct SetCommand {
-    #[clap(help = "The name of the remote to set.")]
+    #[clap(
+        required_unless_present = "stdin",
+        help = "The name of the remote to set."
+    )]
+    name: Option<String>,
+    #[clap(
+        required_unless_present_any = ["stdin", "pick", "from_url"],
+        help = "The ID of the remote to set."
+    )]
+    id: Option<String>,
+    #[clap(
+        long,
+        conflicts_with_all = ["id", "stdin"],
+        help = "Choose the ID from the script IDs you've recently set or pushed to."
+    )]
+    pick: bool,
+    #[clap(
+        long,
+        value_name = "URL",
+        conflicts_with_all = ["id", "stdin", "pick"],
+        help = "Take the ID from the script's editor or project URL."
+    )]
+    from_url: Option<String>,
+    #[clap(
+        long,
+        conflicts_with_all = ["name", "id"],
+        help = "Read whitespace-separated `name id` lines from stdin and set them all in one write. If any line is invalid, nothing is written."
+    )]
+    stdin: bool,
+    #[clap(
+        long,
+        help = "Mark the remote as protected (true) or unprotected (false). Pushes to protected remotes require confirmation."
+    )]
+    protected: Option<bool>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        value_parser = parse_path,
+        value_hint = ValueHint::FilePath,
+        conflicts_with = "stdin",
+        help = "Set the remote's description to the contents of PATH, which may span several lines. An empty file removes the description."
+    )]
+    description_from_file: Option<PathBuf>,
+    #[clap(
+        long,
+        help = "Before saving, check that clasp can pull the script, and refuse to save an ID it can't reach."
+    )]
+    validate_live: bool,
+    #[clap(
+        long,
+        value_name = "POSITION",
+        value_parser = parse_remote_position,
+        help = "Where to put the remote in the manifest: start, end, before:<remote>, or after:<remote>. New remotes go at the end by default, and existing remotes stay where they are."
+    )]
+    position: Option<RemotePosition>,
+}
+
+#[derive(Args)]
+#[clap(about = "Show everything club knows about one remote.")]
+struct ShowCommand {
+    #[clap(help = "The name of the remote to show.")]
     name: String,
-    #[clap(help = "The ID of the remote to set.")]
-    id: String,
+}
+
+#[derive(Args)]
+#[clap(about = "Show the manifest location, remote count, and active remote.")]
+struct StatusCommand {}
+
+#[derive(Args)]
+#[clap(about = "Point the manifest's scriptId at a remote and remember it as the active remote.")]
+struct SwitchCommand {
+    #[clap(help = "The name of the remote to switch to.")]
+    name: String,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Create a new .clasp.json with club already initialized, for projects that have no manifest yet."
+)]
+struct TouchCommand {
+    #[clap(long, help = "The script ID of the main remote.")]
+    script_id: String,
+    #[clap(
+        long,
+        value_hint = ValueHint::DirPath,
+        help = "The directory clasp pushes from, relative to the manifest."
+    )]
+    root_dir: Option<String>,
+    #[clap(long, help = "Overwrite the manifest if one already exists.")]
+    force: bool,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Print a remote name if club would accept it, or fail explaining why not. Doesn't need a manifest."
+)]
+struct ValidateNameCommand {
+    #[clap(help = "The remote name to check.")]
+    name: String,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Print the script ID in an Apps Script URL, or fail if it doesn't contain a valid one. Doesn't need a manifest."
+)]
+struct ValidateUrlCommand {
+    #[clap(help = "The URL to check, such as the Apps Script editor's.")]
+    url: String,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Print club's version, the version of the clasp it runs, and whether you are logged in to clasp, for bug reports. Doesn't need a manifest."
+)]
+struct VersionCommand {}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum VerifyDepth {
+    /// Ask clasp for each script's deployments, which only shows that it can be reached.
+    Shallow,
+    /// Pull each script and check that it has source files. Slow for many remotes.
+    Full,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Check that clasp can reach each remote's script, without changing the manifest. Exits with a non-zero status if any can't be reached."
+)]
+struct VerifyCommand {
+    #[clap(help = "The remotes to check. Defaults to every remote.")]
+    remotes: Vec<String>,
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = VerifyDepth::Shallow,
+        help = "How thoroughly to check each remote."
+    )]
+    depth: VerifyDepth,
+}
+
+#[derive(Args)]
+#[clap(about = "Open a remote in the Apps Script editor, or its latest web app deployment.")]
+struct OpenCommand {
+    #[clap(help = "The name of the remote to open.")]
+    remote: String,
+    #[clap(
+        long,
+        help = "Open the remote's latest versioned deployment as a web app, found with `clasp deployments`."
+    )]
+    deployment: bool,
+    #[clap(long, help = "Print the URL instead of opening it in a browser.")]
+    print: bool,
 }
 
 #[derive(Args)]
 #[clap(about = "Push to a remote. If no remote is specified, defaults to main.")]
 struct PushCommand {
-    #[clap(help = "The name of the remote to push to.")]
+    #[clap(
+        help = "The name of the remote to push to. Names starting with `-` must be given with --only."
+    )]
     remote: Option<String>,
+    #[clap(
+        short,
+        long,
+        allow_hyphen_values = true,
+        conflicts_with_all = ["remote", "all", "group"],
+        help = "The name of the remote to push to, even if it looks like a flag."
+    )]
+    only: Option<String>,
+    #[clap(
+        long,
+        conflicts_with_all = ["remote", "only", "all", "group"],
+        help = "Push to the remote named after the checked-out git branch, or mapped to it by `branchRemoteMap` in .clubrc."
+    )]
+    remote_from_branch: bool,
+    #[clap(
+        long = "scriptid",
+        value_name = "ID",
+        conflicts_with_all = ["remote", "only", "all", "group", "remote_from_branch"],
+        help = "Push once to this script ID without adding it as a remote."
+    )]
+    script_id: Option<String>,
+    #[clap(
+        long,
+        value_name = "NAME",
+        requires = "script_id",
+        help = "With --scriptid, add the script ID as a remote named NAME once the push succeeds."
+    )]
+    and_register: Option<String>,
     #[clap(short, long, help = "Push to all remotes.")]
     all: bool,
+    #[clap(
+        long,
+        value_name = "PATTERN",
+        conflicts_with_all = ["remote", "only", "all", "group", "remote_from_branch", "script_id"],
+        help = "Push to every remote whose whole name matches this regex. Fails if none match, unless --allow-empty is given."
+    )]
+    remote_regex: Option<String>,
+    #[clap(
+        long,
+        requires = "remote_regex",
+        help = "With --remote-regex, succeed without pushing if no remotes match."
+    )]
+    allow_empty: bool,
+    #[clap(
+        short,
+        long,
+        help = "Push to every remote in a group, continuing past failures."
+    )]
+    group: Option<String>,
+    #[clap(
+        long,
+        help = "With --all, --group, or --remote-regex, start pushing from this remote, skipping the ones before it."
+    )]
+    from: Option<String>,
+    #[clap(
+        long,
+        help = "With --all, --group, or --remote-regex, skip remotes whose names match this glob. May be repeated."
+    )]
+    exclude: Vec<String>,
+    #[clap(
+        long,
+        help = "Push to protected remotes without asking for confirmation."
+    )]
+    confirm_prod: bool,
+    #[clap(
+        long,
+        help = "After pushing, leave the manifest pointing at this remote instead of restoring it."
+    )]
+    restore_to: Option<String>,
+    #[clap(
+        long,
+        help = "Push even if requireCleanGit is set and the git working tree has uncommitted changes."
+    )]
+    dirty_ok: bool,
+    #[clap(
+        long,
+        help = "Fail instead of pushing to the default remote when no remote, --all, or --group is given. Also settable as `requireRemote` in .clubrc."
+    )]
+    require_remote: bool,
+    #[clap(
+        short,
+        long,
+        help = "After each successful push, record this message, the remote, and the time in DEPLOY_INFO in rootDir."
+    )]
+    message: Option<String>,
+    #[clap(
+        long,
+        help = "Write progress to stdout as newline-delimited JSON events, sending clasp's own output to stderr."
+    )]
+    stdout_json: bool,
+    #[clap(
+        long,
+        help = "A shell command to run after each successful push, with CLUB_REMOTE and CLUB_SCRIPT_ID set. Overrides the `afterPush` setting in .clubrc."
+    )]
+    after_push: Option<String>,
+    #[clap(
+        long,
+        help = "Fail the push if the --after-push hook fails, instead of only reporting it. Overrides the `hookFatal` setting in .clubrc."
+    )]
+    hook_fatal: bool,
+    #[clap(
+        long,
+        value_name = "COMMAND",
+        help = "A shell command, such as a build, to run in rootDir before each push, with CLUB_REMOTE and CLUB_SCRIPT_ID set. A remote isn't pushed to if its command fails."
+    )]
+    pre_push: Option<String>,
+    #[clap(
+        long,
+        requires = "pre_push",
+        help = "Run the --pre-push command once, before pushing to any remote, instead of before each push. CLUB_REMOTE and CLUB_SCRIPT_ID aren't set."
+    )]
+    pre_push_once: bool,
+    #[clap(
+        long,
+        conflicts_with = "restore_to",
+        help = "Push every target at once, each from its own temporary copy of rootDir, so the shared manifest is never changed."
+    )]
+    concurrency_safe: bool,
+    #[clap(
+        long,
+        requires = "concurrency_safe",
+        help = "With --concurrency-safe, show each remote's state (queued, running, done, or failed) in a table redrawn as pushes progress. Outside a terminal, each change is logged on its own line."
+    )]
+    concurrent_report: bool,
+    #[clap(
+        long,
+        conflicts_with = "print_cmd",
+        help = "Print each remote the push would target and its script ID, with the active remote marked, and exit without pushing."
+    )]
+    dry_run: bool,
+    #[clap(
+        long,
+        conflicts_with = "concurrency_safe",
+        help = "Print the clasp command each push would run, with its working directory, and exit without running it."
+    )]
+    print_cmd: bool,
+    #[clap(
+        long,
+        help = "Push even if the manifest's rootDir does not exist or is not a directory."
+    )]
+    allow_missing_rootdir: bool,
+    #[clap(
+        long,
+        value_name = "DIR",
+        value_parser = parse_path,
+        value_hint = ValueHint::DirPath,
+        help = "Push from DIR, such as a build's output directory, instead of the manifest's rootDir. The manifest's rootDir is restored afterwards along with its scriptId."
+    )]
+    into: Option<PathBuf>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        value_parser = parse_path,
+        value_hint = ValueHint::FilePath,
+        help = "Write a JSON record of this push to PATH: when it started, and each remote's script ID and result. Written even if pushes fail."
+    )]
+    plan_file: Option<PathBuf>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        value_parser = parse_path,
+        value_hint = ValueHint::FilePath,
+        help = "Write a JUnit XML report of this push to PATH, with a test case per remote and clasp's error output for each failure. Written even if pushes fail."
+    )]
+    report: Option<PathBuf>,
+    #[clap(
+        long,
+        conflicts_with = "concurrency_safe",
+        help = "With --all, carry on past failed pushes instead of stopping at the first, then report every failure."
+    )]
+    keep_going: bool,
+    #[clap(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with = "concurrency_safe",
+        help = "With --all or --group, carry on past failed pushes until N have failed, then skip the remaining remotes. Without it, --all stops at the first failure and --group pushes every remote."
+    )]
+    max_failures: Option<u32>,
+    #[clap(
+        long,
+        conflicts_with = "print_cmd",
+        help = "Pull each target first and refuse to push if it has changes, such as edits made in the Apps Script editor, that weren't in the source club last pushed to it."
+    )]
+    if_clean_remote: bool,
+    #[clap(
+        long,
+        help = "Pass --force to clasp push, skipping clasp's prompt before overwriting the remote's manifest. Club's own checks, such as confirming pushes to protected remotes, still apply."
+    )]
+    force: bool,
+    #[clap(
+        long,
+        conflicts_with_all = ["concurrency_safe", "print_cmd", "dry_run"],
+        help = "Print to stderr how long each push spent writing the manifest, running clasp, and restoring the manifest, with a total."
+    )]
+    profile: bool,
+}
+
+impl PushCommand {
+    /// The clasp arguments for each push.
+    fn clasp_args(&self) -> Vec<&'static str> {
+        let mut args = vec!["push"];
+        if self.force {
+            args.push("--force");
+        }
+        args
+    }
 }
 
 #[derive(Args)]
 #[clap(about = "Launches the clasp login command.")]
-struct LoginCommand {}
+struct LoginCommand {
+    #[clap(
+        long,
+        help = "Report the account you're logged in as instead of logging in again. Same as `club whoami`."
+    )]
+    status: bool,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Print the clasp account you're logged in as and whether its credentials look usable. Fails if you aren't logged in. Doesn't need a manifest."
+)]
+struct WhoamiCommand {}
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 struct RemoteName(String);
@@ -87,35 +1134,238 @@ struct RemoteName(String);
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 struct RemoteId(String);
 
+/// A configured remote: its script ID plus any per-remote settings. Remotes without settings are
+/// stored in the manifest as a bare script ID string.
+#[derive(Debug, PartialEq, Clone)]
+struct Remote {
+    id: RemoteId,
+    protected: bool,
+    /// Free-form notes about the remote, which may span several lines.
+    description: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 struct ClaspConfig {
     root_dir: String,
     script_id: String, // script_id is not a RemoteId because we don't necessarily trust it
     parent_ids: Vec<String>,
-    club_remotes: Option<IndexMap<RemoteName, RemoteId>>,
+    club_remotes: Option<IndexMap<RemoteName, Remote>>,
+    club_groups: IndexMap<String, Vec<RemoteName>>,
+    club_settings: ClubSettings,
+    /// Set when remotes came from `CLUB_REMOTES`, so that writing the config back doesn't persist
+    /// them in the manifest.
+    env_remotes: Option<EnvRemotes>,
+    /// The file the `__club__` map is kept in instead of the manifest, if any.
+    remote_file: Option<PathBuf>,
+    /// Set by `push --into`: the rootDir to push from while the manifest points at a remote. The
+    /// manifest's own rootDir is what gets restored.
+    push_root_dir: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct EnvRemotes {
+    mode: RemotesEnvMode,
+    remotes: IndexMap<RemoteName, Remote>,
+    /// The manifest's own remotes, as read before the environment's were applied.
+    manifest_remotes: Option<IndexMap<RemoteName, Remote>>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ClubSettings {
+    require_clean_git: bool,
+    active_remote: Option<RemoteName>,
+    /// The schema version recorded in `__version__`, if any, kept as-is until `config migrate`.
+    /// Configs club creates start at `CLUB_SCHEMA_VERSION`.
+    version: Option<u64>,
+}
+
+/// Project-level defaults, resolved from built-in defaults, then `.clubrc`, then command-line flags.
+#[derive(Debug, Clone)]
+struct Settings {
+    color: ColorChoice,
+    quiet: bool,
+    clasp_bin: String,
+    default_remote: RemoteName,
+    require_remote: bool,
+    after_push: Option<String>,
+    hook_fatal: bool,
+    strict: bool,
+    json: bool,
+    compact_json: bool,
+    validate_ids: bool,
+    lock: bool,
+    /// Set for `push --stdout-json`, so that clasp's output is sent to stderr and stdout carries only
+    /// the event stream.
+    clasp_output_to_stderr: bool,
+    /// Set for `push --report`, so that a failed clasp's stderr ends up in the error that's reported.
+    capture_clasp_stderr: bool,
+    /// Set while `push --concurrent-report` redraws its table, so that clasp's output doesn't break
+    /// it up. A failed clasp's stderr is still kept for the error when it's captured.
+    silence_clasp: bool,
+    /// Set for multi-remote pushes on a terminal, which redraw a single `[n/total]` progress line
+    /// in place. Clasp is silenced for these as well, since its output would break the line up.
+    progress_in_place: bool,
+    /// The file recording recently set and pushed script IDs for `set --pick`, or None to keep no
+    /// history.
+    recent_ids: Option<PathBuf>,
+    /// Whether pushes are recorded in the project's push history for `club log`, from the
+    /// `history` setting.
+    history: bool,
+    /// Whether every push records what it sent for `push --if-clean-remote`, from the
+    /// `trackPushes` setting. Without it, only pushes with the flag do.
+    track_pushes: bool,
+    /// The contents of `CLUB_REMOTES`, if set.
+    remotes_env: Option<String>,
+    remotes_env_mode: RemotesEnvMode,
+    /// The file to keep remotes in instead of the manifest, from `--remote-file` or `remoteFile`.
+    remote_file: Option<PathBuf>,
+    no_write: bool,
+    /// Remotes for `push --remote-from-branch` to use for branches not named after them.
+    branch_remote_map: IndexMap<String, RemoteName>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            color: ColorChoice::Auto,
+            quiet: false,
+            clasp_bin: "clasp".to_string(),
+            default_remote: RemoteName("main".to_string()),
+            require_remote: false,
+            after_push: None,
+            hook_fatal: false,
+            strict: false,
+            json: false,
+            compact_json: false,
+            validate_ids: true,
+            lock: true,
+            clasp_output_to_stderr: false,
+            capture_clasp_stderr: false,
+            silence_clasp: false,
+            progress_in_place: false,
+            recent_ids: None,
+            history: false,
+            track_pushes: false,
+            remotes_env: None,
+            remotes_env_mode: RemotesEnvMode::Merge,
+            remote_file: None,
+            no_write: false,
+            branch_remote_map: IndexMap::new(),
+        }
+    }
+}
+
+/// State for a single command run: the manifest's path and its contents, parsed on first use so that
+/// a command reads the manifest at most once.
+#[derive(Debug)]
+struct Context {
+    manifest_path: PathBuf,
+    validate_ids: bool,
+    config: OnceCell<ClaspConfig>,
+    #[cfg(test)]
+    manifest_reads: std::cell::Cell<usize>,
+    remotes_env: Option<(String, RemotesEnvMode)>,
+    remote_file: Option<PathBuf>,
+    no_write: bool,
+    strict: bool,
 }
 
 #[derive(Debug)]
 enum ClubError {
     ManifestNotFound,
     ManifestReadFail(String),
+    ManifestParseError(usize, usize, String),
+    ManifestShapeError(String),
+    RemotesEnvInvalid(String),
+    RemotesEnvOverride,
+    NoWriteUnsupported,
+    RemoteFileError(PathBuf, String),
     ManifestWriteFail(String),
+    PushedStateWriteFail(String),
+    HistoryWriteFail(String),
+    SettingsReadFail(String),
+    JsonWriteFail(String),
     ClubNotSetup,
+    ConfigVersionTooNew(u64),
     ClubAlreadySetup,
+    ManifestExists(PathBuf),
     RemoteNotFound,
     RemoteAlreadyExists,
+    RemoteDrift(RemoteName, Option<RemoteId>, RemoteId),
+    NameCollision(Vec<(Vec<RemoteName>, RemoteName)>),
     InvalidRemoteName,
+    ReservedRemoteName,
     InvalidRemoteId,
+    InvalidScriptUrl(String),
+    DescriptionReadFail(PathBuf, String),
+    DescriptionTooLong(usize),
+    DescriptionControlChar(char),
     NoRemotesAvailable,
     BothRemoteAndAllPassed,
+    BothRemoteAndGroupPassed,
+    BothAllAndGroupPassed,
+    FilterWithoutMultiPush(&'static str),
+    InvalidRemoteRegex(String, String),
+    NoRemotesMatch(String),
+    RemoteRequired,
+    NoRemoteChosen,
+    NoRecentIds,
+    NoScriptIdChosen,
+    ImportReadFail(String),
+    InvalidImportEntry(String, Box<ClubError>),
+    InvalidRemoteOp(String, Box<ClubError>),
+    PositionAnchorNotFound(RemoteName),
+    PositionAnchorMoved(RemoteName),
+    InvalidBatchLine(usize, String),
+    DirtyWorkingTree,
+    NoGitBranch(PathBuf),
+    NoGitHistory(PathBuf),
+    NoRemoteForBranch(String, RemoteName),
+    ProtectedRemoteNotConfirmed(Vec<RemoteName>),
+    GcNotConfirmed,
+    GroupNotFound,
+    DanglingGroupMember(String, RemoteName),
+    PushFailed(Vec<RemoteName>),
+    PushPanicked(String),
+    PushStopped(Vec<RemoteName>, Vec<RemoteName>),
+    HookFailed(RemoteName, String),
+    PrePushFailed(Option<RemoteName>, String),
+    PrePushPerRemoteConcurrently,
+    IsolatedPushNeedsRootDir,
+    IsolatedPushFail(RemoteName, String),
+    RootDirMissing(PathBuf),
+    IntoDirMissing(PathBuf),
+    MoveTargetExists(PathBuf),
+    ManifestMoveFail(String),
     ClaspError(String),
+    RemoteDiverged(Vec<(RemoteName, Vec<String>)>),
+    ScriptUnreachable(RemoteName, RemoteId),
+    ScriptDirFail(String),
+    VerifyFailed(Vec<RemoteName>),
+    NoWebAppDeployment(RemoteName),
+    BrowserOpenFail(String),
+    ClaspNotInstalled(String),
+    NotLoggedIn,
+    StrictWarning(String),
+    DeployInfoWriteFail(String),
+    PlanFileWriteFail(String),
+    ReportWriteFail(String),
+    UnknownShell,
+    NoExamples(String),
+    CompletionInstallUnsupported(Shell),
+    CompletionInstallFail(String),
+    ShellAliasesUnsupported(Shell),
+    ManifestLocked(PathBuf),
+    LockFail(String),
+    DoctorProblemsLeft(usize),
 }
 
 impl TryFrom<String> for RemoteId {
     type Error = ClubError;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        let re = Regex::new(r"[a-zA-Z0-9-_]{57}").unwrap();
+        // Anchored so that a valid 57-character run can't smuggle in other characters around it.
+        let re = Regex::new(r"^[A-Za-z0-9_-]{57}$").unwrap();
         if re.is_match(&value) {
             Ok(RemoteId(value))
         } else {
@@ -124,12 +1374,59 @@ impl TryFrom<String> for RemoteId {
     }
 }
 
+impl RemoteId {
+    /// Parses a script ID, strictly unless `validate` is off. Then legacy IDs of any length are
+    /// accepted as long as they use the same characters; see `warn_if_legacy_id`.
+    fn parse(value: String, validate: bool) -> Result<Self, ClubError> {
+        if validate {
+            return RemoteId::try_from(value);
+        }
+        let re = Regex::new(r"^[A-Za-z0-9_-]+$").unwrap();
+        if !re.is_match(&value) {
+            return Err(ClubError::InvalidRemoteId);
+        }
+        Ok(RemoteId(value))
+    }
+
+    /// Extracts a script ID from an Apps Script URL: an editor URL (`.../d/<id>/edit`), a project
+    /// URL (`.../projects/<id>`), or a library deployment URL (`.../macros/library/d/<id>/1`). Web
+    /// app URLs (`.../macros/s/<id>/exec`) name a deployment rather than the script, so they fail.
+    fn from_url(url: &str) -> Result<RemoteId, ClubError> {
+        let re = Regex::new(r"/(?:d|projects)/([A-Za-z0-9_-]+)").unwrap();
+        let id = re
+            .captures_iter(url)
+            .find_map(|caps| RemoteId::try_from(caps[1].to_string()).ok());
+        id.ok_or_else(|| ClubError::InvalidScriptUrl(url.to_string()))
+    }
+}
+
+impl RemoteName {
+    /// Parses the name for a remote being created or renamed, which must only use letters, digits,
+    /// hyphens, and underscores. Names of existing remotes are parsed leniently with `try_from`.
+    fn parse_new(value: String) -> Result<Self, ClubError> {
+        let remote_name = RemoteName::try_from(value)?;
+        if remote_name.is_well_formed() {
+            Ok(remote_name)
+        } else {
+            Err(ClubError::InvalidRemoteName)
+        }
+    }
+
+    fn is_well_formed(&self) -> bool {
+        Regex::new(r"^[a-zA-Z0-9_-]+$").unwrap().is_match(&self.0)
+    }
+}
+
 impl TryFrom<String> for RemoteName {
     type Error = ClubError;
 
+    /// Parses the name of a remote that may already be in a manifest. This accepts anything older
+    /// versions of club did, so that manifests they wrote can still be read.
     fn try_from(value: String) -> Result<Self, Self::Error> {
         let re = Regex::new(r"[a-zA-Z0-9-_]+").unwrap();
-        if re.is_match(&value) {
+        if RESERVED_REMOTE_NAMES.contains(&value.as_str()) {
+            Err(ClubError::ReservedRemoteName)
+        } else if re.is_match(&value) {
             Ok(RemoteName(value))
         } else {
             Err(ClubError::InvalidRemoteName)
@@ -144,19 +1441,95 @@ impl Display for ClubError {
                 write!(f, "No clasp manifest found. Are you in a clasp project?")
             }
             ClubError::ManifestReadFail(err) => write!(f, "Error reading clasp manifest: {}", err),
+            ClubError::ManifestParseError(line, column, err) => write!(f, "Clasp manifest is not valid JSON (line {}, column {}): {}", line, column, err),
+            ClubError::ManifestShapeError(err) => write!(f, "Clasp manifest is valid JSON but not a valid clasp manifest: {}", err),
+            ClubError::RemotesEnvInvalid(err) => write!(f, "{} is not a valid `__club__` map: {}", REMOTES_ENV, err),
+            ClubError::RemoteFileError(path, err) => write!(f, "Error reading remote file {}: {}", path.display(), err),
+            ClubError::NoWriteUnsupported => write!(f, "--no-write can't preview commands that run clasp against the manifest. Use push --dry-run or --print-cmd to preview a push."),
+            ClubError::RemotesEnvOverride => write!(f, "Remotes come from {} with --remotes-env-mode override, so this command can't change them. Use --remotes-env-mode merge to write to the manifest.", REMOTES_ENV),
             ClubError::ClubNotSetup => write!(
                 f,
                 "Club is not set up for this project. Run `club init` to set up club."
             ),
+            ClubError::ConfigVersionTooNew(version) => write!(f, "The manifest's __club__ is version {}, but this club only knows up to version {}. Upgrade club to read it.", version, CLUB_SCHEMA_VERSION),
             ClubError::RemoteNotFound => write!(f, "Remote not found."),
             ClubError::RemoteAlreadyExists => write!(f, "New remote name already exists. Remove or rename it first."),
+            ClubError::RemoteDrift(remote_name, Some(current), expected) => write!(f, "{} is set to {}, not {}.", remote_name, current, expected),
+            ClubError::RemoteDrift(remote_name, None, expected) => write!(f, "{} does not exist; expected it to be set to {}.", remote_name, expected),
+            ClubError::NameCollision(collisions) => write!(f, "Some remotes would end up with the same name, so nothing was renamed: {}", collisions.iter().map(|(names, name)| format!("{} -> {}", names.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(", "), name)).collect::<Vec<_>>().join("; ")),
             ClubError::InvalidRemoteName => write!(f, "Invalid remote name. Remote names must be alphanumeric and may contain hyphens and underscores."),
+            ClubError::ReservedRemoteName => write!(f, "Invalid remote name. The names {} are reserved for club settings.", RESERVED_REMOTE_NAMES.join(", ")),
+            ClubError::DescriptionReadFail(path, err) => write!(f, "Failed to read the description from {}: {}", path.display(), err),
+            ClubError::DescriptionTooLong(length) => write!(f, "The description is {} characters long. Descriptions can be at most {} characters.", length, DESCRIPTION_LIMIT),
+            ClubError::DescriptionControlChar(c) => write!(f, "The description contains the control character {:?}. Only newlines are allowed.", c),
+            ClubError::InvalidScriptUrl(url) => write!(f, "No valid script ID found in {}. Use the URL of the Apps Script editor (.../projects/<id>/edit) or a library (.../macros/library/d/<id>/1); web app URLs name a deployment, not the script.", url),
             ClubError::InvalidRemoteId => write!(f, "Invalid remote id. Remote IDs are always 57 characters long and contain only alphanumeric characters, hyphens, and underscores."),
             ClubError::ClubAlreadySetup => write!(f, "Club is already set up for this project."),
+            ClubError::ManifestExists(path) => write!(f, "{} already exists. Run `club init` to set up club for it, or pass --force to replace it.", path.display()),
             ClubError::ManifestWriteFail(err) => write!(f, "Error writing clasp manifest: {}", err),
+            ClubError::HistoryWriteFail(err) => write!(f, "Error recording the push in {}: {}", HISTORY_NAME, err),
+            ClubError::PushedStateWriteFail(err) => write!(f, "Error recording what was pushed in {}: {}", PUSHED_STATE_NAME, err),
+            ClubError::JsonWriteFail(err) => write!(f, "Error writing JSON output: {}", err),
+            ClubError::SettingsReadFail(err) => write!(f, "Error reading {}: {}", SETTINGS_NAME, err),
             ClubError::NoRemotesAvailable => write!(f, "No remotes exist. Run `club set` to add a remote."),
             ClubError::BothRemoteAndAllPassed => write!(f, "Cannot pass both a remote and the --all flag."),
+            ClubError::BothRemoteAndGroupPassed => write!(f, "Cannot pass both a remote and the --group option."),
+            ClubError::BothAllAndGroupPassed => write!(f, "Cannot pass both the --all flag and the --group option."),
+            ClubError::FilterWithoutMultiPush(flag) => write!(f, "{} can only be used with --all, --group, or --remote-regex.", flag),
+            ClubError::InvalidRemoteRegex(pattern, err) => write!(f, "Invalid --remote-regex `{}`: {}", pattern, err),
+            ClubError::NoRemotesMatch(pattern) => write!(f, "No remotes to push to: none match --remote-regex `{}` after --from and --exclude. Pass --allow-empty if that's expected.", pattern),
+            ClubError::NoRemoteChosen => write!(f, "No remote chosen. Pass a remote name to push without being asked."),
+            ClubError::NoRecentIds => write!(f, "No recently used script IDs to pick from. Pass the script ID explicitly."),
+            ClubError::NoScriptIdChosen => write!(f, "No script ID chosen. Pass the script ID explicitly to set it without being asked."),
+            ClubError::RemoteRequired => write!(f, "No remote given and requireRemote is set. Name a remote, or pass --all or --group."),
+            ClubError::ImportReadFail(err) => write!(f, "Error reading import file: {}", err),
+            ClubError::InvalidImportEntry(name, err) => write!(f, "Invalid import entry `{}`: {}", name, err),
+            ClubError::PositionAnchorNotFound(remote_name) => write!(f, "Can't place the remote next to {}, which isn't a remote.", remote_name),
+            ClubError::PositionAnchorMoved(remote_name) => write!(f, "Can't place {} next to itself. Use a remote that isn't being set.", remote_name),
+            ClubError::InvalidRemoteOp(op, err) => write!(f, "Couldn't {} (no remotes were changed): {}", op, err),
+            ClubError::InvalidBatchLine(line, err) => write!(f, "Invalid input on line {} (no remotes were set): {}", line, err),
+            ClubError::NoGitHistory(path) => write!(f, "--detect-remotes reads the manifest's git history, but {} is not tracked in a git repository.", path.display()),
+            ClubError::NoGitBranch(dir) => write!(f, "--remote-from-branch needs a checked-out git branch, but {} is outside git or on a detached HEAD.", dir.display()),
+            ClubError::NoRemoteForBranch(branch, remote_name) => write!(f, "There is no remote {} for the git branch {}. Add it with `club set {} <script-id>`, or map the branch to a remote with `branchRemoteMap` in .clubrc.", remote_name, branch, remote_name),
+            ClubError::DirtyWorkingTree => write!(f, "The git working tree has uncommitted changes and requireCleanGit is set. Commit or stash them, or pass --dirty-ok to push anyway."),
+            ClubError::ProtectedRemoteNotConfirmed(remotes) => write!(f, "Refusing to push to protected remote(s) {} without confirmation. Pass --confirm-prod to push anyway.", remotes.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(", ")),
+            ClubError::GcNotConfirmed => write!(f, "Nothing was pruned. Pass --yes to prune without being asked."),
+            ClubError::GroupNotFound => write!(f, "Group not found."),
+            ClubError::DanglingGroupMember(group, remote) => write!(f, "Group `{}` references remote `{}`, which does not exist.", group, remote),
+            ClubError::PushFailed(remotes) => write!(f, "Failed to push to: {}", remotes.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(", ")),
+            ClubError::PushPanicked(err) => write!(f, "Club crashed while pushing: {}", err),
+            ClubError::PushStopped(failed, skipped) => write!(f, "Failed to push to: {}. Stopped after {} failures, skipping: {}", failed.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(", "), failed.len(), skipped.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(", ")),
             ClubError::ClaspError(err) => write!(f, "Error running clasp: {}", err),
+            ClubError::VerifyFailed(remote_names) => write!(f, "Verification failed for {}.", remote_names.iter().map(|name| name.0.as_str()).collect::<Vec<_>>().join(", ")),
+            ClubError::ScriptDirFail(err) => write!(f, "Error preparing a temporary directory for clasp: {}", err),
+            ClubError::ScriptUnreachable(remote_name, remote_id) => write!(f, "clasp couldn't pull script {}, so it wasn't saved as {}. Check the ID and that your clasp login can open the script.", remote_id, remote_name),
+            ClubError::RemoteDiverged(diverged) => write!(f, "Refusing to push, since these remotes have changes that weren't pushed by club, such as edits in the Apps Script editor: {}. Pull them first, or push without --if-clean-remote to overwrite them.", diverged.iter().map(|(remote, files)| format!("{} ({})", remote, files.join(", "))).collect::<Vec<_>>().join("; ")),
+            ClubError::NoWebAppDeployment(remote) => write!(f, "{} has no versioned deployment to open as a web app. Deploy one with `club clasp {} deploy`, then try again.", remote, remote),
+            ClubError::BrowserOpenFail(err) => write!(f, "Error opening a browser: {}. Pass --print to print the URL instead.", err),
+            ClubError::StrictWarning(message) => write!(f, "{} (--strict is set, so this is an error)", message),
+            ClubError::NotLoggedIn => write!(f, "You are not logged in to clasp. Run `club login` to log in."),
+            ClubError::ClaspNotInstalled(clasp_bin) => write!(f, "clasp not found (tried `{}`); install with `npm i -g @google/clasp` or set {}.", clasp_bin, CLASP_BIN_ENV),
+            ClubError::PrePushFailed(Some(remote), err) => write!(f, "Pre-push command failed for {}, so it wasn't pushed: {}", remote, err),
+            ClubError::PrePushFailed(None, err) => write!(f, "Pre-push command failed, so nothing was pushed: {}", err),
+            ClubError::PrePushPerRemoteConcurrently => write!(f, "--pre-push can't run before each push while --concurrency-safe pushes all at once. Add --pre-push-once to run it once first."),
+            ClubError::HookFailed(remote, err) => write!(f, "After-push hook failed for {}: {}", remote, err),
+            ClubError::MoveTargetExists(path) => write!(f, "{} already exists, so the manifest wasn't moved.", path.display()),
+            ClubError::ManifestMoveFail(err) => write!(f, "Failed to move the manifest: {}", err),
+            ClubError::IntoDirMissing(path) => write!(f, "--into {} does not exist or is not a directory.", path.display()),
+            ClubError::RootDirMissing(path) => write!(f, "rootDir {} does not exist or is not a directory. Fix rootDir in the manifest, or pass --allow-missing-rootdir to push anyway.", path.display()),
+            ClubError::IsolatedPushNeedsRootDir => write!(f, "--concurrency-safe copies rootDir for each push, so it needs the manifest's rootDir to name a directory inside the project rather than the project itself."),
+            ClubError::IsolatedPushFail(remote, err) => write!(f, "Error preparing an isolated push to {}: {}", remote, err),
+            ClubError::ManifestLocked(path) => write!(f, "Another club command is changing the manifest. If none is running, delete the stale lock file {}.", path.display()),
+            ClubError::LockFail(err) => write!(f, "Error locking clasp manifest: {}", err),
+            ClubError::DoctorProblemsLeft(count) => write!(f, "{} problem(s) left unfixed.", count),
+            ClubError::NoExamples(command) => write!(f, "No examples for `{}`. Run `club examples` to see them all.", command),
+            ClubError::UnknownShell => write!(f, "Could not detect your shell from $SHELL. Pass it explicitly, e.g. `club completions zsh`."),
+            ClubError::CompletionInstallUnsupported(shell) => write!(f, "--install does not support {}. Run `club completions {}` and install the script manually.", shell, shell),
+            ClubError::ShellAliasesUnsupported(shell) => write!(f, "shell-aliases does not support {}. Pick bash, zsh, fish, or powershell.", shell),
+            ClubError::CompletionInstallFail(err) => write!(f, "Error installing completions: {}", err),
+            ClubError::PlanFileWriteFail(err) => write!(f, "Error writing plan file: {}", err),
+            ClubError::ReportWriteFail(err) => write!(f, "Error writing push report: {}", err),
+            ClubError::DeployInfoWriteFail(err) => write!(f, "Error writing {}: {}", DEPLOY_INFO_NAME, err),
         }
     }
 }
@@ -173,304 +1546,11173 @@ impl Display for RemoteId {
     }
 }
 
-impl TryFrom<Value> for ClaspConfig {
-    type Error = ClubError;
+impl From<RemoteId> for Remote {
+    fn from(id: RemoteId) -> Self {
+        Remote {
+            id,
+            protected: false,
+            description: None,
+        }
+    }
+}
 
-    fn try_from(value: Value) -> Result<Self, Self::Error> {
-        let root_dir = value["rootDir"]
-            .as_str()
-            .ok_or(ClubError::ManifestReadFail("rootDir not found".to_string()))?;
-        let script_id = value["scriptId"]
-            .as_str()
-            .ok_or(ClubError::ManifestReadFail(
-                "scriptId not found".to_string(),
-            ))?;
-        let parent_ids: Vec<String> = value["parentId"]
-            .as_array()
-            .ok_or(ClubError::ManifestReadFail(
-                "parentId not found".to_string(),
-            ))?
-            .iter()
-            .map(|id| {
-                id.as_str()
-                    .ok_or(ClubError::ManifestReadFail(
-                        "parentId not found".to_string(),
-                    ))
-                    .map(|str| str.to_string())
-            })
-            .collect::<Result<Vec<String>, ClubError>>()?;
-        let club_remotes = value["__club__"].as_object().map(|remotes| {
-            let mut remote_map = IndexMap::new();
-            for (key, value) in remotes {
-                remote_map.insert(
-                    RemoteName::try_from(key.to_string()).unwrap(),
-                    RemoteId::try_from(
-                        value
-                            .as_str()
-                            .expect("Remote ID should be a string")
-                            .to_string(),
-                    )
-                    .unwrap(),
-                );
+impl Remote {
+    /// Parses a remote as stored in the manifest: a bare script ID or an object with settings.
+    fn parse(value: &Value, validate_ids: bool) -> Result<Self, ClubError> {
+        match value {
+            Value::String(id) => Ok(Remote::from(RemoteId::parse(id.clone(), validate_ids)?)),
+            Value::Object(fields) => {
+                let id = fields
+                    .get("id")
+                    .and_then(Value::as_str)
+                    .ok_or(ClubError::InvalidRemoteId)?;
+                Ok(Remote {
+                    id: RemoteId::parse(id.to_string(), validate_ids)?,
+                    protected: fields
+                        .get("protected")
+                        .and_then(Value::as_bool)
+                        .unwrap_or(false),
+                    description: fields
+                        .get("description")
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                })
             }
-            remote_map
-        });
-        Ok(ClaspConfig {
-            root_dir: root_dir.to_string(),
-            script_id: script_id.to_string(),
-            parent_ids,
-            club_remotes,
-        })
+            _ => Err(ClubError::InvalidRemoteId),
+        }
     }
 }
 
-impl TryFrom<ClaspConfig> for Value {
-    type Error = ClubError;
+impl From<&Remote> for Value {
+    fn from(remote: &Remote) -> Self {
+        if !remote.protected && remote.description.is_none() {
+            return Value::String(remote.id.0.clone());
+        }
+        let mut fields = serde_json::Map::new();
+        fields.insert("id".to_string(), Value::String(remote.id.0.clone()));
+        if remote.protected {
+            fields.insert("protected".to_string(), Value::Bool(true));
+        }
+        if let Some(description) = &remote.description {
+            fields.insert(
+                "description".to_string(),
+                Value::String(description.clone()),
+            );
+        }
+        Value::Object(fields)
+    }
+}
 
-    fn try_from(config: ClaspConfig) -> Result<Self, Self::Error> {
-        let mut json = serde_json::json!({
-            "rootDir": config.root_dir,
-            "scriptId": config.script_id,
-            "parentId": config.parent_ids,
-        });
-        if let Some(remotes) = config.club_remotes {
-            let mut remotes_json = serde_json::json!({});
-            for (key, value) in remotes {
-                remotes_json[key.0] = Value::String(value.0);
+/// One change in a batch of changes to a manifest's remotes, applied with `apply_remote_ops`.
+#[derive(Debug, Clone, PartialEq)]
+enum RemoteOp {
+    /// Adds a remote, or changes an existing remote's script ID and keeps its settings.
+    Set(RemoteName, RemoteId),
+    Remove(RemoteName),
+    /// Renames a remote, moving it to the end like `club rename` does.
+    Rename(RemoteName, RemoteName),
+}
+
+impl Display for RemoteOp {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            RemoteOp::Set(remote_name, remote_id) => {
+                write!(f, "set {} to {}", remote_name, remote_id)
+            }
+            RemoteOp::Remove(remote_name) => write!(f, "remove {}", remote_name),
+            RemoteOp::Rename(old_name, new_name) => {
+                write!(f, "rename {} to {}", old_name, new_name)
             }
-            json["__club__"] = remotes_json;
         }
-        Ok(json)
     }
 }
 
-fn get_project_dir() -> Result<PathBuf, ClubError> {
-    Ok(std::env::current_dir().map_err(|e| ClubError::ManifestReadFail(e.to_string()))?)
+/// Where `set --position` puts a remote among the others.
+#[derive(Debug, Clone, PartialEq)]
+enum RemotePosition {
+    Start,
+    End,
+    Before(RemoteName),
+    After(RemoteName),
 }
 
-fn get_manifest_path() -> Result<PathBuf, ClubError> {
-    Ok(get_project_dir()?.join(MANIFEST_NAME))
+fn parse_remote_position(value: &str) -> Result<RemotePosition, String> {
+    let anchor = |name: &str| RemoteName::try_from(name.to_string()).map_err(|e| e.to_string());
+    match value.split_once(':') {
+        None if value == "start" => Ok(RemotePosition::Start),
+        None if value == "end" => Ok(RemotePosition::End),
+        Some(("before", name)) => Ok(RemotePosition::Before(anchor(name)?)),
+        Some(("after", name)) => Ok(RemotePosition::After(anchor(name)?)),
+        _ => Err("expected start, end, before:<remote>, or after:<remote>".to_string()),
+    }
 }
 
-fn get_clasp_config() -> Result<ClaspConfig, ClubError> {
-    let manifest_path = get_manifest_path()?;
-
-    if !manifest_path.exists() {
-        return Err(ClubError::ManifestNotFound);
+/// Moves `names`, which must all be remotes, to `position`, keeping them in the order given.
+fn place_remotes(
+    remotes: &mut IndexMap<RemoteName, Remote>,
+    names: &[RemoteName],
+    position: &RemotePosition,
+) -> Result<(), ClubError> {
+    // The index a remote at `from` ends up at when it's moved to just after the one at `anchor`.
+    let after = |from: usize, anchor: usize| if from > anchor { anchor + 1 } else { anchor };
+    let mut previous: Option<usize> = None;
+    for (placed, remote_name) in names.iter().enumerate() {
+        let from = remotes
+            .get_index_of(remote_name)
+            .ok_or(ClubError::RemoteNotFound)?;
+        let to = match (previous, position) {
+            (Some(previous), _) => after(from, previous),
+            (None, RemotePosition::Start) => placed,
+            (None, RemotePosition::End) => remotes.len() - 1,
+            (None, RemotePosition::Before(anchor_name) | RemotePosition::After(anchor_name)) => {
+                if names.contains(anchor_name) {
+                    return Err(ClubError::PositionAnchorMoved(anchor_name.clone()));
+                }
+                let anchor = remotes
+                    .get_index_of(anchor_name)
+                    .ok_or_else(|| ClubError::PositionAnchorNotFound(anchor_name.clone()))?;
+                match position {
+                    RemotePosition::Before(_) if from > anchor => anchor,
+                    RemotePosition::Before(_) => anchor - 1,
+                    _ => after(from, anchor),
+                }
+            }
+        };
+        remotes.move_index(from, to);
+        previous = Some(to);
     }
-    let manifest_str = std::fs::read_to_string(&manifest_path)
-        .map_err(|e| ClubError::ManifestReadFail(e.to_string()))?;
-    let manifest_json: Value = serde_json::from_str(&manifest_str)
-        .map_err(|e| ClubError::ManifestReadFail(e.to_string()))?;
+    Ok(())
+}
 
-    Ok(ClaspConfig::try_from(manifest_json)?)
+/// Checks that every op in a batch applies to the remotes as they'll be when its turn comes, so
+/// that a batch either applies in full or not at all.
+fn validate_remote_ops(
+    remotes: &IndexMap<RemoteName, Remote>,
+    ops: &[RemoteOp],
+) -> Result<(), ClubError> {
+    let mut names: std::collections::HashSet<&RemoteName> = remotes.keys().collect();
+    for op in ops {
+        let problem = match op {
+            RemoteOp::Set(remote_name, _) => {
+                names.insert(remote_name);
+                None
+            }
+            RemoteOp::Remove(remote_name) => {
+                (!names.remove(remote_name)).then_some(ClubError::RemoteNotFound)
+            }
+            RemoteOp::Rename(old_name, new_name) => {
+                if !names.contains(old_name) {
+                    Some(ClubError::RemoteNotFound)
+                } else if names.contains(new_name) {
+                    Some(ClubError::RemoteAlreadyExists)
+                } else {
+                    names.remove(old_name);
+                    names.insert(new_name);
+                    None
+                }
+            }
+        };
+        if let Some(err) = problem {
+            return Err(ClubError::InvalidRemoteOp(op.to_string(), Box::new(err)));
+        }
+    }
+    Ok(())
 }
 
-fn write_clasp_config(config: ClaspConfig) -> Result<(), ClubError> {
-    let manifest_path = get_manifest_path()?;
-    let json_str = Value::try_from(config).and_then(|value| {
-        serde_json::to_string_pretty(&value)
-            .map_err(|e| ClubError::ManifestWriteFail(e.to_string()))
-    })?;
-    std::fs::write(manifest_path, json_str)
-        .map_err(|e| ClubError::ManifestWriteFail(e.to_string()))?;
+/// Applies a batch of changes to the remotes in order, with the same result as applying them one
+/// at a time. The whole batch is validated first, so nothing changes unless every op applies. Groups
+/// and the active remote are left for the caller to update.
+fn apply_remote_ops(
+    remotes: &mut IndexMap<RemoteName, Remote>,
+    ops: &[RemoteOp],
+) -> Result<(), ClubError> {
+    validate_remote_ops(remotes, ops)?;
+    for op in ops {
+        match op {
+            RemoteOp::Set(remote_name, remote_id) => {
+                remotes
+                    .entry(remote_name.clone())
+                    .or_insert_with(|| Remote::from(remote_id.clone()))
+                    .id = remote_id.clone();
+            }
+            RemoteOp::Remove(remote_name) => {
+                remotes.shift_remove(remote_name);
+            }
+            RemoteOp::Rename(old_name, new_name) => {
+                let remote = remotes.shift_remove(old_name).unwrap();
+                remotes.insert(new_name.clone(), remote);
+            }
+        }
+    }
     Ok(())
 }
 
-fn club_list() -> Result<(), ClubError> {
-    match get_clasp_config() {
-        Err(err) => Err(err),
-        Ok(ClaspConfig {
-            club_remotes: None, ..
-        }) => Err(ClubError::ClubNotSetup),
-        Ok(config) => {
-            let remotes = config.club_remotes.unwrap();
-            for (remote_name, remote_id) in remotes {
-                let key_display = {
-                    if "main" == remote_name.0 {
-                        remote_name.to_string().bold()
-                    } else {
-                        ColoredString::from(remote_name.to_string())
+impl ClaspConfig {
+    /// Where clasp finds the source: `rootDir` relative to the manifest's directory, as clasp
+    /// resolves it, rather than to club's working directory. An absolute `rootDir` is used as-is.
+    fn resolved_root_dir(&self, manifest_dir: &Path) -> PathBuf {
+        let root_dir = Path::new(self.push_root_dir.as_ref().unwrap_or(&self.root_dir));
+        if root_dir.is_absolute() {
+            root_dir.to_path_buf()
+        } else {
+            manifest_dir.join(root_dir)
+        }
+    }
+
+    fn parse(value: Value, validate_ids: bool) -> Result<Self, ClubError> {
+        let root_dir = value["rootDir"]
+            .as_str()
+            .ok_or(ClubError::ManifestShapeError(
+                "rootDir not found".to_string(),
+            ))?;
+        let script_id = value["scriptId"]
+            .as_str()
+            .ok_or(ClubError::ManifestShapeError(
+                "scriptId not found".to_string(),
+            ))?;
+        let parent_ids: Vec<String> = value["parentId"]
+            .as_array()
+            .ok_or(ClubError::ManifestShapeError(
+                "parentId not found".to_string(),
+            ))?
+            .iter()
+            .map(|id| {
+                id.as_str()
+                    .ok_or(ClubError::ManifestShapeError(
+                        "parentId not found".to_string(),
+                    ))
+                    .map(|str| str.to_string())
+            })
+            .collect::<Result<Vec<String>, ClubError>>()?;
+        let club_groups = parse_club_groups(&value["__club__"][GROUPS_KEY])?;
+        let mut club_settings = ClubSettings::default();
+        let club_remotes = match value["__club__"].as_object() {
+            None => None,
+            Some(remotes) => {
+                let mut remote_map = IndexMap::new();
+                for (key, value) in remotes {
+                    if key == REQUIRE_CLEAN_GIT_KEY {
+                        club_settings.require_clean_git = value.as_bool().unwrap_or(false);
+                        continue;
                     }
-                };
-                println!("{}: {}", key_display, remote_id);
+                    if key == ACTIVE_REMOTE_KEY {
+                        club_settings.active_remote = value
+                            .as_str()
+                            .and_then(|name| RemoteName::try_from(name.to_string()).ok());
+                        continue;
+                    }
+                    if key == GROUPS_KEY {
+                        continue;
+                    }
+                    if key == VERSION_KEY {
+                        let version = value.as_u64().ok_or_else(|| {
+                            ClubError::ManifestShapeError(format!(
+                                "{} must be a whole number",
+                                VERSION_KEY
+                            ))
+                        })?;
+                        if version > CLUB_SCHEMA_VERSION {
+                            return Err(ClubError::ConfigVersionTooNew(version));
+                        }
+                        club_settings.version = Some(version);
+                        continue;
+                    }
+                    let remote_name = RemoteName::try_from(key.to_string()).map_err(|err| {
+                        ClubError::ManifestShapeError(format!("remote `{}`: {}", key, err))
+                    })?;
+                    let remote = Remote::parse(value, validate_ids).map_err(|err| {
+                        ClubError::ManifestShapeError(format!("remote `{}`: {}", key, err))
+                    })?;
+                    remote_map.insert(remote_name, remote);
+                }
+                Some(remote_map)
             }
-            Ok(())
+        };
+        Ok(ClaspConfig {
+            root_dir: root_dir.to_string(),
+            script_id: script_id.to_string(),
+            parent_ids,
+            club_remotes,
+            club_groups,
+            club_settings,
+            env_remotes: None,
+            remote_file: None,
+            push_root_dir: None,
+        })
+    }
+
+    /// Reads the `__club__` map from `remote_file` in place of the manifest's. Until the file exists,
+    /// the manifest's own map is kept, so the first write moves it into the file.
+    fn with_remote_file(self, remote_file: &Path, validate_ids: bool) -> Result<Self, ClubError> {
+        let file_err = |err: String| ClubError::RemoteFileError(remote_file.to_path_buf(), err);
+        let config = ClaspConfig {
+            remote_file: Some(remote_file.to_path_buf()),
+            ..self
+        };
+        if !remote_file.exists() {
+            debug!(
+                "Remote file {} doesn't exist yet; using the manifest's remotes",
+                remote_file.display()
+            );
+            return Ok(config);
+        }
+        debug!("Reading remotes from {}", remote_file.display());
+        let club_str = std::fs::read_to_string(remote_file).map_err(|e| file_err(e.to_string()))?;
+        let club_json: Value =
+            serde_json::from_str(&club_str).map_err(|e| file_err(e.to_string()))?;
+        if !club_json.is_object() {
+            return Err(file_err("expected a JSON object".to_string()));
+        }
+        let file_config = ClaspConfig::parse(
+            serde_json::json!({
+                "rootDir": "",
+                "scriptId": "",
+                "parentId": [],
+                "__club__": club_json,
+            }),
+            validate_ids,
+        )
+        .map_err(|err| match err {
+            ClubError::ManifestShapeError(err) => file_err(err),
+            err => err,
+        })?;
+        Ok(ClaspConfig {
+            club_remotes: file_config.club_remotes,
+            club_groups: file_config.club_groups,
+            club_settings: file_config.club_settings,
+            ..config
+        })
+    }
+
+    /// Applies the remotes from `CLUB_REMOTES`: merged over the manifest's remotes, or in place of
+    /// them. Groups and club settings always come from the manifest.
+    fn with_env_remotes(
+        self,
+        remotes_env: &str,
+        mode: RemotesEnvMode,
+        validate_ids: bool,
+    ) -> Result<Self, ClubError> {
+        let club_json: Value = serde_json::from_str(remotes_env)
+            .map_err(|e| ClubError::RemotesEnvInvalid(e.to_string()))?;
+        let Some(club_map) = club_json.as_object() else {
+            return Err(ClubError::RemotesEnvInvalid(
+                "expected a JSON object".to_string(),
+            ));
+        };
+        if let Some(key) = club_map
+            .keys()
+            .find(|key| RESERVED_REMOTE_NAMES.contains(&key.as_str()))
+        {
+            return Err(ClubError::RemotesEnvInvalid(format!(
+                "`{}` can only be set in the manifest",
+                key
+            )));
         }
+        let env_config = ClaspConfig::parse(
+            serde_json::json!({
+                "rootDir": "",
+                "scriptId": "",
+                "parentId": [],
+                "__club__": club_json,
+            }),
+            validate_ids,
+        )
+        .map_err(|err| match err {
+            ClubError::ManifestShapeError(err) => ClubError::RemotesEnvInvalid(err),
+            err => err,
+        })?;
+        let env_remotes = env_config.club_remotes.unwrap_or_default();
+        debug!(
+            "Applying {} remotes from {} in {:?} mode",
+            env_remotes.len(),
+            REMOTES_ENV,
+            mode
+        );
+        let club_remotes = match mode {
+            RemotesEnvMode::Merge => {
+                let mut remotes = self.club_remotes.clone().unwrap_or_default();
+                remotes.extend(env_remotes.clone());
+                remotes
+            }
+            RemotesEnvMode::Override => env_remotes.clone(),
+        };
+        Ok(ClaspConfig {
+            env_remotes: Some(EnvRemotes {
+                mode,
+                remotes: env_remotes,
+                manifest_remotes: self.club_remotes,
+            }),
+            club_remotes: Some(club_remotes),
+            ..self
+        })
     }
 }
 
-fn club_set(set_args: SetCommand) -> Result<(), ClubError> {
-    let config = get_clasp_config()?;
-
-    let (remote_name, remote_id) = match (
-        RemoteName::try_from(set_args.name),
-        RemoteId::try_from(set_args.id),
-    ) {
-        (Ok(remote_name), Ok(remote_id)) => (remote_name, remote_id),
-        (Err(err), _) | (_, Err(err)) => {
-            return Err(err);
+impl EnvRemotes {
+    /// Treats `remote_names` as set by the command rather than the environment, so that they're
+    /// written back to the manifest even when they match a remote in `CLUB_REMOTES`.
+    fn persist<'a>(&mut self, remote_names: impl IntoIterator<Item = &'a RemoteName>) {
+        for remote_name in remote_names {
+            self.remotes.shift_remove(remote_name);
         }
-    };
+    }
 
-    let mut remotes = config.club_remotes.ok_or(ClubError::ClubNotSetup)?;
-    remotes.insert(remote_name, remote_id);
+    /// The remotes to write back to the manifest: the environment's are left out unless a command
+    /// changed them, and the manifest's own remotes they replaced are restored.
+    fn manifest_view(
+        &self,
+        remotes: Option<IndexMap<RemoteName, Remote>>,
+    ) -> Option<IndexMap<RemoteName, Remote>> {
+        if self.mode == RemotesEnvMode::Override {
+            return self.manifest_remotes.clone();
+        }
+        let mut remotes = remotes?;
+        for (name, env_remote) in &self.remotes {
+            if remotes.get(name) != Some(env_remote) {
+                continue;
+            }
+            match self.manifest_remotes.as_ref().and_then(|m| m.get(name)) {
+                Some(remote) => {
+                    remotes.insert(name.clone(), remote.clone());
+                }
+                None => {
+                    remotes.shift_remove(name);
+                }
+            }
+        }
+        if remotes.is_empty() && self.manifest_remotes.is_none() {
+            return None;
+        }
+        Some(remotes)
+    }
+}
 
-    let new_config = ClaspConfig {
-        club_remotes: Some(remotes),
-        ..config
+fn parse_club_groups(value: &Value) -> Result<IndexMap<String, Vec<RemoteName>>, ClubError> {
+    let mut groups = IndexMap::new();
+    let Some(groups_json) = value.as_object() else {
+        return Ok(groups);
     };
-
-    write_clasp_config(new_config)
+    for (group, members) in groups_json {
+        let members = members
+            .as_array()
+            .ok_or(ClubError::ManifestShapeError(format!(
+                "group `{}` must be a list of remote names",
+                group
+            )))?
+            .iter()
+            .map(|member| {
+                member
+                    .as_str()
+                    .ok_or(ClubError::ManifestShapeError(format!(
+                        "group `{}` must be a list of remote names",
+                        group
+                    )))
+                    .and_then(|name| RemoteName::try_from(name.to_string()))
+            })
+            .collect::<Result<Vec<RemoteName>, ClubError>>()?;
+        groups.insert(group.clone(), members);
+    }
+    Ok(groups)
 }
 
-fn club_init() -> Result<(), ClubError> {
-    match get_clasp_config() {
-        Ok(ClaspConfig {
-            club_remotes: Some(_),
-            ..
-        }) => Err(ClubError::ClubAlreadySetup),
-        Err(err) => Err(err),
-        Ok(config) => {
-            let mut club_remotes = IndexMap::new();
+impl TryFrom<&ClaspConfig> for Value {
+    type Error = ClubError;
 
-            // If there's a valid script ID in the manifest already, add it as the main remote
-            let mut created_main = false;
-            if RemoteId::try_from(config.script_id.clone()).is_ok() {
-                club_remotes.insert(
-                    RemoteName("main".to_string()),
-                    RemoteId(config.script_id.clone()),
-                );
-                created_main = true;
+    fn try_from(config: &ClaspConfig) -> Result<Self, Self::Error> {
+        let mut json = serde_json::json!({
+            "rootDir": config.root_dir,
+            "scriptId": config.script_id,
+            "parentId": config.parent_ids,
+        });
+        // Environment remotes are rare enough that building a separate view for them is fine;
+        // everything else borrows straight from the config.
+        let env_view;
+        let club_remotes = match &config.env_remotes {
+            Some(env_remotes) => {
+                env_view = env_remotes.manifest_view(config.club_remotes.clone());
+                env_view.as_ref()
             }
-
-            let new_config = ClaspConfig {
-                root_dir: config.root_dir,
-                script_id: config.script_id.clone(),
-                parent_ids: config.parent_ids,
-                club_remotes: Some(club_remotes),
-            };
-            write_clasp_config(new_config)?;
-            if created_main {
-                println!(
-                    "Club initialized with main remote set to manifest's scriptId: {}",
-                    config.script_id
-                );
-            } else {
-                println!("Club initialized an empty configuration.");
+            None => config.club_remotes.as_ref(),
+        };
+        if let Some(remotes) = club_remotes {
+            let mut remotes_json = serde_json::json!({});
+            if let Some(version) = config.club_settings.version {
+                remotes_json[VERSION_KEY] = Value::from(version);
             }
-            Ok(())
+            for (key, value) in remotes {
+                remotes_json[&key.0] = Value::from(value);
+            }
+            if !config.club_groups.is_empty() {
+                let mut groups_json = serde_json::json!({});
+                for (group, members) in &config.club_groups {
+                    groups_json[group] =
+                        Value::Array(members.iter().map(|m| Value::String(m.0.clone())).collect());
+                }
+                remotes_json[GROUPS_KEY] = groups_json;
+            }
+            if config.club_settings.require_clean_git {
+                remotes_json[REQUIRE_CLEAN_GIT_KEY] = Value::Bool(true);
+            }
+            // An active remote that only exists in `CLUB_REMOTES` would dangle once written back.
+            if let Some(active_remote) = config
+                .club_settings
+                .active_remote
+                .as_ref()
+                .filter(|name| config.env_remotes.is_none() || remotes.contains_key(*name))
+            {
+                remotes_json[ACTIVE_REMOTE_KEY] = Value::String(active_remote.0.clone());
+            }
+            json["__club__"] = remotes_json;
         }
+        Ok(json)
     }
 }
 
-fn club_remove(remove_args: RemoveCommand) -> Result<(), ClubError> {
-    let config = get_clasp_config()?;
+/// Parses a path option, expanding a leading `~` and `$VAR`/`${VAR}` references the way a shell
+/// would, for paths that reach club unexpanded (quoted, or from scripts and config). References to
+/// unset variables are left as they are, since `$` is a legal file name character.
+fn parse_path(value: &str) -> Result<PathBuf, String> {
+    expand_path(value, |name| std::env::var(name).ok())
+}
 
-    let remote_name = RemoteName::try_from(remove_args.name)?;
-    let mut remotes = config.club_remotes.ok_or(ClubError::ClubNotSetup)?;
-    if remotes.shift_remove(&remote_name).is_none() {
-        return Err(ClubError::RemoteNotFound);
+fn expand_path(value: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<PathBuf, String> {
+    let re = Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)\}|\$([A-Za-z_][A-Za-z0-9_]*)").unwrap();
+    let mut expanded = String::new();
+    let mut last = 0;
+    for caps in re.captures_iter(value) {
+        let whole = caps.get(0).unwrap();
+        let name = caps.get(1).or(caps.get(2)).unwrap().as_str();
+        let Some(var) = lookup(name) else {
+            continue;
+        };
+        expanded.push_str(&value[last..whole.start()]);
+        expanded.push_str(&var);
+        last = whole.end();
     }
-    let new_config = ClaspConfig {
-        club_remotes: Some(remotes),
-        ..config
-    };
+    expanded.push_str(&value[last..]);
 
-    write_clasp_config(new_config)
+    if expanded == "~" || expanded.starts_with("~/") {
+        let home = lookup("HOME").ok_or("cannot expand ~ because $HOME is not set")?;
+        return Ok(Path::new(&home).join(expanded[1..].trim_start_matches('/')));
+    }
+    Ok(PathBuf::from(expanded))
 }
 
-fn club_rename(rename_args: RenameCommand) -> Result<(), ClubError> {
-    let config = get_clasp_config()?;
-
-    let old_name = RemoteName::try_from(rename_args.old_name)?;
-    let new_name = RemoteName::try_from(rename_args.new_name)?;
-    let mut remotes = config.club_remotes.ok_or(ClubError::ClubNotSetup)?;
-    if remotes.contains_key(&new_name) {
-        return Err(ClubError::RemoteAlreadyExists);
+fn get_project_dir(manifest_path: &Path) -> PathBuf {
+    match manifest_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
     }
-    let remote_id = remotes
-        .shift_remove(&old_name)
-        .ok_or(ClubError::RemoteNotFound)?;
-    remotes.insert(new_name, remote_id);
-    let new_config = ClaspConfig {
-        club_remotes: Some(remotes),
-        ..config
-    };
+}
 
-    write_clasp_config(new_config)
+fn get_manifest_path(
+    manifest: Option<PathBuf>,
+    project: Option<&str>,
+) -> Result<PathBuf, ClubError> {
+    match manifest {
+        Some(manifest_path) => Ok(manifest_path),
+        None => Ok(resolve_manifest_path(
+            &std::env::current_dir().map_err(|e| ClubError::ManifestReadFail(e.to_string()))?,
+            project,
+        )),
+    }
 }
 
-fn club_push(push_args: PushCommand) -> Result<(), ClubError> {
-    let config = get_clasp_config()?;
-    let remotes = config.club_remotes.clone().ok_or(ClubError::ClubNotSetup)?;
+/// The manifest in `dir` for a clasp project variant: `.clasp.<project>.json` if it exists,
+/// otherwise `.clasp.json`.
+fn resolve_manifest_path(dir: &Path, project: Option<&str>) -> PathBuf {
+    if let Some(project) = project {
+        let variant_path = dir.join(format!(".clasp.{}.json", project));
+        if variant_path.exists() {
+            return variant_path;
+        }
+        debug!(
+            "{} not found, falling back to {}",
+            variant_path.display(),
+            MANIFEST_NAME
+        );
+    }
+    dir.join(MANIFEST_NAME)
+}
 
-    if push_args.remote.is_some() && push_args.all {
-        return Err(ClubError::BothRemoteAndAllPassed);
+/// The `-P <file>` arguments that point clasp at the manifest club is using, if it isn't the
+/// default `.clasp.json` that clasp would read anyway.
+fn clasp_project_args(manifest_path: &Path) -> Vec<&OsStr> {
+    match manifest_path.file_name() {
+        Some(file_name) if file_name != MANIFEST_NAME => vec![OsStr::new("-P"), file_name],
+        _ => Vec::new(),
     }
-    if remotes.len() == 0 {
-        return Err(ClubError::NoRemotesAvailable);
+}
+
+/// The shell command equivalent to running clasp with `args` the way club does: from the project
+/// directory, with `-P` for a non-default manifest.
+fn clasp_command_line(clasp_bin: &str, manifest_path: &Path, args: &[&str]) -> String {
+    let project_dir = get_project_dir(manifest_path);
+    let words: Vec<String> = std::iter::once(clasp_bin.to_string())
+        .chain(
+            clasp_project_args(manifest_path)
+                .into_iter()
+                .map(|arg| arg.to_string_lossy().into_owned()),
+        )
+        .chain(args.iter().map(|arg| arg.to_string()))
+        .map(|word| shell_quote(&word))
+        .collect();
+    format!(
+        "cd {} && {}",
+        shell_quote(&project_dir.to_string_lossy()),
+        words.join(" ")
+    )
+}
+
+/// Quotes `word` for a POSIX shell, leaving it bare if it has no special characters.
+fn shell_quote(word: &str) -> String {
+    let safe = Regex::new(r"^[A-Za-z0-9_./:=@%+,-]+$").unwrap();
+    if safe.is_match(word) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
     }
+}
 
-    if push_args.all {
-        for (remote_name, remote_id) in remotes {
-            push_to_remote(remote_name, remote_id, config.clone())?;
+/// Loads settings from the `.clubrc` next to the manifest, if there is one, and applies any
+/// command-line overrides on top.
+fn load_settings(args: &ClubArgs, manifest_path: &Path) -> Result<Settings, ClubError> {
+    let mut settings = Settings::default();
+    let mut remember_ids = true;
+    let settings_path = get_project_dir(manifest_path).join(SETTINGS_NAME);
+    if settings_path.exists() {
+        debug!("Reading settings from {}", settings_path.display());
+        let settings_str = std::fs::read_to_string(&settings_path)
+            .map_err(|e| ClubError::SettingsReadFail(e.to_string()))?;
+        let settings_json: Value = serde_json::from_str(&settings_str)
+            .map_err(|e| ClubError::SettingsReadFail(e.to_string()))?;
+        if let Some(color) = settings_json["color"].as_str() {
+            settings.color = ColorChoice::from_str(color, true)
+                .map_err(|_| ClubError::SettingsReadFail(format!("invalid color `{}`", color)))?;
         }
-        Ok(())
-    } else {
-        let remote_name =
-            RemoteName::try_from(push_args.remote.unwrap_or_else(|| "main".to_string()))?;
-        let remote_id = remotes.get(&remote_name).ok_or(ClubError::RemoteNotFound)?;
-        push_to_remote(remote_name, remote_id.clone(), config.clone())
+        if let Some(quiet) = settings_json["quiet"].as_bool() {
+            settings.quiet = quiet;
+        }
+        if let Some(clasp_bin) = settings_json["claspBin"].as_str() {
+            settings.clasp_bin = clasp_bin.to_string();
+        }
+        if let Some(default_remote) = settings_json["defaultRemote"].as_str() {
+            settings.default_remote = RemoteName::try_from(default_remote.to_string())?;
+        }
+        if let Some(require_remote) = settings_json["requireRemote"].as_bool() {
+            settings.require_remote = require_remote;
+        }
+        if let Some(after_push) = settings_json["afterPush"].as_str() {
+            settings.after_push = Some(after_push.to_string());
+        }
+        if let Some(hook_fatal) = settings_json["hookFatal"].as_bool() {
+            settings.hook_fatal = hook_fatal;
+        }
+        if let Some(strict) = settings_json["strict"].as_bool() {
+            settings.strict = strict;
+        }
+        if let Some(recent_ids) = settings_json["recentIds"].as_bool() {
+            remember_ids = recent_ids;
+        }
+        if let Some(history) = settings_json["history"].as_bool() {
+            settings.history = history;
+        }
+        if let Some(track_pushes) = settings_json["trackPushes"].as_bool() {
+            settings.track_pushes = track_pushes;
+        }
+        if let Some(remote_file) = settings_json["remoteFile"].as_str() {
+            let remote_file = parse_path(remote_file).map_err(ClubError::SettingsReadFail)?;
+            settings.remote_file = Some(get_project_dir(manifest_path).join(remote_file));
+        }
+        if let Some(branch_remote_map) = settings_json["branchRemoteMap"].as_object() {
+            for (branch, remote) in branch_remote_map {
+                let remote = remote.as_str().ok_or_else(|| {
+                    ClubError::SettingsReadFail(format!(
+                        "branchRemoteMap entry `{}` is not a remote name",
+                        branch
+                    ))
+                })?;
+                let remote = RemoteName::try_from(remote.to_string())?;
+                settings.branch_remote_map.insert(branch.clone(), remote);
+            }
+        }
+    }
+
+    if let Ok(clasp_bin) = std::env::var(CLASP_BIN_ENV) {
+        settings.clasp_bin = clasp_bin;
+    }
+    if let Some(color) = args.color {
+        settings.color = color;
+    }
+    if args.quiet {
+        settings.quiet = true;
+    }
+    if let Some(clasp_bin) = &args.clasp_bin {
+        settings.clasp_bin = clasp_bin.clone();
+    }
+    if args.strict {
+        settings.strict = true;
+    }
+    settings.json = args.json;
+    settings.compact_json = args.compact;
+    settings.validate_ids = !args.no_validate_ids;
+    settings.lock = !args.no_lock;
+    settings.no_write = args.no_write;
+    settings.recent_ids = std::env::var_os("HOME")
+        .filter(|_| remember_ids)
+        .map(|home| Path::new(&home).join(RECENT_IDS_NAME));
+    settings.remotes_env = std::env::var(REMOTES_ENV).ok();
+    settings.remotes_env_mode = args.remotes_env_mode;
+    if let Some(remote_file) = &args.remote_file {
+        settings.remote_file = Some(remote_file.clone());
     }
+    trace!("Settings: {:?}", settings);
+    Ok(settings)
 }
 
-fn push_to_remote(
-    remote_name: RemoteName,
-    remote_id: RemoteId,
-    config: ClaspConfig,
-) -> Result<(), ClubError> {
-    println!("Pushing to {}", remote_name);
-    let mut config_copy = config.clone();
-    config_copy.script_id = remote_id.0;
-    write_clasp_config(config_copy)?;
-    let status = Command::new("clasp")
-        .arg("push")
-        .status()
-        .map_err(|e| ClubError::ClaspError(e.to_string()))?;
-    let return_val = if status.success() {
-        Ok(())
-    } else {
-        Err(ClubError::ClaspError("clasp push failed".to_string()))
-    };
-    // Restore the original config
-    write_clasp_config(config)?;
-    return_val
+/// Reports a problem that doesn't stop the command, unless `--strict` is set, in which case the
+/// warning is returned as an error instead.
+fn warn(settings: &Settings, message: String) -> Result<(), ClubError> {
+    warn_unless_strict(settings.strict, message)
 }
 
-fn club_login() -> Result<(), ClubError> {
-    let status = Command::new("clasp")
-        .arg("login")
-        .status()
-        .map_err(|e| ClubError::ClaspError(e.to_string()))?;
-    if status.success() {
-        Ok(())
-    } else {
-        Err(ClubError::ClaspError("clasp login failed".to_string()))
+fn warn_unless_strict(strict: bool, message: String) -> Result<(), ClubError> {
+    if strict {
+        return Err(ClubError::StrictWarning(message));
     }
+    eprintln!("{}", format!("Warning: {}", message).yellow());
+    Ok(())
 }
 
-fn main() {
-    let args = ClubArgs::parse();
-    if let Err(e) = match args.command {
-        ClubCommand::Init(_) => club_init(),
-        ClubCommand::List(_) => club_list(),
-        ClubCommand::Set(set_args) => club_set(set_args),
-        ClubCommand::Remove(remove_args) => club_remove(remove_args),
-        ClubCommand::Rename(rename_args) => club_rename(rename_args),
-        ClubCommand::Push(push_args) => club_push(push_args),
-        ClubCommand::Login(_) => club_login(),
-    } {
-        println!("{}", e);
+/// Warns about a script ID given on the command line that --no-validate-ids let through but isn't
+/// the length of a modern script ID.
+fn warn_if_legacy_id(settings: &Settings, remote_id: &RemoteId) -> Result<(), ClubError> {
+    if remote_id.0.len() == 57 {
+        return Ok(());
+    }
+    warn(
+        settings,
+        format!(
+            "accepting script ID {} of length {}; modern script IDs are 57 characters long.",
+            remote_id,
+            remote_id.0.len()
+        ),
+    )
+}
+
+fn get_clasp_config(manifest_path: &Path, validate_ids: bool) -> Result<ClaspConfig, ClubError> {
+    ClaspConfig::parse(read_manifest_json(manifest_path)?, validate_ids)
+}
+
+fn read_manifest_json(manifest_path: &Path) -> Result<Value, ClubError> {
+    if !manifest_path.exists() {
+        return Err(ClubError::ManifestNotFound);
+    }
+    debug!("Reading manifest {}", manifest_path.display());
+    let manifest_str = std::fs::read_to_string(manifest_path)
+        .map_err(|e| ClubError::ManifestReadFail(e.to_string()))?;
+    let manifest_json: Value = serde_json::from_str(&manifest_str).map_err(|e| {
+        // serde_json appends the position to its message; it's reported separately.
+        let position = format!(" at line {} column {}", e.line(), e.column());
+        let message = e.to_string();
+        let message = message.strip_suffix(&position).unwrap_or(&message);
+        ClubError::ManifestParseError(e.line(), e.column(), message.to_string())
+    })?;
+    Ok(manifest_json)
+}
+
+impl Context {
+    fn new(manifest_path: PathBuf, validate_ids: bool) -> Self {
+        Context {
+            manifest_path,
+            validate_ids,
+            config: OnceCell::new(),
+            #[cfg(test)]
+            manifest_reads: std::cell::Cell::new(0),
+            remotes_env: None,
+            remote_file: None,
+            no_write: false,
+            strict: false,
+        }
+    }
+
+    /// Reads remotes from where the settings say they live: the `--remote-file`, if one is set, and
+    /// `CLUB_REMOTES` on top.
+    fn with_settings(self, settings: &Settings) -> Self {
+        Context {
+            remotes_env: settings
+                .remotes_env
+                .clone()
+                .map(|remotes_env| (remotes_env, settings.remotes_env_mode)),
+            remote_file: settings.remote_file.clone(),
+            no_write: settings.no_write,
+            strict: settings.strict,
+            ..self
+        }
+    }
+
+    /// Writes `config` to the manifest, or with --no-write, prints what would be written.
+    fn write_config(&self, config: &ClaspConfig) -> Result<(), ClubError> {
+        if !self.no_write {
+            return write_clasp_config(config, &self.manifest_path);
+        }
+        let rendered = render_clasp_config(config)?;
+        let preview = match rendered.remotes {
+            Some((remote_file, remotes)) => format!(
+                "==> {} <==\n{}\n\n==> {} <==\n{}",
+                self.manifest_path.display(),
+                rendered.manifest,
+                remote_file.display(),
+                remotes
+            ),
+            None => rendered.manifest,
+        };
+        println!("{}", preview);
+        Ok(())
+    }
+
+    /// The parsed manifest, read from disk the first time it's needed. Errors aren't cached, so a
+    /// failed read is retried on the next call.
+    fn config(&self) -> Result<&ClaspConfig, ClubError> {
+        if let Some(config) = self.config.get() {
+            return Ok(config);
+        }
+        let config = self.read_config(self.validate_ids)?;
+        let off_format: Vec<&str> = config
+            .club_remotes
+            .iter()
+            .flat_map(IndexMap::keys)
+            .filter(|remote_name| !remote_name.is_well_formed())
+            .map(|remote_name| remote_name.0.as_str())
+            .collect();
+        if !off_format.is_empty() {
+            warn_unless_strict(
+                self.strict,
+                format!(
+                    "remote names should only use letters, digits, hyphens, and underscores: {}. Rename them with `club rename`, or run `club config migrate` to fix them all.",
+                    off_format.join(", ")
+                ),
+            )?;
+        }
+        Ok(self.config.get_or_init(|| config))
+    }
+
+    /// Reads the manifest and its remotes afresh, bypassing the cached config, for commands that
+    /// need to read it with different ID validation.
+    fn read_config(&self, validate_ids: bool) -> Result<ClaspConfig, ClubError> {
+        #[cfg(test)]
+        self.manifest_reads.set(self.manifest_reads.get() + 1);
+        let config = get_clasp_config(&self.manifest_path, validate_ids)?;
+        self.with_remote_sources(config, validate_ids)
+    }
+
+    /// Applies the `--remote-file` and `CLUB_REMOTES` to a config parsed from the manifest.
+    fn with_remote_sources(
+        &self,
+        mut config: ClaspConfig,
+        validate_ids: bool,
+    ) -> Result<ClaspConfig, ClubError> {
+        if let Some(remote_file) = &self.remote_file {
+            config = config.with_remote_file(remote_file, validate_ids)?;
+        }
+        if let Some((remotes_env, mode)) = &self.remotes_env {
+            config = config.with_env_remotes(remotes_env, *mode, validate_ids)?;
+        }
+        Ok(config)
+    }
+}
+
+/// An advisory lock on a manifest, held as a `<manifest>.lock` file for as long as the guard lives.
+/// Pushes swap the manifest's scriptId and restore it afterwards, so two club commands changing the
+/// same manifest at once could leave it pointing at the wrong remote.
+#[derive(Debug)]
+struct ManifestLock {
+    path: PathBuf,
+}
+
+impl ManifestLock {
+    /// Where the lock for the manifest at `manifest_path` is kept.
+    fn path_for(manifest_path: &Path) -> PathBuf {
+        let mut path = manifest_path.as_os_str().to_owned();
+        path.push(".lock");
+        PathBuf::from(path)
+    }
+
+    /// Takes the lock, waiting up to `timeout` for another club command to release it. A lock left by
+    /// a process that is no longer running is taken over.
+    fn acquire(manifest_path: &Path, timeout: Duration) -> Result<Self, ClubError> {
+        let path = ManifestLock::path_for(manifest_path);
+        let start = Instant::now();
+        loop {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    debug!("Took manifest lock {}", path.display());
+                    // The PID helps whoever finds a stale lock work out which command left it.
+                    let _ = writeln!(file, "{}", std::process::id());
+                    return Ok(ManifestLock { path });
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                    if ManifestLock::remove_if_stale(&path) {
+                        continue;
+                    }
+                    if start.elapsed() >= timeout {
+                        return Err(ClubError::ManifestLocked(path));
+                    }
+                    trace!("Waiting for manifest lock {}", path.display());
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                Err(err) => return Err(ClubError::LockFail(err.to_string())),
+            }
+        }
+    }
+
+    /// Removes the lock at `path` if the process whose PID it holds has exited, returning whether it
+    /// did. A lock whose holder can't be told is left alone.
+    fn remove_if_stale(path: &Path) -> bool {
+        let holder = || {
+            std::fs::read_to_string(path)
+                .ok()
+                .and_then(|pid| pid.trim().parse::<u32>().ok())
+        };
+        let Some(pid) = holder() else {
+            return false;
+        };
+        if process_is_running(pid) != Some(false) {
+            return false;
+        }
+        // Another command may have taken over the stale lock since it was read.
+        if holder() != Some(pid) {
+            return false;
+        }
+        debug!(
+            "Removing manifest lock {} left by exited process {}",
+            path.display(),
+            pid
+        );
+        std::fs::remove_file(path).is_ok()
+    }
+}
+
+/// Whether the process `pid` is still running, or `None` where that can't be told.
+fn process_is_running(pid: u32) -> Option<bool> {
+    if !cfg!(unix) {
+        return None;
+    }
+    let proc_dir = Path::new("/proc");
+    if proc_dir.join("self").exists() {
+        return Some(proc_dir.join(pid.to_string()).exists());
+    }
+    Command::new("kill")
+        .arg("-0")
+        .arg(pid.to_string())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .ok()
+        .map(|status| status.success())
+}
+
+impl Drop for ManifestLock {
+    fn drop(&mut self) {
+        debug!("Releasing manifest lock {}", self.path.display());
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl ClubCommand {
+    /// Whether the command can change the manifest, and so must hold the manifest lock.
+    fn writes_manifest(&self) -> bool {
+        match self {
+            ClubCommand::Audit(_)
+            | ClubCommand::DiffConfig(_)
+            | ClubCommand::DumpConfig(_)
+            | ClubCommand::CheckManifest(_)
+            | ClubCommand::Completions(_)
+            | ClubCommand::Current(_)
+            | ClubCommand::Env(_)
+            | ClubCommand::Examples(_)
+            | ClubCommand::Export(_)
+            | ClubCommand::List(_)
+            | ClubCommand::Log(_)
+            | ClubCommand::ShellAliases(_)
+            | ClubCommand::Show(_)
+            | ClubCommand::Status(_)
+            | ClubCommand::Login(_)
+            | ClubCommand::ValidateName(_)
+            | ClubCommand::ValidateUrl(_)
+            | ClubCommand::Verify(_)
+            | ClubCommand::Version(_)
+            | ClubCommand::Whoami(_)
+            | ClubCommand::Group(GroupCommand::List(_)) => false,
+            ClubCommand::Doctor(doctor_args) => doctor_args.fix,
+            ClubCommand::Ensure(ensure_args) => !ensure_args.check,
+            ClubCommand::Import(import_args) => !import_args.dry_run,
+            ClubCommand::Open(open_args) => open_args.deployment,
+            // --and-register writes the new remote even when the pushes leave the manifest alone.
+            ClubCommand::Push(push_args) => {
+                !(push_args.print_cmd || push_args.dry_run)
+                    && (!push_args.concurrency_safe || push_args.and_register.is_some())
+            }
+            ClubCommand::Remove(remove_args) => !remove_args.dry_run,
+            ClubCommand::Rename(rename_args) => !rename_args.dry_run,
+            ClubCommand::Clasp(_)
+            | ClubCommand::Config(_)
+            | ClubCommand::Gc(_)
+            | ClubCommand::NormalizeNames(_)
+            | ClubCommand::Group(_)
+            | ClubCommand::Init(_)
+            | ClubCommand::MvManifest(_)
+            | ClubCommand::Reassign(_)
+            | ClubCommand::Repair(_)
+            | ClubCommand::Set(_)
+            | ClubCommand::Swap(_)
+            | ClubCommand::Switch(_)
+            | ClubCommand::Touch(_) => true,
+        }
+    }
+
+    /// Whether the command points the manifest at a remote while clasp runs, which --no-write can't
+    /// preview.
+    fn runs_clasp_on_manifest(&self) -> bool {
+        match self {
+            ClubCommand::Push(push_args) => {
+                !(push_args.concurrency_safe || push_args.print_cmd || push_args.dry_run)
+            }
+            ClubCommand::Clasp(_) | ClubCommand::Open(_) => self.writes_manifest(),
+            _ => false,
+        }
+    }
+
+    /// Whether the command can change the remotes recorded in the manifest, as opposed to only
+    /// pointing the manifest at one of them.
+    fn changes_remotes(&self) -> bool {
+        match self {
+            ClubCommand::Push(_)
+            | ClubCommand::Switch(_)
+            | ClubCommand::Clasp(_)
+            | ClubCommand::MvManifest(_)
+            | ClubCommand::Open(_) => false,
+            command => command.writes_manifest(),
+        }
+    }
+}
+
+/// The text of the files a `ClaspConfig` is written to.
+struct RenderedConfig<'a> {
+    manifest: String,
+    /// The remote file and its text, if remotes are kept outside the manifest.
+    remotes: Option<(&'a Path, String)>,
+}
+
+fn render_clasp_config(config: &ClaspConfig) -> Result<RenderedConfig<'_>, ClubError> {
+    let mut value = Value::try_from(config)?;
+    let mut remotes = None;
+    if let Some(remote_file) = &config.remote_file {
+        if let Some(club_json) = value
+            .as_object_mut()
+            .and_then(|json| json.remove("__club__"))
+        {
+            let club_str = serde_json::to_string_pretty(&club_json)
+                .map_err(|e| ClubError::ManifestWriteFail(e.to_string()))?;
+            remotes = Some((remote_file.as_path(), club_str));
+        }
+    }
+    let json_str = serde_json::to_string_pretty(&value)
+        .map_err(|e| ClubError::ManifestWriteFail(e.to_string()))?;
+    Ok(RenderedConfig {
+        manifest: json_str,
+        remotes,
+    })
+}
+
+fn write_clasp_config(config: &ClaspConfig, manifest_path: &Path) -> Result<(), ClubError> {
+    let rendered = render_clasp_config(config)?;
+    if let Some((remote_file, club_str)) = rendered.remotes {
+        debug!("Writing remotes to {}", remote_file.display());
+        std::fs::write(remote_file, club_str).map_err(|e| {
+            ClubError::ManifestWriteFail(format!("{}: {}", remote_file.display(), e))
+        })?;
+    }
+    debug!(
+        "Writing manifest {} with scriptId {}",
+        manifest_path.display(),
+        config.script_id
+    );
+    std::fs::write(manifest_path, rendered.manifest)
+        .map_err(|e| ClubError::ManifestWriteFail(e.to_string()))?;
+    Ok(())
+}
+
+/// Masks all but the last four characters of a script ID, so that bug reports can tell IDs apart
+/// without sharing them.
+fn redact_id(id: &str) -> String {
+    let keep = if id.chars().count() > 4 { 4 } else { 0 };
+    let masked = id.chars().count() - keep;
+    "*".repeat(masked) + &id.chars().skip(masked).collect::<String>()
+}
+
+/// The output of `club --dump-config`: the resolved manifest path, the parsed manifest along with
+/// any top-level keys club doesn't use, the effective settings, and the clasp binary.
+fn dump_config_json(
+    ctx: &Context,
+    settings: &Settings,
+    redact: bool,
+    clasp_version: Option<String>,
+) -> Value {
+    let show_id = |id: &str| {
+        if redact {
+            redact_id(id)
+        } else {
+            id.to_string()
+        }
+    };
+    let manifest = match ctx.config() {
+        Ok(config) => {
+            let extra: serde_json::Map<String, Value> = std::fs::read_to_string(&ctx.manifest_path)
+                .ok()
+                .and_then(|manifest| serde_json::from_str::<Value>(&manifest).ok())
+                .and_then(|manifest| manifest.as_object().cloned())
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|(key, _)| {
+                    !["rootDir", "scriptId", "parentId", "__club__"].contains(&key.as_str())
+                })
+                .collect();
+            let remotes: Option<serde_json::Map<String, Value>> =
+                config.club_remotes.as_ref().map(|remotes| {
+                    remotes
+                        .iter()
+                        .map(|(name, remote)| {
+                            (
+                                name.0.clone(),
+                                serde_json::json!({
+                                    "id": show_id(&remote.id.0),
+                                    "protected": remote.protected,
+                                }),
+                            )
+                        })
+                        .collect()
+                });
+            serde_json::json!({
+                "rootDir": config.root_dir,
+                "scriptId": show_id(&config.script_id),
+                "parentId": config.parent_ids.iter().map(|id| show_id(id)).collect::<Vec<_>>(),
+                "remotes": remotes,
+                "groups": config
+                    .club_groups
+                    .iter()
+                    .map(|(group, members)| {
+                        let members: Vec<&str> = members.iter().map(|m| m.0.as_str()).collect();
+                        (group.clone(), serde_json::json!(members))
+                    })
+                    .collect::<serde_json::Map<_, _>>(),
+                "activeRemote": config.club_settings.active_remote.as_ref().map(|name| &name.0),
+                "requireCleanGit": config.club_settings.require_clean_git,
+                "extra": extra,
+            })
+        }
+        Err(err) => serde_json::json!({ "error": err.to_string() }),
+    };
+    serde_json::json!({
+        "manifestPath": ctx.manifest_path.display().to_string(),
+        "manifest": manifest,
+        "settings": {
+            "color": settings.color.to_possible_value().map(|value| value.get_name().to_string()),
+            "quiet": settings.quiet,
+            "defaultRemote": settings.default_remote.0,
+            "requireRemote": settings.require_remote,
+            "afterPush": settings.after_push,
+            "hookFatal": settings.hook_fatal,
+            "strict": settings.strict,
+            "history": settings.history,
+            "trackPushes": settings.track_pushes,
+            "validateIds": settings.validate_ids,
+            "lock": settings.lock,
+            "remoteFile": settings.remote_file.as_ref().map(|path| path.display().to_string()),
+            "remotesEnv": settings.remotes_env.is_some(),
+            "remotesEnvMode": settings
+                .remotes_env_mode
+                .to_possible_value()
+                .map(|value| value.get_name().to_string()),
+        },
+        "clasp": {
+            "bin": settings.clasp_bin,
+            "version": clasp_version,
+        },
+    })
+}
+
+/// The version clasp reports, or None if it can't be run.
+fn clasp_version(clasp_bin: &str) -> Option<String> {
+    let output = Command::new(clasp_bin).arg("--version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// What `club version` reports: club's version, the clasp binary and the version it reports, and
+/// whether clasp is logged in, or None if clasp can't be asked.
+fn version_json(settings: &Settings, home: Option<&Path>) -> Value {
+    serde_json::json!({
+        "club": env!("CARGO_PKG_VERSION"),
+        "clasp": {
+            "bin": settings.clasp_bin,
+            "version": clasp_version(&settings.clasp_bin),
+        },
+        "loggedIn": login_status(settings, home)
+            .ok()
+            .map(|status| matches!(status, LoginStatus::LoggedIn(_))),
+    })
+}
+
+/// Whether clasp has credentials, as reported by `clasp login --status`.
+#[derive(Debug, PartialEq)]
+enum LoginStatus {
+    /// Logged in, as the account clasp named, if it named one.
+    LoggedIn(Option<String>),
+    LoggedOut,
+}
+
+/// Reads the output of `clasp login --status`, or None if it says neither way.
+fn parse_login_status(output: &str) -> Option<LoginStatus> {
+    if Regex::new(r"(?i)not logged in").unwrap().is_match(output) {
+        return Some(LoginStatus::LoggedOut);
+    }
+    if !Regex::new(r"(?i)logged in").unwrap().is_match(output) {
+        return None;
+    }
+    let account = Regex::new(r"[^\s<>()'\x22]+@[^\s<>()'\x22]+")
+        .unwrap()
+        .find(output)
+        .map(|m| m.as_str().trim_end_matches('.').to_string());
+    Some(LoginStatus::LoggedIn(account))
+}
+
+/// Whether `.clasprc.json` in `home` holds a refresh token, without which clasp can't stay logged in.
+fn clasprc_has_refresh_token(home: &Path) -> bool {
+    fn has_token(value: &Value) -> bool {
+        match value {
+            Value::Object(fields) => fields.iter().any(|(key, value)| {
+                (key == "refresh_token" && value.as_str().is_some_and(|token| !token.is_empty()))
+                    || has_token(value)
+            }),
+            Value::Array(values) => values.iter().any(has_token),
+            _ => false,
+        }
+    }
+    std::fs::read_to_string(home.join(CLASPRC_NAME))
+        .ok()
+        .and_then(|text| serde_json::from_str::<Value>(&text).ok())
+        .is_some_and(|clasprc| has_token(&clasprc))
+}
+
+/// Asks clasp who's logged in, falling back to whether `.clasprc.json` exists in `home` if clasp's
+/// answer can't be read. `whoami`, `version`, and `doctor` all decide whether clasp is logged in
+/// through here.
+fn login_status(settings: &Settings, home: Option<&Path>) -> Result<LoginStatus, ClubError> {
+    let output = Command::new(&settings.clasp_bin)
+        .args(["login", "--status"])
+        .output()
+        .map_err(|e| clasp_spawn_error(settings, e))?;
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    debug!(
+        "clasp login --status exited with {}: {}",
+        output.status,
+        text.trim()
+    );
+    Ok(parse_login_status(&text).unwrap_or_else(|| {
+        if home.is_some_and(|home| home.join(CLASPRC_NAME).is_file()) {
+            LoginStatus::LoggedIn(None)
+        } else {
+            LoginStatus::LoggedOut
+        }
+    }))
+}
+
+/// Who's logged in to clasp, and whether their saved credentials can be refreshed. Fails if no one
+/// is logged in.
+fn login_status_json(settings: &Settings, home: Option<&Path>) -> Result<Value, ClubError> {
+    let LoginStatus::LoggedIn(account) = login_status(settings, home)? else {
+        return Err(ClubError::NotLoggedIn);
+    };
+    Ok(serde_json::json!({
+        "loggedIn": true,
+        "account": account,
+        "credentialsValid": home.is_some_and(clasprc_has_refresh_token),
+    }))
+}
+
+fn club_whoami(settings: &Settings) -> Result<(), ClubError> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let status = login_status_json(settings, home.as_deref())?;
+    if settings.json {
+        return print_json(&status, settings);
+    }
+    match status["account"].as_str() {
+        Some(account) => println!("Logged in to clasp as {}.", account),
+        None => println!("Logged in to clasp."),
+    }
+    if status["credentialsValid"] != true {
+        warn(
+            settings,
+            format!(
+                "~/{} has no refresh token, so clasp may fail to authenticate. Run `club login` to log in again.",
+                CLASPRC_NAME
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+fn club_version(settings: &Settings) -> Result<(), ClubError> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let version = version_json(settings, home.as_deref());
+    if settings.json {
+        return print_json(&version, settings);
+    }
+    println!("club {}", env!("CARGO_PKG_VERSION"));
+    match version["clasp"]["version"].as_str() {
+        Some(clasp_version) => println!("clasp {}", clasp_version),
+        None => println!("clasp: {} could not be run", settings.clasp_bin),
+    }
+    if let Some(logged_in) = version["loggedIn"].as_bool() {
+        println!(
+            "logged in to clasp: {}",
+            if logged_in { "yes" } else { "no" }
+        );
+    }
+    Ok(())
+}
+
+fn club_dump_config(
+    dump_args: DumpConfigCommand,
+    ctx: &Context,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    let dump = dump_config_json(
+        ctx,
+        settings,
+        !dump_args.no_redact,
+        clasp_version(&settings.clasp_bin),
+    );
+    print!("{}", json_text(&dump, settings.compact_json)?);
+    Ok(())
+}
+
+fn club_check_manifest(ctx: &Context, settings: &Settings) -> Result<(), ClubError> {
+    ctx.config()?;
+    if !settings.quiet {
+        eprintln!("{} is a valid clasp manifest.", ctx.manifest_path.display());
+    }
+    Ok(())
+}
+
+fn club_list(list_args: ListCommand, ctx: &Context, settings: &Settings) -> Result<(), ClubError> {
+    match ctx.config().cloned() {
+        Err(err) => Err(err),
+        Ok(ClaspConfig {
+            club_remotes: None, ..
+        }) => Err(ClubError::ClubNotSetup),
+        Ok(config) => {
+            let active = list_args
+                .with_active_marker
+                .then(|| active_remote(&config).cloned())
+                .flatten();
+            let mut remotes = config.club_remotes.unwrap();
+            if remotes.is_empty() {
+                if let Some(hint) = list_empty_hint(&list_args, settings.json) {
+                    eprintln!("{}", hint);
+                }
+            }
+            if list_args.active_only {
+                retain_active(&mut remotes, &config.script_id);
+            }
+            if settings.json {
+                let mut json = remotes_json(&remotes);
+                if list_args.with_active_marker {
+                    mark_active(&mut json, active.as_ref());
+                }
+                return print_json(&json, settings);
+            }
+            if list_args.names_only {
+                for remote_name in remotes.keys() {
+                    println!("{}", remote_name);
+                }
+                return Ok(());
+            }
+            if list_args.format == ListFormat::Csv {
+                print!("{}", remotes_csv(&remotes, !list_args.no_header));
+                return Ok(());
+            }
+            if list_args.tree {
+                let marked = list_args.with_active_marker.then_some(active.as_ref());
+                for line in remotes_tree(&remotes, &config.club_groups, marked) {
+                    println!("{}", line);
+                }
+                return Ok(());
+            }
+            if list_args.format == ListFormat::Table {
+                let marked = list_args.with_active_marker.then_some(active.as_ref());
+                for line in remotes_table(&remotes, marked, !list_args.no_header) {
+                    println!("{}", line);
+                }
+                return Ok(());
+            }
+            for (remote_name, remote) in remotes {
+                let key_display = {
+                    if "main" == remote_name.0 {
+                        remote_name.to_string().bold()
+                    } else {
+                        ColoredString::from(remote_name.to_string())
+                    }
+                };
+                let marker = match &active {
+                    Some(active) if *active == remote_name => "* ",
+                    _ if list_args.with_active_marker => "  ",
+                    _ => "",
+                };
+                if remote.protected {
+                    println!(
+                        "{}{}: {} {}",
+                        marker,
+                        key_display,
+                        remote.id,
+                        "(protected)".red()
+                    );
+                } else {
+                    println!("{}{}: {}", marker, key_display, remote.id);
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Renders remotes for `list --tree`: remotes in no group first, then each group with its members
+/// beneath it. A remote in several groups appears under each. `marked` works as for
+/// `remotes_table`.
+fn remotes_tree(
+    remotes: &IndexMap<RemoteName, Remote>,
+    groups: &IndexMap<String, Vec<RemoteName>>,
+    marked: Option<Option<&RemoteName>>,
+) -> Vec<String> {
+    let marker = |remote_name: Option<&RemoteName>| match marked {
+        Some(active) if remote_name.is_some() && active == remote_name => "* ",
+        Some(_) => "  ",
+        None => "",
+    };
+    let describe = |remote_name: &RemoteName| {
+        let name = if remote_name.0 == "main" {
+            remote_name.to_string().bold()
+        } else {
+            ColoredString::from(remote_name.to_string())
+        };
+        match remotes.get(remote_name) {
+            Some(remote) if remote.protected => {
+                format!("{}: {} {}", name, remote.id, "(protected)".red())
+            }
+            Some(remote) => format!("{}: {}", name, remote.id),
+            None => format!("{} {}", name, "(missing)".red()),
+        }
+    };
+
+    let grouped: Vec<&RemoteName> = groups.values().flatten().collect();
+    let mut lines: Vec<String> = remotes
+        .keys()
+        .filter(|remote_name| !grouped.contains(remote_name))
+        .map(|remote_name| format!("{}{}", marker(Some(remote_name)), describe(remote_name)))
+        .collect();
+    for (group, members) in groups {
+        lines.push(format!("{}{}", marker(None), group.bold()));
+        for (index, member) in members.iter().enumerate() {
+            let connector = if index + 1 == members.len() {
+                "`-- "
+            } else {
+                "|-- "
+            };
+            lines.push(format!(
+                "{}{}{}",
+                marker(Some(member)),
+                connector,
+                describe(member)
+            ));
+        }
+    }
+    lines
+}
+
+/// Renders remotes for `list --format table`: name, script ID, and protected columns, padded to
+/// the widest entry in each. Widths come from the entries alone, never the terminal, so the output
+/// is the same when piped. `marked` adds an active-remote column, marking the given remote if any.
+fn remotes_table(
+    remotes: &IndexMap<RemoteName, Remote>,
+    marked: Option<Option<&RemoteName>>,
+    header: bool,
+) -> Vec<String> {
+    let name_width = remotes
+        .keys()
+        .map(|name| name.0.chars().count())
+        .chain(header.then_some("NAME".len()))
+        .max()
+        .unwrap_or(0);
+    let id_width = remotes
+        .values()
+        .map(|remote| remote.id.0.chars().count())
+        .chain(header.then_some("SCRIPT ID".len()))
+        .max()
+        .unwrap_or(0);
+    let marker = |is_active: bool| match marked {
+        Some(_) if is_active => "* ",
+        Some(_) => "  ",
+        None => "",
+    };
+
+    let mut lines = Vec::new();
+    if header {
+        lines.push(format!(
+            "{}{:name_width$}  {:id_width$}  PROTECTED",
+            marker(false),
+            "NAME",
+            "SCRIPT ID"
+        ));
+    }
+    for (remote_name, remote) in remotes {
+        // Padded before styling, so that escape codes don't count towards the width.
+        let name = format!("{:name_width$}", remote_name.0);
+        let name = if remote_name.0 == "main" {
+            name.bold()
+        } else {
+            ColoredString::from(name)
+        };
+        let protected = if remote.protected {
+            "yes".red()
+        } else {
+            ColoredString::from("")
+        };
+        let line = format!(
+            "{}{}  {:id_width$}  {}",
+            marker(marked.flatten() == Some(remote_name)),
+            name,
+            remote.id.0,
+            protected
+        );
+        lines.push(line.trim_end().to_string());
+    }
+    lines
+}
+
+/// The hint `club list` prints to stderr when no remotes are configured. Machine-readable modes get
+/// no hint, only their empty output.
+fn list_empty_hint(list_args: &ListCommand, json: bool) -> Option<&'static str> {
+    let human = !json && !list_args.names_only && list_args.format != ListFormat::Csv;
+    human.then_some("No remotes configured. Add one with `club set <name> <id>`.")
+}
+
+/// Renders remotes for `list --json`, as an array of `{ name, id, protected }` objects.
+fn remotes_json(remotes: &IndexMap<RemoteName, Remote>) -> Value {
+    Value::Array(
+        remotes
+            .iter()
+            .map(|(remote_name, remote)| {
+                serde_json::json!({
+                    "name": remote_name.0,
+                    "id": remote.id.0,
+                    "protected": remote.protected,
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Adds an `active` field to each remote in `list --json` output.
+fn mark_active(remotes_json: &mut Value, active: Option<&RemoteName>) {
+    for remote in remotes_json.as_array_mut().into_iter().flatten() {
+        let is_active = active.is_some_and(|active| remote["name"] == active.0.as_str());
+        remote["active"] = Value::Bool(is_active);
+    }
+}
+
+/// Keeps only the remotes the manifest's scriptId points at, for `list --active-only`. Every remote
+/// sharing the ID is kept; a detached manifest keeps none.
+fn retain_active(remotes: &mut IndexMap<RemoteName, Remote>, script_id: &str) {
+    remotes.retain(|_, remote| remote.id.0 == script_id);
+}
+
+/// Renders remotes as `name,id,is_main` CSV rows, optionally under a header row.
+fn remotes_csv(remotes: &IndexMap<RemoteName, Remote>, header: bool) -> String {
+    let mut csv = String::new();
+    if header {
+        csv.push_str("name,id,is_main\n");
+    }
+    for (remote_name, remote) in remotes {
+        csv.push_str(&format!(
+            "{},{},{}\n",
+            csv_escape(&remote_name.0),
+            csv_escape(&remote.id.0),
+            remote_name.0 == "main"
+        ));
+    }
+    csv
+}
+
+/// Quotes a CSV field if it contains a delimiter, quote, or line break.
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn club_set(mut set_args: SetCommand, ctx: &Context, settings: &Settings) -> Result<(), ClubError> {
+    let config = ctx.config()?.clone();
+
+    if set_args.pick {
+        let candidates = settings
+            .recent_ids
+            .as_deref()
+            .map(read_recent_ids)
+            .unwrap_or_default();
+        if candidates.is_empty() {
+            return Err(ClubError::NoRecentIds);
+        }
+        if !std::io::stdin().is_terminal() {
+            return Err(ClubError::NoScriptIdChosen);
+        }
+        let remotes = config.club_remotes.clone().unwrap_or_default();
+        let chosen = pick_script_id(&candidates, &remotes).ok_or(ClubError::NoScriptIdChosen)?;
+        set_args.id = Some(chosen);
+    }
+    if let Some(url) = &set_args.from_url {
+        set_args.id = Some(RemoteId::from_url(url)?.0);
+    }
+
+    let entries = if set_args.stdin {
+        parse_remote_lines(std::io::stdin().lock(), settings.validate_ids)?
+    } else {
+        match (
+            RemoteName::parse_new(set_args.name.unwrap_or_default()),
+            RemoteId::parse(set_args.id.unwrap_or_default(), settings.validate_ids),
+        ) {
+            (Ok(remote_name), Ok(remote_id)) => vec![(remote_name, remote_id)],
+            (Err(err), _) | (_, Err(err)) => {
+                return Err(err);
+            }
+        }
+    };
+    for (_, remote_id) in &entries {
+        warn_if_legacy_id(settings, remote_id)?;
+    }
+
+    let description = match &set_args.description_from_file {
+        Some(path) => Some(read_description(path)?),
+        None => None,
+    };
+
+    if set_args.validate_live {
+        for (remote_name, remote_id) in &entries {
+            ensure_script_reachable(remote_name, remote_id, &config, settings)?;
+        }
+    }
+
+    let mut remotes = config.club_remotes.ok_or(ClubError::ClubNotSetup)?;
+    let remotes_set: Vec<RemoteName> = entries.iter().map(|(name, _)| name.clone()).collect();
+    let ops: Vec<RemoteOp> = entries
+        .into_iter()
+        .map(|(remote_name, remote_id)| RemoteOp::Set(remote_name, remote_id))
+        .collect();
+    apply_remote_ops(&mut remotes, &ops)?;
+    if let Some(position) = &set_args.position {
+        place_remotes(&mut remotes, &remotes_set, position)?;
+    }
+    for remote_name in &remotes_set {
+        let remote = &mut remotes[remote_name];
+        if let Some(protected) = set_args.protected {
+            remote.protected = protected;
+        }
+        if let Some(description) = &description {
+            remote.description = description.clone();
+        }
+    }
+    let set_ids: Vec<RemoteId> = remotes_set
+        .iter()
+        .map(|name| remotes[name].id.clone())
+        .collect();
+    for (remote_name, remote_id) in remotes_set.iter().zip(&set_ids) {
+        let others: Vec<String> = remotes
+            .iter()
+            .filter(|(other, remote)| *other != remote_name && remote.id == *remote_id)
+            .map(|(other, _)| other.to_string())
+            .collect();
+        if !others.is_empty() {
+            warn(
+                settings,
+                format!(
+                    "{} has the same script ID as {}.",
+                    remote_name,
+                    others.join(", ")
+                ),
+            )?;
+        }
+    }
+
+    let mut new_config = ClaspConfig {
+        club_remotes: Some(remotes),
+        ..config
+    };
+    if let Some(env_remotes) = &mut new_config.env_remotes {
+        env_remotes.persist(&remotes_set);
+    }
+
+    ctx.write_config(&new_config)?;
+    remember_recent_ids(settings, &set_ids);
+    Ok(())
+}
+
+/// Reads a remote description for `set --description-from-file`, with Windows line endings and
+/// trailing whitespace dropped. An empty file means no description.
+fn read_description(path: &Path) -> Result<Option<String>, ClubError> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| ClubError::DescriptionReadFail(path.to_path_buf(), e.to_string()))?;
+    let description = text.replace("\r\n", "\n").trim_end().to_string();
+    if description.is_empty() {
+        return Ok(None);
+    }
+    let length = description.chars().count();
+    if length > DESCRIPTION_LIMIT {
+        return Err(ClubError::DescriptionTooLong(length));
+    }
+    if let Some(c) = description.chars().find(|c| c.is_control() && *c != '\n') {
+        return Err(ClubError::DescriptionControlChar(c));
+    }
+    Ok(Some(description))
+}
+
+/// Reads recently used script IDs, most recent first, skipping duplicates and anything that isn't a
+/// valid script ID. A missing file is an empty history.
+fn read_recent_ids(path: &Path) -> Vec<RemoteId> {
+    let mut ids: Vec<RemoteId> = Vec::new();
+    for line in std::fs::read_to_string(path).unwrap_or_default().lines() {
+        if let Ok(id) = RemoteId::try_from(line.trim().to_string()) {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+    ids
+}
+
+/// Moves `ids` to the front of the recent-IDs history, keeping at most RECENT_IDS_LIMIT entries.
+/// Recording history is best-effort and never fails the command that used the IDs.
+fn remember_recent_ids(settings: &Settings, ids: &[RemoteId]) {
+    let Some(path) = settings.recent_ids.as_ref().filter(|_| !settings.no_write) else {
+        return;
+    };
+    let older = read_recent_ids(path);
+    let mut recent: Vec<&str> = Vec::new();
+    for id in ids.iter().rev().chain(&older) {
+        if !recent.contains(&id.0.as_str()) {
+            recent.push(&id.0);
+        }
+    }
+    recent.truncate(RECENT_IDS_LIMIT);
+    trace!("Remembering recent script IDs in {}", path.display());
+    let _ = std::fs::write(path, recent.join("\n") + "\n");
+}
+
+/// Asks the user to pick one of `candidates`, labelling each with the remotes that already use it.
+/// Typing a script ID directly also works; it's validated like any other ID.
+fn pick_script_id(
+    candidates: &[RemoteId],
+    remotes: &IndexMap<RemoteName, Remote>,
+) -> Option<String> {
+    for (index, id) in candidates.iter().enumerate() {
+        let users: Vec<String> = remotes
+            .iter()
+            .filter(|(_, remote)| remote.id == *id)
+            .map(|(name, _)| name.to_string())
+            .collect();
+        if users.is_empty() {
+            eprintln!("{}) {}", index + 1, id);
+        } else {
+            eprintln!(
+                "{}) {} {}",
+                index + 1,
+                id,
+                format!("({})", users.join(", ")).dimmed()
+            );
+        }
+    }
+    eprint!("Set to which script ID? ");
+    std::io::stderr().flush().ok()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).ok()?;
+    parse_id_choice(answer.trim(), candidates)
+}
+
+/// Reads a script ID picker answer: a 1-based number, or a script ID typed out.
+fn parse_id_choice(answer: &str, candidates: &[RemoteId]) -> Option<String> {
+    if answer.is_empty() {
+        return None;
+    }
+    match answer.parse::<usize>() {
+        Ok(number) => candidates
+            .get(number.checked_sub(1)?)
+            .map(|id| id.0.clone()),
+        Err(_) => Some(answer.to_string()),
+    }
+}
+
+/// Parses whitespace-separated `name id` lines, skipping blank lines and `#` comments. Every line
+/// is validated before anything is returned, so callers can apply the batch atomically.
+fn parse_remote_lines(
+    reader: impl BufRead,
+    validate_ids: bool,
+) -> Result<Vec<(RemoteName, RemoteId)>, ClubError> {
+    let mut entries = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.map_err(|e| ClubError::InvalidBatchLine(line_number, e.to_string()))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [name, id] = fields[..] else {
+            return Err(ClubError::InvalidBatchLine(
+                line_number,
+                "expected a remote name and a script ID".to_string(),
+            ));
+        };
+        let remote_name = RemoteName::parse_new(name.to_string())
+            .map_err(|e| ClubError::InvalidBatchLine(line_number, e.to_string()))?;
+        let remote_id = RemoteId::parse(id.to_string(), validate_ids)
+            .map_err(|e| ClubError::InvalidBatchLine(line_number, e.to_string()))?;
+        entries.push((remote_name, remote_id));
+    }
+    Ok(entries)
+}
+
+fn club_init(init_args: InitCommand, ctx: &Context, settings: &Settings) -> Result<(), ClubError> {
+    match ctx.config().cloned() {
+        Ok(ClaspConfig {
+            club_remotes: Some(_),
+            ..
+        }) => Err(ClubError::ClubAlreadySetup),
+        Err(err) => Err(err),
+        Ok(config) => {
+            let mut club_remotes = IndexMap::new();
+
+            // If there's a valid script ID in the manifest already, add it as the main remote
+            let mut created_main = false;
+            if RemoteId::parse(config.script_id.clone(), settings.validate_ids).is_ok() {
+                club_remotes.insert(
+                    RemoteName("main".to_string()),
+                    Remote::from(RemoteId(config.script_id.clone())),
+                );
+                created_main = true;
+            }
+
+            let scan = match &init_args.from_existing {
+                Some(pattern) => Some(scan_existing_manifests(
+                    &get_project_dir(&ctx.manifest_path),
+                    pattern,
+                    &club_remotes,
+                    settings.validate_ids,
+                )?),
+                None => None,
+            };
+            if let Some(scan) = &scan {
+                for (_, name, id) in &scan.found {
+                    club_remotes.insert(name.clone(), Remote::from(id.clone()));
+                }
+            }
+            if init_args.detect_remotes {
+                let history = detect_historical_remotes(
+                    &ctx.manifest_path,
+                    &club_remotes,
+                    settings.validate_ids,
+                    run_git,
+                )?;
+                if history.found.is_empty() {
+                    if !settings.quiet {
+                        eprintln!("No other script IDs found in the manifest's git history.");
+                    }
+                } else {
+                    history.print();
+                    if init_args.yes || confirm("Add these remotes?") {
+                        for (_, name, id) in history.found {
+                            club_remotes.insert(name, Remote::from(id));
+                        }
+                    }
+                }
+            }
+
+            let new_config = ClaspConfig {
+                root_dir: config.root_dir,
+                script_id: config.script_id.clone(),
+                parent_ids: config.parent_ids,
+                club_remotes: Some(club_remotes),
+                club_groups: config.club_groups,
+                club_settings: ClubSettings {
+                    version: Some(CLUB_SCHEMA_VERSION),
+                    ..config.club_settings
+                },
+                env_remotes: config.env_remotes,
+                remote_file: config.remote_file,
+                push_root_dir: None,
+            };
+            ctx.write_config(&new_config)?;
+            if settings.quiet {
+                return Ok(());
+            }
+            if created_main {
+                eprintln!(
+                    "Club initialized with main remote set to manifest's scriptId: {}",
+                    config.script_id
+                );
+            } else {
+                eprintln!("Club initialized an empty configuration.");
+            }
+            if let Some(scan) = scan {
+                scan.print();
+            }
+            Ok(())
+        }
+    }
+}
+
+/// What `init --from-existing` found: a remote for each usable file, and why the rest were skipped.
+#[derive(Debug, Default)]
+struct ExistingScan {
+    found: Vec<(PathBuf, RemoteName, RemoteId)>,
+    skipped: Vec<(PathBuf, String)>,
+}
+
+impl ExistingScan {
+    fn print(&self) {
+        for (path, name, id) in &self.found {
+            eprintln!("+ {} ({}) from {}", name, id, path.display());
+        }
+        for (path, reason) in &self.skipped {
+            eprintln!(
+                "{}",
+                format!("- skipped {}: {}", path.display(), reason).yellow()
+            );
+        }
+    }
+}
+
+/// What `init --detect-remotes` found: a remote for each script ID the manifest has had in git
+/// history, named in order from oldest, and the commits whose scriptId was invalid.
+#[derive(Debug, Default)]
+struct HistoryScan {
+    found: Vec<(String, RemoteName, RemoteId)>,
+    skipped: Vec<(String, String)>,
+}
+
+impl HistoryScan {
+    fn print(&self) {
+        for (commit, name, id) in &self.found {
+            eprintln!("+ {} ({}) from commit {}", name, id, commit);
+        }
+        for (commit, reason) in &self.skipped {
+            eprintln!(
+                "{}",
+                format!("- skipped commit {}: {}", commit, reason).yellow()
+            );
+        }
+    }
+}
+
+/// Walks the git history of the manifest at `manifest_path` from oldest to newest, collecting each
+/// distinct `scriptId` it has had as a `historical-N` remote. IDs already in `existing` are left
+/// out, and names taken there are skipped over.
+fn detect_historical_remotes(
+    manifest_path: &Path,
+    existing: &IndexMap<RemoteName, Remote>,
+    validate_ids: bool,
+    git: GitRunner,
+) -> Result<HistoryScan, ClubError> {
+    let project_dir = get_project_dir(manifest_path);
+    let file_name = manifest_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let log = git(
+        &project_dir,
+        &["log", "--reverse", "--format=%h", "--", &file_name],
+    )
+    .ok_or_else(|| ClubError::NoGitHistory(manifest_path.to_path_buf()))?;
+
+    let mut scan = HistoryScan::default();
+    let mut seen: std::collections::HashSet<RemoteId> =
+        existing.values().map(|remote| remote.id.clone()).collect();
+    let mut next_number = 1;
+    for commit in log.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        // A commit that deleted the manifest has nothing to show.
+        let Some(text) = git(
+            &project_dir,
+            &["show", &format!("{}:./{}", commit, file_name)],
+        ) else {
+            continue;
+        };
+        let Some(script_id) = serde_json::from_str::<Value>(&text).ok().and_then(|json| {
+            json.get("scriptId")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        }) else {
+            continue;
+        };
+        let id = match RemoteId::parse(script_id.clone(), validate_ids) {
+            Ok(id) => id,
+            Err(_) => {
+                scan.skipped.push((
+                    commit.to_string(),
+                    format!("invalid script ID {}", script_id),
+                ));
+                continue;
+            }
+        };
+        if !seen.insert(id.clone()) {
+            continue;
+        }
+        let name = loop {
+            let name = RemoteName(format!("historical-{}", next_number));
+            next_number += 1;
+            if !existing.contains_key(&name) {
+                break name;
+            }
+        };
+        scan.found.push((commit.to_string(), name, id));
+    }
+    Ok(scan)
+}
+
+/// Scans files matching `pattern` for clasp-style `scriptId`s. The wildcards apply to the file
+/// name only; any directory part of the pattern is taken relative to `project_dir`. Files whose
+/// name or ID duplicates one already in `existing` (or found earlier) are skipped.
+fn scan_existing_manifests(
+    project_dir: &Path,
+    pattern: &str,
+    existing: &IndexMap<RemoteName, Remote>,
+    validate_ids: bool,
+) -> Result<ExistingScan, ClubError> {
+    let pattern_path = project_dir.join(pattern);
+    let dir = pattern_path.parent().unwrap_or(project_dir);
+    let file_pattern = pattern_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| ClubError::ImportReadFail(format!("{}: {}", dir.display(), e)))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| glob_matches(&file_pattern, &name.to_string_lossy()))
+        })
+        .collect();
+    paths.sort();
+
+    let mut scan = ExistingScan::default();
+    for path in paths {
+        match existing_remote(&path, validate_ids) {
+            Err(reason) => scan.skipped.push((path, reason)),
+            Ok((name, id)) => {
+                let duplicate = existing
+                    .iter()
+                    .map(|(name, remote)| (name, &remote.id))
+                    .chain(scan.found.iter().map(|(_, name, id)| (name, id)))
+                    .find_map(|(known_name, known_id)| {
+                        if *known_name == name {
+                            Some(format!("remote {} already exists", name))
+                        } else if *known_id == id {
+                            Some(format!("script ID already used by {}", known_name))
+                        } else {
+                            None
+                        }
+                    });
+                match duplicate {
+                    Some(reason) => scan.skipped.push((path, reason)),
+                    None => scan.found.push((path, name, id)),
+                }
+            }
+        }
+    }
+    Ok(scan)
+}
+
+/// Reads the remote a manifest-like file describes: its `scriptId`, named after the file.
+fn existing_remote(path: &Path, validate_ids: bool) -> Result<(RemoteName, RemoteId), String> {
+    let name = remote_name_from_file(path).ok_or("no usable remote name in file name")?;
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let json: Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    let script_id = json
+        .get("scriptId")
+        .and_then(Value::as_str)
+        .ok_or("no scriptId")?;
+    let id = RemoteId::parse(script_id.to_string(), validate_ids)
+        .map_err(|_| format!("invalid script ID {}", script_id))?;
+    Ok((name, id))
+}
+
+/// Derives a remote name from a file stem, so `.clasp.staging.json` becomes `staging` and
+/// `prod backup.json` becomes `prod-backup`.
+fn remote_name_from_file(path: &Path) -> Option<RemoteName> {
+    let stem = path.file_stem()?.to_string_lossy();
+    let stem = stem.trim_start_matches('.');
+    let stem = stem.strip_prefix("clasp.").unwrap_or(stem);
+    RemoteName::parse_new(sanitize_remote_name(stem)).ok()
+}
+
+/// Replaces each character a remote name can't have with `-`, dropping any at the ends.
+fn sanitize_remote_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    sanitized.trim_matches('-').to_string()
+}
+
+fn club_remove(remove_args: RemoveCommand, ctx: &Context) -> Result<(), ClubError> {
+    let config = ctx.config()?.clone();
+
+    let remote_name = RemoteName::try_from(remove_args.name)?;
+    let mut remotes = config.club_remotes.ok_or(ClubError::ClubNotSetup)?;
+    if remotes.shift_remove(&remote_name).is_none() {
+        return Err(ClubError::RemoteNotFound);
+    }
+    if remove_args.dry_run {
+        eprintln!("{}", describe_removal(&remote_name));
+        return Ok(());
+    }
+    let mut groups = config.club_groups.clone();
+    for members in groups.values_mut() {
+        members.retain(|member| *member != remote_name);
+    }
+    let mut settings = config.club_settings.clone();
+    if settings.active_remote.as_ref() == Some(&remote_name) {
+        settings.active_remote = None;
+    }
+    let new_config = ClaspConfig {
+        club_remotes: Some(remotes),
+        club_groups: groups,
+        club_settings: settings,
+        ..config
+    };
+
+    ctx.write_config(&new_config)
+}
+
+fn club_reassign(
+    reassign_args: ReassignCommand,
+    ctx: &Context,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    let mut config = ctx.config()?.clone();
+
+    let remote_name = RemoteName::try_from(reassign_args.name)?;
+    let new_id = RemoteId::parse(reassign_args.id, settings.validate_ids)?;
+    warn_if_legacy_id(settings, &new_id)?;
+    // Checked before the remote changes, so that a manifest pointing at the old ID through this
+    // remote follows it to the new one.
+    let was_current = active_remote(&config) == Some(&remote_name);
+    let remote = config
+        .club_remotes
+        .as_mut()
+        .ok_or(ClubError::ClubNotSetup)?
+        .get_mut(&remote_name)
+        .ok_or(ClubError::RemoteNotFound)?;
+    let old_id = std::mem::replace(&mut remote.id, new_id.clone());
+    if was_current {
+        config.script_id = new_id.0.clone();
+    }
+    if let Some(env_remotes) = &mut config.env_remotes {
+        env_remotes.persist([&remote_name]);
+    }
+    ctx.write_config(&config)?;
+    if !settings.quiet {
+        eprintln!("Reassigned {}: {} -> {}", remote_name, old_id, new_id);
+    }
+    Ok(())
+}
+
+fn club_ensure(
+    ensure_args: EnsureCommand,
+    ctx: &Context,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    let mut config = ctx.config()?.clone();
+
+    let remote_name = RemoteName::try_from(ensure_args.name)?;
+    let remote_id = RemoteId::parse(ensure_args.id, settings.validate_ids)?;
+    warn_if_legacy_id(settings, &remote_id)?;
+    let was_current = active_remote(&config) == Some(&remote_name);
+    let remotes = config
+        .club_remotes
+        .as_mut()
+        .ok_or(ClubError::ClubNotSetup)?;
+    let current = remotes.get(&remote_name).map(|remote| remote.id.clone());
+    if current.is_none() && !remote_name.is_well_formed() {
+        return Err(ClubError::InvalidRemoteName);
+    }
+    if current.as_ref() == Some(&remote_id) {
+        println!("unchanged");
+        return Ok(());
+    }
+    if ensure_args.check {
+        return Err(ClubError::RemoteDrift(remote_name, current, remote_id));
+    }
+
+    remotes
+        .entry(remote_name.clone())
+        .or_insert_with(|| Remote::from(remote_id.clone()))
+        .id = remote_id.clone();
+    // As with reassign, a manifest pointing at the remote follows it to the new ID.
+    if was_current {
+        config.script_id = remote_id.0.clone();
+    }
+    if let Some(env_remotes) = &mut config.env_remotes {
+        env_remotes.persist([&remote_name]);
+    }
+    ctx.write_config(&config)?;
+    remember_recent_ids(settings, &[remote_id]);
+    println!("updated");
+    Ok(())
+}
+
+fn club_swap(swap_args: SwapCommand, ctx: &Context, settings: &Settings) -> Result<(), ClubError> {
+    let mut config = ctx.config()?.clone();
+
+    let a = RemoteName::try_from(swap_args.a)?;
+    let b = RemoteName::try_from(swap_args.b)?;
+    // As with reassign, a manifest pointing at one of the remotes keeps following it by name.
+    let active = active_remote(&config).cloned();
+    let remotes = config
+        .club_remotes
+        .as_mut()
+        .ok_or(ClubError::ClubNotSetup)?;
+    let a_id = remotes.get(&a).ok_or(ClubError::RemoteNotFound)?.id.clone();
+    let b_id = remotes.get(&b).ok_or(ClubError::RemoteNotFound)?.id.clone();
+    remotes[&a].id = b_id.clone();
+    remotes[&b].id = a_id.clone();
+    if active.as_ref() == Some(&a) {
+        config.script_id = b_id.0.clone();
+    } else if active.as_ref() == Some(&b) {
+        config.script_id = a_id.0.clone();
+    }
+    ctx.write_config(&config)?;
+    if !settings.quiet {
+        eprintln!("Swapped {} and {}:", a, b);
+        eprintln!("  {}: {} -> {}", a, a_id, b_id);
+        eprintln!("  {}: {} -> {}", b, b_id, a_id);
+    }
+    Ok(())
+}
+
+/// What `remove --dry-run` reports.
+fn describe_removal(remote_name: &RemoteName) -> String {
+    format!("Would remove {}", remote_name)
+}
+
+/// What `rename --dry-run` reports.
+fn describe_rename(old_name: &RemoteName, new_name: &RemoteName) -> String {
+    format!("Would rename {} -> {}", old_name, new_name)
+}
+
+/// What `rename --swap --dry-run` reports when the two names are exchanged.
+fn describe_name_swap(a: &RemoteName, b: &RemoteName) -> String {
+    format!("Would swap the names {} <-> {}", a, b)
+}
+
+/// A note for renames that only change a name's case, which look like no-ops to anyone used to
+/// case-insensitive names.
+fn case_only_rename_note(old_name: &RemoteName, new_name: &RemoteName) -> Option<String> {
+    (old_name != new_name && old_name.0.eq_ignore_ascii_case(&new_name.0)).then(|| {
+        format!(
+            "Note: {} and {} differ only in case. Remote names are case-sensitive, so the remote is now {} and commands must use that spelling.",
+            old_name, new_name, new_name
+        )
+    })
+}
+
+fn club_rename(
+    rename_args: RenameCommand,
+    ctx: &Context,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    let config = ctx.config()?.clone();
+
+    let old_name = RemoteName::try_from(rename_args.old_name)?;
+    let new_name = RemoteName::parse_new(rename_args.new_name)?;
+    let mut remotes = config.club_remotes.ok_or(ClubError::ClubNotSetup)?;
+    let swap = remotes.contains_key(&new_name);
+    if swap && !rename_args.swap {
+        return Err(ClubError::RemoteAlreadyExists);
+    }
+    if !remotes.contains_key(&old_name) {
+        return Err(ClubError::RemoteNotFound);
+    }
+    if rename_args.dry_run {
+        if swap {
+            eprintln!("{}", describe_name_swap(&old_name, &new_name));
+        } else {
+            eprintln!("{}", describe_rename(&old_name, &new_name));
+        }
+        return Ok(());
+    }
+    // Groups and the active remote follow each remote to its new name. When swapping, the two
+    // remotes trade everything but their place in the manifest.
+    let renamed = |name: &RemoteName| {
+        if *name == old_name {
+            new_name.clone()
+        } else if swap && *name == new_name {
+            old_name.clone()
+        } else {
+            name.clone()
+        }
+    };
+    let mut groups = config.club_groups.clone();
+    for member in groups.values_mut().flatten() {
+        *member = renamed(member);
+    }
+    let mut club_settings = config.club_settings.clone();
+    club_settings.active_remote = club_settings.active_remote.as_ref().map(renamed);
+    if swap {
+        let old_index = remotes.get_index_of(&old_name).unwrap();
+        let new_index = remotes.get_index_of(&new_name).unwrap();
+        let old_remote = remotes[old_index].clone();
+        remotes[old_index] = std::mem::replace(&mut remotes[new_index], old_remote);
+    } else {
+        apply_remote_ops(
+            &mut remotes,
+            &[RemoteOp::Rename(old_name.clone(), new_name.clone())],
+        )?;
+    }
+    let new_config = ClaspConfig {
+        club_remotes: Some(remotes),
+        club_groups: groups,
+        club_settings,
+        ..config
+    };
+
+    ctx.write_config(&new_config)?;
+    if let Some(note) = case_only_rename_note(&old_name, &new_name) {
+        if !settings.quiet {
+            eprintln!("{}", note);
+        }
+    }
+    Ok(())
+}
+
+/// The new name for each remote whose name changes case, keeping the manifest's order. Fails without
+/// renaming anything if two remotes would share a name.
+fn normalized_names(
+    remotes: &IndexMap<RemoteName, Remote>,
+    normalize: impl Fn(&str) -> String,
+) -> Result<Vec<(RemoteName, RemoteName)>, ClubError> {
+    let mut by_new_name: IndexMap<String, Vec<RemoteName>> = IndexMap::new();
+    for remote_name in remotes.keys() {
+        by_new_name
+            .entry(normalize(&remote_name.0))
+            .or_default()
+            .push(remote_name.clone());
+    }
+    let collisions: Vec<(Vec<RemoteName>, RemoteName)> = by_new_name
+        .iter()
+        .filter(|(_, names)| names.len() > 1)
+        .map(|(new_name, names)| (names.clone(), RemoteName(new_name.clone())))
+        .collect();
+    if !collisions.is_empty() {
+        return Err(ClubError::NameCollision(collisions));
+    }
+    by_new_name
+        .into_iter()
+        .filter_map(|(new_name, mut names)| {
+            let old_name = names.remove(0);
+            (old_name.0 != new_name)
+                .then(|| RemoteName::parse_new(new_name).map(|new_name| (old_name, new_name)))
+        })
+        .collect()
+}
+
+/// The files club keeps beside the manifest, which move with it.
+const PROJECT_STATE_NAMES: &[&str] = &[PUSHED_STATE_NAME, HISTORY_NAME];
+
+fn club_mv_manifest(
+    mv_args: MvManifestCommand,
+    ctx: &Context,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    let move_err = |e: std::io::Error| ClubError::ManifestMoveFail(e.to_string());
+    let old_dir = normalize_path(
+        &std::path::absolute(get_project_dir(&ctx.manifest_path)).map_err(move_err)?,
+    );
+    let new_dir = normalize_path(&std::path::absolute(&mv_args.new_dir).map_err(move_err)?);
+    let manifest_name = ctx
+        .manifest_path
+        .file_name()
+        .unwrap_or(MANIFEST_NAME.as_ref());
+    let new_manifest_path = new_dir.join(manifest_name);
+    if new_manifest_path.exists() {
+        return Err(ClubError::MoveTargetExists(new_manifest_path));
+    }
+    // Settings left behind would silently stop applying, and merging two .clubrc files is guesswork.
+    let moves_settings = old_dir.join(SETTINGS_NAME).exists();
+    if moves_settings && new_dir.join(SETTINGS_NAME).exists() {
+        return Err(ClubError::MoveTargetExists(new_dir.join(SETTINGS_NAME)));
+    }
+
+    let mut config = ctx.config()?.clone();
+    let old_root_dir = config.root_dir.clone();
+    if !Path::new(&old_root_dir).is_absolute() {
+        let source = normalize_path(&config.resolved_root_dir(&old_dir));
+        config.root_dir = relative_path(&new_dir, &source)
+            .to_string_lossy()
+            .into_owned();
+    }
+    if !settings.no_write {
+        std::fs::create_dir_all(&new_dir).map_err(move_err)?;
+    }
+    Context::new(new_manifest_path.clone(), settings.validate_ids)
+        .with_settings(settings)
+        .write_config(&config)?;
+    if settings.no_write {
+        return Ok(());
+    }
+    std::fs::remove_file(&ctx.manifest_path).map_err(move_err)?;
+    if moves_settings {
+        move_settings(&old_dir, &new_dir)?;
+    }
+    for name in PROJECT_STATE_NAMES {
+        let (from, to) = (old_dir.join(name), new_dir.join(name));
+        if from.exists() && !to.exists() {
+            std::fs::rename(&from, &to).map_err(move_err)?;
+        }
+    }
+    if !settings.quiet {
+        eprintln!(
+            "Moved {} to {}.",
+            ctx.manifest_path.display(),
+            new_manifest_path.display()
+        );
+        if config.root_dir != old_root_dir {
+            eprintln!("rootDir: `{}` -> `{}`", old_root_dir, config.root_dir);
+        }
+    }
+    Ok(())
+}
+
+/// Moves `.clubrc` from `old_dir` to `new_dir`, rewriting a relative `remoteFile` so that it still
+/// points at the same file.
+fn move_settings(old_dir: &Path, new_dir: &Path) -> Result<(), ClubError> {
+    let move_err = |e: std::io::Error| ClubError::ManifestMoveFail(e.to_string());
+    let (from, to) = (old_dir.join(SETTINGS_NAME), new_dir.join(SETTINGS_NAME));
+    let settings_str = std::fs::read_to_string(&from).map_err(move_err)?;
+    let mut settings_json: Value = serde_json::from_str(&settings_str)
+        .map_err(|e| ClubError::SettingsReadFail(e.to_string()))?;
+    let remote_file = settings_json["remoteFile"]
+        .as_str()
+        .map(parse_path)
+        .transpose()
+        .map_err(ClubError::SettingsReadFail)?;
+    let Some(remote_file) = remote_file.filter(|remote_file| remote_file.is_relative()) else {
+        return std::fs::rename(&from, &to).map_err(move_err);
+    };
+    let source = normalize_path(&old_dir.join(remote_file));
+    settings_json["remoteFile"] = Value::String(
+        relative_path(new_dir, &source)
+            .to_string_lossy()
+            .into_owned(),
+    );
+    std::fs::write(
+        &to,
+        format!(
+            "{}\n",
+            serde_json::to_string_pretty(&settings_json).unwrap()
+        ),
+    )
+    .map_err(move_err)?;
+    std::fs::remove_file(&from).map_err(move_err)
+}
+
+/// Resolves `.` and `..` in an absolute path without following symlinks.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// The path to `to` from the directory `from`, both absolute and normalized. Empty if they're the
+/// same, which clasp reads as the manifest's own directory.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<_> = from.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    for component in &to[common..] {
+        relative.push(component);
+    }
+    relative
+}
+
+/// Renames remotes all at once, each keeping its place in the manifest. Groups and the active remote
+/// follow the renames.
+fn rename_remotes(config: &mut ClaspConfig, renames: &[(RemoteName, RemoteName)]) {
+    let new_name_of = |remote_name: &RemoteName| {
+        renames
+            .iter()
+            .find(|(old_name, _)| old_name == remote_name)
+            .map_or(remote_name.clone(), |(_, new_name)| new_name.clone())
+    };
+    if let Some(remotes) = config.club_remotes.take() {
+        config.club_remotes = Some(
+            remotes
+                .into_iter()
+                .map(|(remote_name, remote)| (new_name_of(&remote_name), remote))
+                .collect(),
+        );
+    }
+    for member in config.club_groups.values_mut().flatten() {
+        *member = new_name_of(member);
+    }
+    config.club_settings.active_remote =
+        config.club_settings.active_remote.as_ref().map(new_name_of);
+}
+
+fn club_normalize_names(
+    normalize_args: NormalizeNamesCommand,
+    ctx: &Context,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    let config = ctx.config()?.clone();
+    let remotes = config.club_remotes.clone().ok_or(ClubError::ClubNotSetup)?;
+    let renames = if normalize_args.upper {
+        normalized_names(&remotes, str::to_uppercase)?
+    } else {
+        normalized_names(&remotes, str::to_lowercase)?
+    };
+    if renames.is_empty() {
+        if !settings.quiet {
+            eprintln!("All remote names are already normalized.");
+        }
+        return Ok(());
+    }
+
+    let mut config = config;
+    rename_remotes(&mut config, &renames);
+    ctx.write_config(&config)?;
+    if !settings.quiet {
+        for (old_name, new_name) in &renames {
+            eprintln!("Renamed {} -> {}", old_name, new_name);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct ImportPlan {
+    added: Vec<(RemoteName, Remote)>,
+    /// Remotes whose script ID or settings change, as (name, old ID, new remote).
+    updated: Vec<(RemoteName, RemoteId, Remote)>,
+    /// Existing remotes kept despite a different ID in the file, as (name, kept ID, file's ID).
+    conflicts: Vec<(RemoteName, RemoteId, RemoteId)>,
+    unchanged: Vec<RemoteName>,
+    removed: Vec<RemoteName>,
+    invalid: Vec<(String, ClubError)>,
+}
+
+impl ImportPlan {
+    /// Plans importing `incoming`, whose entries are bare script IDs or remote objects as `club
+    /// export` prints them. A bare ID keeps an existing remote's settings, while an object's
+    /// replace them. Club's own keys, such as `__groups__`, are skipped, so an export can be
+    /// imported as-is.
+    fn new(
+        existing: &IndexMap<RemoteName, Remote>,
+        incoming: Vec<(String, Value)>,
+        replace: bool,
+        overwrite: bool,
+        validate_ids: bool,
+    ) -> ImportPlan {
+        let mut plan = ImportPlan::default();
+        let mut seen = Vec::new();
+        for (key, value) in incoming {
+            if RESERVED_REMOTE_NAMES.contains(&key.as_str()) {
+                continue;
+            }
+            let entry = RemoteName::parse_new(key.clone())
+                .and_then(|remote_name| Ok((remote_name, Remote::parse(&value, validate_ids)?)));
+            let (remote_name, mut remote) = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    plan.invalid.push((key, err));
+                    continue;
+                }
+            };
+            seen.push(remote_name.clone());
+            let old = existing.get(&remote_name);
+            if let (Some(old), Value::String(_)) = (old, &value) {
+                remote = Remote {
+                    id: remote.id,
+                    ..old.clone()
+                };
+            }
+            match old {
+                None => plan.added.push((remote_name, remote)),
+                Some(old) if *old == remote => plan.unchanged.push(remote_name),
+                Some(old) if old.id == remote.id || replace || overwrite => {
+                    plan.updated.push((remote_name, old.id.clone(), remote))
+                }
+                Some(old) => plan
+                    .conflicts
+                    .push((remote_name, old.id.clone(), remote.id)),
+            }
+        }
+        if replace {
+            plan.removed = existing
+                .keys()
+                .filter(|name| !seen.contains(name))
+                .cloned()
+                .collect();
+        }
+        plan
+    }
+
+    fn ops(&self) -> Vec<RemoteOp> {
+        let removed = self.removed.iter().cloned().map(RemoteOp::Remove);
+        let updated = self
+            .updated
+            .iter()
+            .map(|(remote_name, _, remote)| RemoteOp::Set(remote_name.clone(), remote.id.clone()));
+        let added = self
+            .added
+            .iter()
+            .map(|(remote_name, remote)| RemoteOp::Set(remote_name.clone(), remote.id.clone()));
+        removed.chain(updated).chain(added).collect()
+    }
+
+    fn apply(&self, remotes: &mut IndexMap<RemoteName, Remote>) -> Result<(), ClubError> {
+        apply_remote_ops(remotes, &self.ops())?;
+        // The ops only set script IDs, so the imported settings are filled in afterwards.
+        for (remote_name, remote) in &self.added {
+            remotes.insert(remote_name.clone(), remote.clone());
+        }
+        for (remote_name, _, remote) in &self.updated {
+            remotes.insert(remote_name.clone(), remote.clone());
+        }
+        Ok(())
+    }
+
+    fn print(&self) {
+        for line in self.lines() {
+            println!("{}", line);
+        }
+    }
+
+    /// The plan as a `club diff-config --json` report. `existing` supplies removed remotes' IDs.
+    fn diff_json(&self, existing: &IndexMap<RemoteName, Remote>) -> Value {
+        serde_json::json!({
+            "added": self
+                .added
+                .iter()
+                .map(|(name, remote)| serde_json::json!({ "name": name.0, "id": remote.id.0 }))
+                .collect::<Vec<_>>(),
+            "changed": self
+                .updated
+                .iter()
+                .map(|(name, local_id, remote)| {
+                    serde_json::json!({ "name": name.0, "local_id": local_id.0, "file_id": remote.id.0 })
+                })
+                .collect::<Vec<_>>(),
+            "removed": self
+                .removed
+                .iter()
+                .map(|name| serde_json::json!({ "name": name.0, "id": existing[name].id.0 }))
+                .collect::<Vec<_>>(),
+            "invalid": self
+                .invalid
+                .iter()
+                .map(|(key, err)| serde_json::json!({ "name": key, "error": err.to_string() }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    /// The plan as diff-style lines: green additions, yellow ID changes and conflicts, and red
+    /// removals. Whether the colors are shown follows `--color` and `NO_COLOR`.
+    fn lines(&self) -> Vec<ColoredString> {
+        let mut lines = Vec::new();
+        for (remote_name, remote) in &self.added {
+            lines.push(format!("+ {}: {}", remote_name, remote.id).green());
+        }
+        for (remote_name, old_id, remote) in &self.updated {
+            let change = if *old_id == remote.id {
+                format!("~ {}: {} (settings changed)", remote_name, old_id)
+            } else {
+                format!("~ {}: {} -> {}", remote_name, old_id, remote.id)
+            };
+            lines.push(change.yellow());
+        }
+        lines.extend(self.conflict_lines());
+        for remote_name in &self.unchanged {
+            lines.push(ColoredString::from(format!("  {}", remote_name)));
+        }
+        for remote_name in &self.removed {
+            lines.push(format!("- {}", remote_name).red());
+        }
+        for (key, err) in &self.invalid {
+            lines.push(format!("! {}: {}", key, err).red().bold());
+        }
+        lines
+    }
+
+    fn print_conflicts(&self) {
+        for line in self.conflict_lines() {
+            eprintln!("{}", line);
+        }
+    }
+
+    fn conflict_lines(&self) -> Vec<ColoredString> {
+        self.conflicts
+            .iter()
+            .map(|(remote_name, kept_id, file_id)| {
+                format!(
+                    "= {}: keeping {} (file has {})",
+                    remote_name, kept_id, file_id
+                )
+                .yellow()
+            })
+            .collect()
+    }
+}
+
+/// Reads a JSON file mapping remote names to script IDs, as used by `diff-config`.
+fn read_remotes_file(path: &Path) -> Result<Vec<(String, Value)>, ClubError> {
+    let file_str =
+        std::fs::read_to_string(path).map_err(|e| ClubError::ImportReadFail(e.to_string()))?;
+    parse_remotes(&file_str, InputFormat::Json)
+}
+
+/// The format of a remotes file: the one asked for, else the one its extension names, else the one
+/// its content looks like.
+fn detect_input_format(format: InputFormat, path: Option<&Path>, text: &str) -> InputFormat {
+    if format != InputFormat::Auto {
+        return format;
+    }
+    match path
+        .and_then(Path::extension)
+        .and_then(OsStr::to_str)
+        .map(str::to_ascii_lowercase)
+        .as_deref()
+    {
+        Some("json") => InputFormat::Json,
+        Some("yaml" | "yml") => InputFormat::Yaml,
+        Some("toml") => InputFormat::Toml,
+        _ => sniff_input_format(text),
+    }
+}
+
+/// Guesses a format from the first line that isn't blank or a comment: a TOML `[section]` header
+/// or `key =`, a YAML `---` or `key:`, and JSON for anything else, including a leading `{`.
+fn sniff_input_format(text: &str) -> InputFormat {
+    let Some(line) = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+    else {
+        return InputFormat::Json;
+    };
+    let toml_header = Regex::new(r#"^\[\s*[A-Za-z0-9_."'-]+\s*\]$"#).unwrap();
+    let toml_key = Regex::new(r#"^[A-Za-z0-9_"'-]+\s*="#).unwrap();
+    let yaml_key = Regex::new(r#"^[A-Za-z0-9_"'-]+\s*:"#).unwrap();
+    if toml_header.is_match(line) || toml_key.is_match(line) {
+        InputFormat::Toml
+    } else if line.starts_with("---") || yaml_key.is_match(line) {
+        InputFormat::Yaml
+    } else {
+        InputFormat::Json
+    }
+}
+
+/// Parses a mapping of remote names to script IDs (or remote objects) in `format`, which must not be
+/// `Auto`.
+fn parse_remotes(text: &str, format: InputFormat) -> Result<Vec<(String, Value)>, ClubError> {
+    let parsed: Value = match format {
+        InputFormat::Auto | InputFormat::Json => serde_json::from_str(text)
+            .map_err(|e| ClubError::ImportReadFail(format!("invalid JSON: {}", e)))?,
+        InputFormat::Yaml => serde_yaml_ng::from_str(text)
+            .map_err(|e| ClubError::ImportReadFail(format!("invalid YAML: {}", e)))?,
+        InputFormat::Toml => toml::from_str(text)
+            .map_err(|e| ClubError::ImportReadFail(format!("invalid TOML: {}", e)))?,
+    };
+    Ok(parsed
+        .as_object()
+        .ok_or(ClubError::ImportReadFail(
+            "expected an object mapping remote names to script IDs".to_string(),
+        ))?
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect())
+}
+
+fn club_import(
+    import_args: ImportCommand,
+    ctx: &Context,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    let config = ctx.config()?.clone();
+    let remotes = config.club_remotes.clone().ok_or(ClubError::ClubNotSetup)?;
+
+    let text = match &import_args.file {
+        Some(file) => std::fs::read_to_string(file),
+        None => std::io::read_to_string(std::io::stdin()),
+    }
+    .map_err(|e| ClubError::ImportReadFail(e.to_string()))?;
+    let format = detect_input_format(import_args.input_format, import_args.file.as_deref(), &text);
+    debug!("Reading imported remotes as {:?}", format);
+    let incoming = parse_remotes(&text, format)?;
+    let mut plan = ImportPlan::new(
+        &remotes,
+        incoming,
+        import_args.replace,
+        import_args.overwrite,
+        settings.validate_ids,
+    );
+    if import_args.dry_run {
+        plan.print();
+        return Ok(());
+    }
+    if !plan.invalid.is_empty() {
+        let (key, err) = plan.invalid.remove(0);
+        return Err(ClubError::InvalidImportEntry(key, Box::new(err)));
+    }
+
+    let mut new_remotes = remotes;
+    plan.apply(&mut new_remotes)?;
+    // Remotes dropped by --replace leave their groups and stop being active, as with `club remove`.
+    let mut groups = config.club_groups.clone();
+    for members in groups.values_mut() {
+        members.retain(|member| !plan.removed.contains(member));
+    }
+    let mut club_settings = config.club_settings.clone();
+    if club_settings
+        .active_remote
+        .as_ref()
+        .is_some_and(|active| plan.removed.contains(active))
+    {
+        club_settings.active_remote = None;
+    }
+    let new_config = ClaspConfig {
+        club_remotes: Some(new_remotes),
+        club_groups: groups,
+        club_settings,
+        ..config
+    };
+    ctx.write_config(&new_config)?;
+    if settings.quiet {
+        return Ok(());
+    }
+    eprintln!(
+        "Imported remotes: {} added, {} updated, {} unchanged, {} removed.",
+        plan.added.len(),
+        plan.updated.len(),
+        plan.unchanged.len(),
+        plan.removed.len()
+    );
+    if !plan.conflicts.is_empty() {
+        eprintln!(
+            "{} conflicting remote(s) kept their existing script IDs. Pass --overwrite to replace them.",
+            plan.conflicts.len()
+        );
+        plan.print_conflicts();
+    }
+    Ok(())
+}
+
+fn club_diff_config(
+    diff_args: DiffConfigCommand,
+    ctx: &Context,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    let remotes = ctx
+        .config()?
+        .club_remotes
+        .as_ref()
+        .ok_or(ClubError::ClubNotSetup)?;
+    let incoming = read_remotes_file(&diff_args.file)?;
+    // A replacing, overwriting import plan is exactly the difference between the two.
+    let plan = ImportPlan::new(remotes, incoming, true, true, settings.validate_ids);
+    if settings.json {
+        return print_json(&plan.diff_json(remotes), settings);
+    }
+    if plan.added.is_empty()
+        && plan.updated.is_empty()
+        && plan.removed.is_empty()
+        && plan.invalid.is_empty()
+    {
+        println!("Remotes match {}.", diff_args.file.display());
+        return Ok(());
+    }
+    plan.print();
+    Ok(())
+}
+
+/// Structural observations about a project's remotes, for `club audit`.
+#[derive(Debug, PartialEq)]
+struct Audit {
+    duplicate_ids: Vec<(RemoteId, Vec<RemoteName>)>,
+    invalid_ids: Vec<(RemoteName, RemoteId)>,
+    dangling_group_members: Vec<(String, RemoteName)>,
+    active_remote: Option<RemoteName>,
+    default_remote: RemoteName,
+    default_remote_exists: bool,
+}
+
+impl Audit {
+    fn new(config: &ClaspConfig, default_remote: &RemoteName) -> Result<Audit, ClubError> {
+        let remotes = config
+            .club_remotes
+            .as_ref()
+            .ok_or(ClubError::ClubNotSetup)?;
+        let mut by_id: IndexMap<&RemoteId, Vec<RemoteName>> = IndexMap::new();
+        for (remote_name, remote) in remotes {
+            by_id
+                .entry(&remote.id)
+                .or_default()
+                .push(remote_name.clone());
+        }
+        let duplicate_ids = by_id
+            .into_iter()
+            .filter(|(_, remote_names)| remote_names.len() > 1)
+            .map(|(remote_id, remote_names)| (remote_id.clone(), remote_names))
+            .collect();
+        let invalid_ids = remotes
+            .iter()
+            .filter(|(_, remote)| RemoteId::try_from(remote.id.0.clone()).is_err())
+            .map(|(remote_name, remote)| (remote_name.clone(), remote.id.clone()))
+            .collect();
+        let mut dangling_group_members = Vec::new();
+        for (group, members) in &config.club_groups {
+            for member in members {
+                if !remotes.contains_key(member) {
+                    dangling_group_members.push((group.clone(), member.clone()));
+                }
+            }
+        }
+        Ok(Audit {
+            duplicate_ids,
+            invalid_ids,
+            dangling_group_members,
+            active_remote: active_remote(config).cloned(),
+            default_remote: default_remote.clone(),
+            default_remote_exists: remotes.contains_key(default_remote),
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        let duplicate_ids: Vec<Value> = self
+            .duplicate_ids
+            .iter()
+            .map(|(remote_id, remote_names)| {
+                let names: Vec<&str> = remote_names.iter().map(|name| name.0.as_str()).collect();
+                serde_json::json!({ "id": remote_id.0, "remotes": names })
+            })
+            .collect();
+        let invalid_ids: Vec<Value> = self
+            .invalid_ids
+            .iter()
+            .map(|(remote_name, remote_id)| {
+                serde_json::json!({ "remote": remote_name.0, "id": remote_id.0 })
+            })
+            .collect();
+        let dangling_group_members: Vec<Value> = self
+            .dangling_group_members
+            .iter()
+            .map(|(group, remote_name)| {
+                serde_json::json!({ "group": group, "remote": remote_name.0 })
+            })
+            .collect();
+        serde_json::json!({
+            "duplicate_ids": duplicate_ids,
+            "invalid_ids": invalid_ids,
+            "dangling_group_members": dangling_group_members,
+            "active_remote": self.active_remote.as_ref().map(|name| &name.0),
+            "default_remote": self.default_remote.0,
+            "default_remote_exists": self.default_remote_exists,
+        })
+    }
+
+    fn print(&self) {
+        println!("{}", "Remotes".bold());
+        match &self.active_remote {
+            Some(remote_name) => println!("  Active remote: {}", remote_name),
+            None => println!("  Active remote: none (the manifest's scriptId matches no remote)"),
+        }
+        if self.default_remote_exists {
+            println!("  Default remote {} exists.", self.default_remote);
+        } else {
+            println!(
+                "{}",
+                format!("  Default remote {} does not exist.", self.default_remote).yellow()
+            );
+        }
+
+        println!("{}", "Duplicate IDs".bold());
+        if self.duplicate_ids.is_empty() {
+            println!("  None.");
+        }
+        for (remote_id, remote_names) in &self.duplicate_ids {
+            let names: Vec<String> = remote_names.iter().map(|r| r.to_string()).collect();
+            println!(
+                "{}",
+                format!("  {} is shared by {}", remote_id, names.join(", ")).yellow()
+            );
+        }
+
+        println!("{}", "Off-format IDs".bold());
+        if self.invalid_ids.is_empty() {
+            println!("  None.");
+        }
+        for (remote_name, remote_id) in &self.invalid_ids {
+            println!("{}", format!("  {}: {}", remote_name, remote_id).yellow());
+        }
+
+        println!("{}", "Dangling group members".bold());
+        if self.dangling_group_members.is_empty() {
+            println!("  None.");
+        }
+        for (group, remote_name) in &self.dangling_group_members {
+            println!(
+                "{}",
+                format!(
+                    "  Group {} references missing remote {}",
+                    group, remote_name
+                )
+                .red()
+            );
+        }
+    }
+}
+
+fn club_audit(ctx: &Context, settings: &Settings) -> Result<(), ClubError> {
+    // Read leniently, so that off-format IDs are reported rather than failing the whole audit.
+    let config = ctx.read_config(false)?;
+    let audit = Audit::new(&config, &settings.default_remote)?;
+    if settings.json {
+        return print_json(&audit.to_json(), settings);
+    }
+    audit.print();
+    Ok(())
+}
+
+/// What `club gc` prunes: remotes with invalid script IDs, and remotes merged into an earlier remote
+/// with the same script ID, as (removed, kept). Remotes it leaves alone are listed with the reason.
+#[derive(Debug, Default, PartialEq)]
+struct GcPlan {
+    invalid: Vec<RemoteName>,
+    merged: Vec<(RemoteName, RemoteName)>,
+    spared: Vec<(RemoteName, String)>,
+}
+
+impl GcPlan {
+    fn new(
+        remotes: &IndexMap<RemoteName, Remote>,
+        default_remote: &RemoteName,
+        force: bool,
+    ) -> GcPlan {
+        let mut plan = GcPlan::default();
+        let mut first_with_id: IndexMap<&RemoteId, &RemoteName> = IndexMap::new();
+        for (remote_name, remote) in remotes {
+            let problem = if RemoteId::try_from(remote.id.0.clone()).is_err() {
+                Some(None)
+            } else if let Some(kept) = first_with_id.get(&remote.id) {
+                Some(Some((*kept).clone()))
+            } else {
+                first_with_id.insert(&remote.id, remote_name);
+                None
+            };
+            let Some(merge_into) = problem else {
+                continue;
+            };
+            if !force && (remote_name == default_remote || remote.protected) {
+                let why = if remote.protected {
+                    "protected"
+                } else {
+                    "the default remote"
+                };
+                plan.spared
+                    .push((remote_name.clone(), format!("{}; pass --force", why)));
+                continue;
+            }
+            match merge_into {
+                Some(kept) => plan.merged.push((remote_name.clone(), kept)),
+                None => plan.invalid.push(remote_name.clone()),
+            }
+        }
+        plan
+    }
+
+    fn is_empty(&self) -> bool {
+        self.invalid.is_empty() && self.merged.is_empty()
+    }
+
+    fn print(&self) {
+        for remote_name in &self.invalid {
+            println!("{}", format!("- {}: invalid script ID", remote_name).red());
+        }
+        for (removed, kept) in &self.merged {
+            println!(
+                "{}",
+                format!("- {}: same script ID as {}, merged into it", removed, kept).yellow()
+            );
+        }
+        for (remote_name, why) in &self.spared {
+            println!("  {}: left alone ({})", remote_name, why);
+        }
+    }
+
+    /// Removes the pruned remotes. Groups and the active remote follow merged remotes to the remote
+    /// they were merged into, and drop invalid ones.
+    fn apply(&self, config: &mut ClaspConfig) -> Result<(), ClubError> {
+        let replacement = |remote_name: &RemoteName| -> Option<Option<RemoteName>> {
+            if self.invalid.contains(remote_name) {
+                return Some(None);
+            }
+            self.merged
+                .iter()
+                .find(|(removed, _)| removed == remote_name)
+                .map(|(_, kept)| Some(kept.clone()))
+        };
+        if let Some(remotes) = config.club_remotes.as_mut() {
+            let removed = self
+                .invalid
+                .iter()
+                .chain(self.merged.iter().map(|(removed, _)| removed));
+            apply_remote_ops(
+                remotes,
+                &removed.cloned().map(RemoteOp::Remove).collect::<Vec<_>>(),
+            )?;
+        }
+        for members in config.club_groups.values_mut() {
+            let mut new_members: Vec<RemoteName> = Vec::new();
+            for member in members.iter() {
+                let member = match replacement(member) {
+                    None => Some(member.clone()),
+                    Some(kept) => kept,
+                };
+                if let Some(member) = member {
+                    if !new_members.contains(&member) {
+                        new_members.push(member);
+                    }
+                }
+            }
+            *members = new_members;
+        }
+        if let Some(active) = config.club_settings.active_remote.clone() {
+            if let Some(kept) = replacement(&active) {
+                config.club_settings.active_remote = kept;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn club_gc(gc_args: GcCommand, ctx: &Context, settings: &Settings) -> Result<(), ClubError> {
+    // Read leniently, so that remotes with invalid IDs can be found and removed.
+    let mut config = ctx.read_config(false)?;
+    let remotes = config
+        .club_remotes
+        .as_ref()
+        .ok_or(ClubError::ClubNotSetup)?;
+    let plan = GcPlan::new(remotes, &settings.default_remote, gc_args.force);
+    if plan.is_empty() {
+        if !settings.quiet {
+            plan.print();
+            println!("Nothing to prune.");
+        }
+        return Ok(());
+    }
+    plan.print();
+    if !gc_args.yes && !confirm("Prune these remotes?") {
+        return Err(ClubError::GcNotConfirmed);
+    }
+    plan.apply(&mut config)?;
+    ctx.write_config(&config)?;
+    if !settings.quiet {
+        eprintln!(
+            "Pruned {} remote(s): {} invalid, {} merged.",
+            plan.invalid.len() + plan.merged.len(),
+            plan.invalid.len(),
+            plan.merged.len()
+        );
+    }
+    Ok(())
+}
+
+/// Which remotes a `club push` invocation targets, validated from the raw arguments.
+#[derive(Debug, PartialEq)]
+enum PushSelection {
+    Remote(RemoteName),
+    All(PushFilters),
+    Group(String, PushFilters),
+    /// A one-off push to a script ID that isn't a remote, under the name it's reported as.
+    ScriptId(RemoteName, RemoteId),
+    /// Every remote whose whole name matches a regex. Selecting none is an error unless the flag,
+    /// set by --allow-empty, is.
+    Matching(String, PushFilters, bool),
+}
+
+/// Filters that narrow a multi-remote push.
+#[derive(Debug, Default, PartialEq)]
+struct PushFilters {
+    from: Option<RemoteName>,
+    exclude: Vec<String>,
+}
+
+impl TryFrom<&PushCommand> for PushSelection {
+    type Error = ClubError;
+
+    fn try_from(push_args: &PushCommand) -> Result<Self, Self::Error> {
+        match (&push_args.remote, push_args.all, &push_args.group) {
+            (Some(_), true, _) => return Err(ClubError::BothRemoteAndAllPassed),
+            (Some(_), _, Some(_)) => return Err(ClubError::BothRemoteAndGroupPassed),
+            (None, true, Some(_)) => return Err(ClubError::BothAllAndGroupPassed),
+            _ => {}
+        }
+        let filters = PushFilters {
+            from: push_args
+                .from
+                .clone()
+                .map(RemoteName::try_from)
+                .transpose()?,
+            exclude: push_args.exclude.clone(),
+        };
+        if let Some(pattern) = &push_args.remote_regex {
+            remote_regex(pattern)?;
+            Ok(PushSelection::Matching(
+                pattern.clone(),
+                filters,
+                push_args.allow_empty,
+            ))
+        } else if let Some(group) = &push_args.group {
+            Ok(PushSelection::Group(group.clone(), filters))
+        } else if push_args.all {
+            Ok(PushSelection::All(filters))
+        } else if filters.from.is_some() {
+            Err(ClubError::FilterWithoutMultiPush("--from"))
+        } else if !filters.exclude.is_empty() {
+            Err(ClubError::FilterWithoutMultiPush("--exclude"))
+        } else {
+            // A bare push's remote is filled in by `bare_push_target` before this, from the
+            // default remote or the user's pick.
+            let remote = push_args.remote.clone().ok_or(ClubError::RemoteRequired)?;
+            Ok(PushSelection::Remote(RemoteName::try_from(remote)?))
+        }
+    }
+}
+
+impl PushSelection {
+    fn is_only(&self, remote_name: &RemoteName) -> bool {
+        matches!(self, PushSelection::Remote(selected) if selected == remote_name)
+    }
+
+    /// Resolves the selection against the configured remotes, in push order.
+    fn targets(&self, config: &ClaspConfig) -> Result<Vec<(RemoteName, Remote)>, ClubError> {
+        let remotes = config
+            .club_remotes
+            .as_ref()
+            .ok_or(ClubError::ClubNotSetup)?;
+        let (candidates, filters) = match self {
+            PushSelection::Remote(remote_name) => {
+                let remote = remotes.get(remote_name).ok_or(ClubError::RemoteNotFound)?;
+                return Ok(vec![(remote_name.clone(), remote.clone())]);
+            }
+            PushSelection::ScriptId(remote_name, remote_id) => {
+                return Ok(vec![(remote_name.clone(), Remote::from(remote_id.clone()))]);
+            }
+            PushSelection::All(filters) => (
+                remotes
+                    .iter()
+                    .map(|(name, remote)| (name.clone(), remote.clone()))
+                    .collect::<Vec<_>>(),
+                filters,
+            ),
+            PushSelection::Matching(pattern, filters, _) => {
+                let re = remote_regex(pattern)?;
+                (
+                    remotes
+                        .iter()
+                        .filter(|(name, _)| re.is_match(&name.0))
+                        .map(|(name, remote)| (name.clone(), remote.clone()))
+                        .collect::<Vec<_>>(),
+                    filters,
+                )
+            }
+            PushSelection::Group(group, filters) => {
+                let members = config
+                    .club_groups
+                    .get(group)
+                    .ok_or(ClubError::GroupNotFound)?;
+                let mut candidates = Vec::new();
+                for member in members {
+                    let remote = remotes.get(member).ok_or_else(|| {
+                        ClubError::DanglingGroupMember(group.clone(), member.clone())
+                    })?;
+                    candidates.push((member.clone(), remote.clone()));
+                }
+                (candidates, filters)
+            }
+        };
+        let start = match &filters.from {
+            Some(from) => candidates
+                .iter()
+                .position(|(name, _)| name == from)
+                .ok_or(ClubError::RemoteNotFound)?,
+            None => 0,
+        };
+        let targets: Vec<(RemoteName, Remote)> = candidates
+            .into_iter()
+            .skip(start)
+            .filter(|(name, _)| {
+                !filters
+                    .exclude
+                    .iter()
+                    .any(|pattern| glob_matches(pattern, &name.0))
+            })
+            .collect();
+        if let PushSelection::Matching(pattern, _, false) = self {
+            if targets.is_empty() {
+                return Err(ClubError::NoRemotesMatch(pattern.clone()));
+            }
+        }
+        Ok(targets)
+    }
+}
+
+/// Compiles a `--remote-regex` pattern, anchored so that it has to match the whole remote name.
+fn remote_regex(pattern: &str) -> Result<Regex, ClubError> {
+    Regex::new(&format!("^(?:{})$", pattern))
+        .map_err(|e| ClubError::InvalidRemoteRegex(pattern.to_string(), e.to_string()))
+}
+
+/// Matches `name` against a glob `pattern` supporting `*` and `?` wildcards.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern = regex::escape(pattern)
+        .replace(r"\*", ".*")
+        .replace(r"\?", ".");
+    Regex::new(&format!("^{}$", pattern))
+        .map(|re| re.is_match(name))
+        .unwrap_or(false)
+}
+
+fn club_push(push_args: PushCommand, ctx: &Context, settings: &Settings) -> Result<(), ClubError> {
+    push_with_events(push_args, ctx, settings, &mut std::io::stdout())
+}
+
+/// Runs a push, writing `--stdout-json` progress events to `events`. Human-readable output is
+/// silenced while events are streamed, so the two never interleave.
+fn push_with_events(
+    mut push_args: PushCommand,
+    ctx: &Context,
+    settings: &Settings,
+    events: &mut dyn Write,
+) -> Result<(), ClubError> {
+    let settings = &Settings {
+        quiet: settings.quiet || push_args.stdout_json,
+        clasp_output_to_stderr: push_args.stdout_json,
+        capture_clasp_stderr: push_args.report.is_some(),
+        after_push: push_args
+            .after_push
+            .clone()
+            .or_else(|| settings.after_push.clone()),
+        hook_fatal: settings.hook_fatal || push_args.hook_fatal,
+        ..settings.clone()
+    };
+    let mut config = ctx.config()?.clone();
+    let mut remotes = config.club_remotes.clone().ok_or(ClubError::ClubNotSetup)?;
+
+    // A --scriptid push is reported under its --and-register name, or else the ID itself.
+    let one_off = match &push_args.script_id {
+        Some(script_id) => {
+            let remote_id = RemoteId::parse(script_id.clone(), settings.validate_ids)?;
+            warn_if_legacy_id(settings, &remote_id)?;
+            let remote_name = match &push_args.and_register {
+                Some(name) => RemoteName::parse_new(name.clone())?,
+                None => RemoteName(remote_id.0.clone()),
+            };
+            if remotes.contains_key(&remote_name) {
+                return Err(ClubError::RemoteAlreadyExists);
+            }
+            // Only so that plan entries find the ID; `config` never sees it.
+            remotes.insert(remote_name.clone(), Remote::from(remote_id.clone()));
+            Some((remote_name, remote_id))
+        }
+        None => None,
+    };
+
+    // Each push's result, for --plan-file and the push history, and its timings, for --profile.
+    let mut attempts = Vec::new();
+    let mut history = Vec::new();
+    let mut profile = Vec::new();
+    let mut emit = |event: Value| {
+        if event["event"] == "push_result" {
+            let attempt = plan_entry(&event, &remotes);
+            let mut entry = attempt.clone();
+            entry["pushedAt"] = Value::String(format_timestamp(SystemTime::now()));
+            history.push(entry);
+            attempts.push(attempt);
+        }
+        if event["event"] == "push_profile" {
+            profile.push(event.clone());
+        }
+        if push_args.stdout_json {
+            // A consumer that has gone away shouldn't stop the push itself.
+            let _ = writeln!(events, "{}", event).and_then(|_| events.flush());
+        }
+    };
+
+    if let Some(only) = push_args.only.take() {
+        push_args.remote = Some(only);
+    }
+    if push_args.remote_from_branch {
+        let remote_name = branch_remote(
+            &get_project_dir(&ctx.manifest_path),
+            &settings.branch_remote_map,
+            &remotes,
+            run_git,
+        )?;
+        push_args.remote = Some(remote_name.0);
+    }
+    if let Some(restore_to) = push_args.restore_to.clone() {
+        let remote_name = RemoteName::try_from(restore_to)?;
+        let remote = remotes.get(&remote_name).ok_or(ClubError::RemoteNotFound)?;
+        // Each push restores to this config, so the manifest ends up pointing at the target.
+        config.script_id = remote.id.0.clone();
+        config.club_settings.active_remote = Some(remote_name);
+    }
+
+    if push_args.remote.is_none()
+        && !push_args.all
+        && push_args.group.is_none()
+        && push_args.remote_regex.is_none()
+        && one_off.is_none()
+    {
+        let interactive = !push_args.stdout_json
+            && std::io::stdin().is_terminal()
+            && std::io::stderr().is_terminal();
+        let remote_name = bare_push_target(
+            ctx.config()?,
+            settings,
+            push_args.require_remote || settings.require_remote,
+            interactive,
+            pick_remote,
+        )?;
+        push_args.remote = Some(remote_name.0);
+    }
+    let selection = match one_off.clone() {
+        Some((remote_name, remote_id)) => PushSelection::ScriptId(remote_name, remote_id),
+        None => PushSelection::try_from(&push_args)?,
+    };
+    if remotes.is_empty() {
+        return Err(ClubError::NoRemotesAvailable);
+    }
+    if push_args.pre_push.is_some() && !push_args.pre_push_once && push_args.concurrency_safe {
+        return Err(ClubError::PrePushPerRemoteConcurrently);
+    }
+    if push_args.concurrency_safe {
+        let root_dir = config.push_root_dir.as_ref().unwrap_or(&config.root_dir);
+        if root_dir.is_empty() || Path::new(root_dir) == Path::new(".") {
+            return Err(ClubError::IsolatedPushNeedsRootDir);
+        }
+    }
+
+    if push_args.dry_run {
+        if settings.json {
+            let targets = selection.targets(&config)?.into_iter().collect();
+            let mut json = remotes_json(&targets);
+            mark_active(&mut json, active_remote(&config));
+            return print_json(&json, settings);
+        }
+        for line in push_plan(&selection, &config)? {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+
+    if push_args.print_cmd {
+        for (remote_name, remote) in selection.targets(&config)? {
+            if config.script_id != remote.id.0 {
+                eprintln!(
+                    "{}",
+                    format!(
+                        "Note: the manifest points at {}, not {}. Run `club switch {}` first.",
+                        config.script_id, remote_name, remote_name
+                    )
+                    .yellow()
+                );
+            }
+            println!(
+                "{}",
+                clasp_command_line(
+                    &settings.clasp_bin,
+                    &ctx.manifest_path,
+                    &push_args.clasp_args()
+                )
+            );
+        }
+        return Ok(());
+    }
+
+    // Before rootDir and --into are checked, since the build may be what creates them. It runs in the
+    // project's own rootDir, or in the project directory until rootDir exists.
+    if let (Some(hook), true) = (&push_args.pre_push, push_args.pre_push_once) {
+        let project_dir = get_project_dir(&ctx.manifest_path);
+        let root_dir = project_dir.join(&config.root_dir);
+        let hook_dir = if root_dir.is_dir() {
+            &root_dir
+        } else {
+            &project_dir
+        };
+        run_pre_push_hook(hook, None, hook_dir)?;
+    }
+
+    if let Some(into) = &push_args.into {
+        let into = std::path::absolute(into).unwrap_or_else(|_| into.clone());
+        if !into.is_dir() {
+            return Err(ClubError::IntoDirMissing(into));
+        }
+        config.push_root_dir = Some(into.to_string_lossy().into_owned());
+    } else if !push_args.allow_missing_rootdir {
+        ensure_root_dir(&config, &ctx.manifest_path)?;
+    }
+
+    if !push_args.dirty_ok {
+        ensure_clean_git(&config, &selection, &ctx.manifest_path, settings, run_git)?;
+    }
+
+    let targets = selection.targets(&config)?;
+    let target_names: Vec<RemoteName> = targets.iter().map(|(name, _)| name.clone()).collect();
+    if !push_args.confirm_prod {
+        confirm_protected(&targets, confirm)?;
+    }
+    if push_args.if_clean_remote {
+        ensure_clean_remotes(&targets, &config, &ctx.manifest_path, settings)?;
+    }
+
+    let started_at = SystemTime::now();
+    let result = if push_args.concurrency_safe {
+        let mut report = (push_args.concurrent_report && !settings.quiet && !settings.json)
+            .then(|| ConcurrentReport::new(&targets, std::io::stderr().is_terminal()));
+        push_isolated_all(
+            targets,
+            &config,
+            &ctx.manifest_path,
+            settings,
+            &push_args.clasp_args(),
+            push_args.message.as_deref(),
+            report.as_mut(),
+            &mut emit,
+        )
+    } else {
+        push_targets(
+            selection,
+            targets,
+            &config,
+            &ctx.manifest_path,
+            settings,
+            &push_args,
+            &mut emit,
+        )
+    };
+
+    if push_args.profile {
+        for line in profile_table(&profile) {
+            eprintln!("{}", line);
+        }
+    }
+
+    // Written before anything else that can fail, so that a failure there doesn't lose them.
+    let pushed_ok = result.is_ok();
+    let result = match &push_args.plan_file {
+        Some(plan_file) => and_written(result, write_plan_file(plan_file, started_at, &attempts)),
+        None => result,
+    };
+    let result = match &push_args.report {
+        Some(report) => and_written(
+            result,
+            write_push_report(report, started_at, &target_names, &attempts),
+        ),
+        None => result,
+    };
+
+    if settings.history {
+        if let Err(err) = append_history(&ctx.manifest_path, &history) {
+            // Not through `warn`: the pushes have happened, so even --strict shouldn't fail them.
+            eprintln!("{}", format!("Warning: {}", err).yellow());
+        }
+    }
+
+    if push_args.if_clean_remote || settings.track_pushes {
+        let pushed: Vec<RemoteName> = attempts
+            .iter()
+            .filter(|attempt| attempt["ok"] == true)
+            .map(|attempt| RemoteName(attempt["remote"].as_str().unwrap_or_default().to_string()))
+            .collect();
+        if let Err(err) = record_pushed_state(&config, &ctx.manifest_path, &pushed) {
+            // Not through `warn`, for the same reason as the history.
+            eprintln!("{}", format!("Warning: {}", err).yellow());
+        }
+    }
+
+    if let (true, Some((remote_name, remote_id)), Some(_)) =
+        (pushed_ok, &one_off, &push_args.and_register)
+    {
+        // The push has restored the manifest to `config`, so registering starts from there.
+        let mut registered = ClaspConfig {
+            push_root_dir: None,
+            ..config.clone()
+        };
+        registered
+            .club_remotes
+            .get_or_insert_with(IndexMap::new)
+            .insert(remote_name.clone(), Remote::from(remote_id.clone()));
+        ctx.write_config(&registered)?;
+        if !settings.quiet {
+            eprintln!("Added remote {} ({}).", remote_name, remote_id);
+        }
+    }
+
+    result
+}
+
+/// Combines a push's result with that of writing a record of it, reporting the push's error
+/// first if both failed.
+fn and_written(
+    result: Result<(), ClubError>,
+    written: Result<(), ClubError>,
+) -> Result<(), ClubError> {
+    match (result, written) {
+        (Err(err), Err(write_err)) => {
+            eprintln!("{}", write_err);
+            Err(err)
+        }
+        (result, written) => result.and(written),
+    }
+}
+
+/// The `push --dry-run` table: each remote the selection targets and its script ID, with the active
+/// remote marked.
+fn push_plan(selection: &PushSelection, config: &ClaspConfig) -> Result<Vec<String>, ClubError> {
+    let targets = selection.targets(config)?.into_iter().collect();
+    Ok(remotes_table(&targets, Some(active_remote(config)), true))
+}
+
+/// Pushes to `targets` one at a time. A group push, or any push with --max-failures, carries on past
+/// failures and reports them all, skipping the remaining remotes once --max-failures is reached; any
+/// other push stops at the first failure.
+fn push_targets(
+    selection: PushSelection,
+    targets: Vec<(RemoteName, Remote)>,
+    config: &ClaspConfig,
+    manifest_path: &Path,
+    settings: &Settings,
+    push_args: &PushCommand,
+    emit: &mut dyn FnMut(Value),
+) -> Result<(), ClubError> {
+    let carry_on = matches!(selection, PushSelection::Group(..))
+        || push_args.keep_going
+        || push_args.max_failures.is_some();
+    let in_place =
+        targets.len() > 1 && !settings.quiet && !settings.json && std::io::stderr().is_terminal();
+    let settings = &Settings {
+        progress_in_place: in_place,
+        silence_clasp: settings.silence_clasp || in_place,
+        capture_clasp_stderr: settings.capture_clasp_stderr || in_place,
+        ..settings.clone()
+    };
+    if !carry_on {
+        let total = targets.len();
+        for (index, (remote_name, remote)) in targets.into_iter().enumerate() {
+            emit(push_event("push_start", &remote_name, None));
+            let mut timings = ClaspTimings::default();
+            let result = push_to_remote(
+                remote_name.clone(),
+                remote.id,
+                config,
+                manifest_path,
+                settings,
+                push_args,
+                (index, total),
+                &mut timings,
+            );
+            if push_args.profile {
+                emit(profile_event(&remote_name, &timings));
+            }
+            emit(push_event("push_result", &remote_name, Some(&result)));
+            if result.is_err() {
+                end_progress_line(settings);
+            }
+            result?;
+        }
+        end_progress_line(settings);
+        return Ok(());
+    }
+
+    let mut failed = Vec::new();
+    let mut attempted = 0;
+    for (index, (remote_name, remote)) in targets.iter().enumerate() {
+        if push_args
+            .max_failures
+            .is_some_and(|max_failures| failed.len() >= max_failures as usize)
+        {
+            debug!("Stopping after {} failed pushes", failed.len());
+            break;
+        }
+        attempted += 1;
+        emit(push_event("push_start", remote_name, None));
+        let mut timings = ClaspTimings::default();
+        let result = push_to_remote(
+            remote_name.clone(),
+            remote.id.clone(),
+            config,
+            manifest_path,
+            settings,
+            push_args,
+            (index, targets.len()),
+            &mut timings,
+        );
+        if push_args.profile {
+            emit(profile_event(remote_name, &timings));
+        }
+        emit(push_event("push_result", remote_name, Some(&result)));
+        if let Err(err) = result {
+            end_progress_line(settings);
+            if !push_args.stdout_json {
+                eprintln!("{}", err);
+            }
+            failed.push(remote_name.clone());
+        }
+    }
+    // A failed push has already ended the line.
+    if attempted > 0 && failed.last() != Some(&targets[attempted - 1].0) {
+        end_progress_line(settings);
+    }
+    if !settings.quiet {
+        let group = match &selection {
+            PushSelection::Group(group, _) => format!(" in group {}", group),
+            _ => String::new(),
+        };
+        eprintln!(
+            "Pushed to {} of {} remotes{}.",
+            attempted - failed.len(),
+            targets.len(),
+            group
+        );
+    }
+    let skipped: Vec<RemoteName> = targets[attempted..]
+        .iter()
+        .map(|(remote_name, _)| remote_name.clone())
+        .collect();
+    if !skipped.is_empty() {
+        Err(ClubError::PushStopped(failed, skipped))
+    } else if !failed.is_empty() {
+        Err(ClubError::PushFailed(failed))
+    } else {
+        Ok(())
+    }
+}
+
+/// The `push_profile` event for one push's --profile timings, in milliseconds.
+fn profile_event(remote_name: &RemoteName, timings: &ClaspTimings) -> Value {
+    serde_json::json!({
+        "event": "push_profile",
+        "remote": remote_name.0,
+        "write_ms": timings.write.as_millis() as u64,
+        "clasp_ms": timings.clasp.as_millis() as u64,
+        "restore_ms": timings.restore.as_millis() as u64,
+        "total_ms": timings.total().as_millis() as u64,
+    })
+}
+
+/// The --profile table: a row of timings per push from its `push_profile` event, then their totals.
+fn profile_table(events: &[Value]) -> Vec<String> {
+    const COLUMNS: [&str; 4] = ["write_ms", "clasp_ms", "restore_ms", "total_ms"];
+    let mut rows: Vec<(String, Vec<u64>)> = events
+        .iter()
+        .map(|event| {
+            let remote = event["remote"].as_str().unwrap_or_default().to_string();
+            let millis = COLUMNS
+                .iter()
+                .map(|column| event[column].as_u64().unwrap_or_default())
+                .collect();
+            (remote, millis)
+        })
+        .collect();
+    let totals = (0..COLUMNS.len())
+        .map(|column| rows.iter().map(|(_, millis)| millis[column]).sum())
+        .collect();
+    rows.push(("total".to_string(), totals));
+    let width = rows
+        .iter()
+        .map(|(remote, _)| remote.len())
+        .chain(["remote".len()])
+        .max()
+        .unwrap_or_default();
+    let format_row = |remote: &str, cells: Vec<String>| {
+        format!(
+            "{:<width$}  {:>8}  {:>8}  {:>8}  {:>8}",
+            remote,
+            cells[0],
+            cells[1],
+            cells[2],
+            cells[3],
+            width = width
+        )
+    };
+    std::iter::once(format_row(
+        "remote",
+        ["write", "clasp", "restore", "total"]
+            .iter()
+            .map(|header| header.to_string())
+            .collect(),
+    ))
+    .chain(rows.into_iter().map(|(remote, millis)| {
+        format_row(
+            &remote,
+            millis.iter().map(|ms| format!("{}ms", ms)).collect(),
+        )
+    }))
+    .collect()
+}
+
+/// One remote's line in a --plan-file record, built from its `push_result` event.
+fn plan_entry(event: &Value, remotes: &IndexMap<RemoteName, Remote>) -> Value {
+    let remote_name = RemoteName(event["remote"].as_str().unwrap_or_default().to_string());
+    let mut entry = serde_json::json!({
+        "remote": remote_name.0,
+        "id": remotes.get(&remote_name).map(|remote| &remote.id.0),
+        "ok": event["ok"],
+    });
+    if let Some(error) = event.get("error") {
+        entry["error"] = error.clone();
+    }
+    entry
+}
+
+/// Escapes text for an XML attribute or element.
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// The --report JUnit XML for a push: a test case per target, failed with the push's error or
+/// skipped if the push never reached it.
+fn push_report_xml(started_at: SystemTime, targets: &[RemoteName], attempts: &[Value]) -> String {
+    let mut cases = String::new();
+    let (mut failures, mut skipped) = (0, 0);
+    for remote_name in targets {
+        let attempt = attempts
+            .iter()
+            .find(|attempt| attempt["remote"] == remote_name.0.as_str());
+        let name = xml_escape(&remote_name.0);
+        match attempt {
+            Some(attempt) if attempt["ok"] == true => {
+                cases += &format!("  <testcase classname=\"club.push\" name=\"{}\"/>\n", name);
+            }
+            Some(attempt) => {
+                failures += 1;
+                let error = xml_escape(attempt["error"].as_str().unwrap_or("push failed"));
+                cases += &format!(
+                    "  <testcase classname=\"club.push\" name=\"{}\">\n    <failure message=\"{}\">{}</failure>\n  </testcase>\n",
+                    name, error, error
+                );
+            }
+            None => {
+                skipped += 1;
+                cases += &format!(
+                    "  <testcase classname=\"club.push\" name=\"{}\">\n    <skipped/>\n  </testcase>\n",
+                    name
+                );
+            }
+        }
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuite name=\"club push\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" timestamp=\"{}\">\n{}</testsuite>\n",
+        targets.len(),
+        failures,
+        skipped,
+        format_timestamp(started_at),
+        cases
+    )
+}
+
+fn write_push_report(
+    path: &Path,
+    started_at: SystemTime,
+    targets: &[RemoteName],
+    attempts: &[Value],
+) -> Result<(), ClubError> {
+    std::fs::write(path, push_report_xml(started_at, targets, attempts))
+        .map_err(|e| ClubError::ReportWriteFail(e.to_string()))
+}
+
+/// Writes the --plan-file record of a push invocation.
+fn write_plan_file(
+    path: &Path,
+    started_at: SystemTime,
+    attempts: &[Value],
+) -> Result<(), ClubError> {
+    let plan = serde_json::json!({
+        "startedAt": format_timestamp(started_at),
+        "pushes": attempts,
+    });
+    let plan_str = serde_json::to_string_pretty(&plan)
+        .map_err(|e| ClubError::PlanFileWriteFail(e.to_string()))?;
+    std::fs::write(path, plan_str + "\n").map_err(|e| ClubError::PlanFileWriteFail(e.to_string()))
+}
+
+/// Asks the user to pick one of `remotes`, marking the active one. An empty answer picks `default`.
+type RemotePicker =
+    fn(&[RemoteName], Option<&RemoteName>, Option<&RemoteName>) -> Option<RemoteName>;
+
+/// The remote a `club push` with no remote, --all, or --group targets. In an interactive terminal
+/// with several remotes the user picks one, with the default remote preselected unless a remote is
+/// required. Otherwise it's the default remote, or an error if a remote is required.
+fn bare_push_target(
+    config: &ClaspConfig,
+    settings: &Settings,
+    require_remote: bool,
+    interactive: bool,
+    pick: RemotePicker,
+) -> Result<RemoteName, ClubError> {
+    let remote_names: Vec<RemoteName> = config
+        .club_remotes
+        .as_ref()
+        .map(|remotes| remotes.keys().cloned().collect())
+        .unwrap_or_default();
+    if interactive && remote_names.len() > 1 {
+        let default = (!require_remote).then_some(&settings.default_remote);
+        return pick(&remote_names, active_remote(config), default)
+            .ok_or(ClubError::NoRemoteChosen);
+    }
+    if require_remote {
+        return Err(ClubError::RemoteRequired);
+    }
+    Ok(settings.default_remote.clone())
+}
+
+fn pick_remote(
+    remote_names: &[RemoteName],
+    active: Option<&RemoteName>,
+    default: Option<&RemoteName>,
+) -> Option<RemoteName> {
+    for (index, remote_name) in remote_names.iter().enumerate() {
+        let line = format!("{}) {}", index + 1, remote_name);
+        if Some(remote_name) == active {
+            eprintln!("{} {}", line.bold(), "(active)".green());
+        } else {
+            eprintln!("{}", line);
+        }
+    }
+    match default {
+        Some(default) => eprint!("Push to which remote? [{}] ", default),
+        None => eprint!("Push to which remote? "),
+    }
+    std::io::stderr().flush().ok()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).ok()?;
+    parse_remote_choice(answer.trim(), remote_names, default)
+}
+
+/// Reads a picker answer: a 1-based number or a remote name, or nothing for `default`.
+fn parse_remote_choice(
+    answer: &str,
+    remote_names: &[RemoteName],
+    default: Option<&RemoteName>,
+) -> Option<RemoteName> {
+    if answer.is_empty() {
+        return default.cloned();
+    }
+    if let Ok(number) = answer.parse::<usize>() {
+        return remote_names.get(number.checked_sub(1)?).cloned();
+    }
+    remote_names
+        .iter()
+        .find(|remote_name| remote_name.0 == answer)
+        .cloned()
+}
+
+/// One `--stdout-json` progress event. Results carry `ok`, and failed results carry the error.
+fn push_event(
+    event: &str,
+    remote_name: &RemoteName,
+    result: Option<&Result<(), ClubError>>,
+) -> Value {
+    let mut json = serde_json::json!({
+        "event": event,
+        "remote": remote_name.0,
+    });
+    if let Some(result) = result {
+        json["ok"] = Value::Bool(result.is_ok());
+        if let Err(err) = result {
+            json["error"] = Value::String(err.to_string());
+        }
+    }
+    json
+}
+
+/// Asks before pushing to any protected remote among `targets`, failing if the answer isn't yes.
+/// The prompt is passed in so that tests can answer it.
+fn confirm_protected(
+    targets: &[(RemoteName, Remote)],
+    confirm: fn(&str) -> bool,
+) -> Result<(), ClubError> {
+    let protected: Vec<RemoteName> = targets
+        .iter()
+        .filter(|(_, remote)| remote.protected)
+        .map(|(remote_name, _)| remote_name.clone())
+        .collect();
+    if protected.is_empty() {
+        return Ok(());
+    }
+    let names: Vec<String> = protected.iter().map(|r| r.to_string()).collect();
+    let prompt = format!(
+        "{} protected. Push anyway?",
+        match names.len() {
+            1 => format!("Remote {} is", names[0]),
+            _ => format!("Remotes {} are", names.join(", ")),
+        }
+    );
+    if !confirm(&prompt) {
+        return Err(ClubError::ProtectedRemoteNotConfirmed(protected));
+    }
+    Ok(())
+}
+
+/// Asks the user a yes/no question on the terminal. Always answers no when stdin is not a TTY, so
+/// that non-interactive runs never proceed past a confirmation.
+fn confirm(prompt: &str) -> bool {
+    if !std::io::stdin().is_terminal() {
+        return false;
+    }
+    eprint!("{} [y/N] ", prompt);
+    if std::io::stderr().flush().is_err() {
+        return false;
+    }
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Returns whether `dir` has uncommitted changes in git, not counting files named in `ignored`, or
+/// `None` if `dir` is not inside a git repository (or git is not available).
+fn git_tree_is_dirty(dir: &Path, ignored: &[String], git: GitRunner) -> Option<bool> {
+    let excludes: Vec<String> = ignored
+        .iter()
+        .map(|name| format!(":(exclude,glob)**/{}", name))
+        .collect();
+    let mut args = vec!["status", "--porcelain", "--", "."];
+    args.extend(excludes.iter().map(String::as_str));
+    let status = git(dir, &args)?;
+    Some(!status.is_empty())
+}
+
+/// Returns the remote `push --remote-from-branch` pushes to for the git branch checked out in `dir`:
+/// the branch's `branchRemoteMap` entry if it has one, or else the branch name made into a remote
+/// name, so `feature/login` maps to `feature-login`.
+fn branch_remote(
+    dir: &Path,
+    branch_remote_map: &IndexMap<String, RemoteName>,
+    remotes: &IndexMap<RemoteName, Remote>,
+    git: GitRunner,
+) -> Result<RemoteName, ClubError> {
+    let branch = git(dir, &["rev-parse", "--abbrev-ref", "HEAD"])
+        .map(|output| output.trim().to_string())
+        .filter(|branch| !branch.is_empty() && branch != "HEAD")
+        .ok_or_else(|| ClubError::NoGitBranch(dir.to_path_buf()))?;
+    let remote_name = match branch_remote_map.get(&branch) {
+        Some(remote_name) => remote_name.clone(),
+        None => RemoteName::try_from(sanitize_remote_name(&branch))?,
+    };
+    if !remotes.contains_key(&remote_name) {
+        return Err(ClubError::NoRemoteForBranch(branch, remote_name));
+    }
+    Ok(remote_name)
+}
+
+/// Runs git with `args` in `dir`, returning its stdout if it ran and succeeded. The git helpers
+/// take the runner as a parameter so that tests can stand in for git.
+type GitRunner = fn(&Path, &[&str]) -> Option<String>;
+
+fn run_git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Refuses a push whose rootDir, resolved against the manifest's directory, isn't a directory. An
+/// empty rootDir is clasp's default and means the manifest's directory itself.
+fn ensure_root_dir(config: &ClaspConfig, manifest_path: &Path) -> Result<(), ClubError> {
+    let root_dir = config.resolved_root_dir(&get_project_dir(manifest_path));
+    if root_dir.is_dir() {
+        Ok(())
+    } else {
+        Err(ClubError::RootDirMissing(root_dir))
+    }
+}
+
+/// Refuses a push under `requireCleanGit` when rootDir has uncommitted changes. A push to just the
+/// default remote is exempt, and so is a rootDir outside git. Club's own files, such as the manifest
+/// lock the push itself holds, don't count as changes.
+fn ensure_clean_git(
+    config: &ClaspConfig,
+    selection: &PushSelection,
+    manifest_path: &Path,
+    settings: &Settings,
+    git: GitRunner,
+) -> Result<(), ClubError> {
+    if !config.club_settings.require_clean_git || selection.is_only(&settings.default_remote) {
+        return Ok(());
+    }
+    let root_dir = config.resolved_root_dir(&get_project_dir(manifest_path));
+    let lock_path = ManifestLock::path_for(manifest_path);
+    let club_files: Vec<String> = lock_path
+        .file_name()
+        .into_iter()
+        .map(|name| name.to_string_lossy().into_owned())
+        .chain(
+            [PUSHED_STATE_NAME, HISTORY_NAME, DEPLOY_INFO_NAME]
+                .iter()
+                .map(|name| name.to_string()),
+        )
+        .collect();
+    if git_tree_is_dirty(&root_dir, &club_files, git) == Some(true) {
+        return Err(ClubError::DirtyWorkingTree);
+    }
+    Ok(())
+}
+
+/// Prints which remote is about to be pushed. Multi-remote pushes on a terminal also show a
+/// `[n/total]` counter so long runs don't look stalled, redrawn in place for one-at-a-time pushes.
+fn announce_push(remote_name: &RemoteName, index: usize, total: usize, settings: &Settings) {
+    if settings.quiet {
+        return;
+    }
+    let counter = total > 1 && !settings.json && std::io::stderr().is_terminal();
+    let announcement = push_announcement(remote_name, index, total, counter);
+    if settings.progress_in_place {
+        eprint!("\r\x1b[2K{}", announcement);
+        let _ = std::io::stderr().flush();
+    } else {
+        eprintln!("{}", announcement);
+    }
+}
+
+/// Ends the progress line a one-at-a-time push redraws in place, so that what's printed next starts
+/// on a line of its own.
+fn end_progress_line(settings: &Settings) {
+    if settings.progress_in_place {
+        eprintln!();
+    }
+}
+
+/// The line announcing a push, with an `[n/total]` counter for multi-remote pushes in a terminal.
+fn push_announcement(
+    remote_name: &RemoteName,
+    index: usize,
+    total: usize,
+    counter: bool,
+) -> String {
+    if counter {
+        let counter = format!("[{}/{}]", index + 1, total);
+        format!("{} Pushing to {}...", counter.bold(), remote_name)
+    } else {
+        format!("Pushing to {}", remote_name)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn push_to_remote(
+    remote_name: RemoteName,
+    remote_id: RemoteId,
+    config: &ClaspConfig,
+    manifest_path: &Path,
+    settings: &Settings,
+    push_args: &PushCommand,
+    (index, total): (usize, usize),
+    timings: &mut ClaspTimings,
+) -> Result<(), ClubError> {
+    announce_push(&remote_name, index, total, settings);
+    let root_dir = config.resolved_root_dir(&get_project_dir(manifest_path));
+    if let (Some(hook), false) = (&push_args.pre_push, push_args.pre_push_once) {
+        run_pre_push_hook(hook, Some((&remote_name, &remote_id)), &root_dir)?;
+    }
+    run_clasp_on_remote(
+        remote_id.clone(),
+        config,
+        manifest_path,
+        settings,
+        &push_args.clasp_args(),
+        timings,
+    )?;
+    // run_clasp_on_remote has restored the manifest by now, so the hook sees the usual state.
+    finish_push(
+        &remote_name,
+        &remote_id,
+        &root_dir,
+        manifest_path,
+        settings,
+        push_args.message.as_deref(),
+    )
+}
+
+/// Records a successful push's deploy marker and runs the after-push hook, if either is set.
+fn finish_push(
+    remote_name: &RemoteName,
+    remote_id: &RemoteId,
+    root_dir: &Path,
+    manifest_path: &Path,
+    settings: &Settings,
+    message: Option<&str>,
+) -> Result<(), ClubError> {
+    remember_recent_ids(settings, std::slice::from_ref(remote_id));
+    if let Some(message) = message {
+        write_deploy_marker(root_dir, remote_name, message, SystemTime::now())?;
+    }
+    if let Some(hook) = &settings.after_push {
+        if let Err(err) = run_after_push_hook(hook, remote_name, remote_id, manifest_path) {
+            if settings.hook_fatal {
+                return Err(err);
+            }
+            warn(settings, err.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Where one remote's push is up to, for `push --concurrent-report`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PushState {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+impl Display for PushState {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let state = match self {
+            PushState::Queued => "queued".dimmed(),
+            PushState::Running => "running".yellow(),
+            PushState::Done => "done".green(),
+            PushState::Failed => "failed".red(),
+        };
+        write!(f, "{}", state)
+    }
+}
+
+/// The status table `push --concurrent-report` keeps on stderr while pushes run at once.
+struct ConcurrentReport {
+    states: Vec<(RemoteName, PushState)>,
+    /// Redraw the table in place, rather than logging each change on a line of its own.
+    live: bool,
+    /// How many lines the last redraw wrote, to move back over.
+    drawn: usize,
+}
+
+impl ConcurrentReport {
+    fn new(targets: &[(RemoteName, Remote)], live: bool) -> Self {
+        ConcurrentReport {
+            states: targets
+                .iter()
+                .map(|(remote_name, _)| (remote_name.clone(), PushState::Queued))
+                .collect(),
+            live,
+            drawn: 0,
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let width = self
+            .states
+            .iter()
+            .map(|(remote_name, _)| remote_name.0.len())
+            .max()
+            .unwrap_or(0);
+        self.states
+            .iter()
+            .map(|(remote_name, state)| format!("  {:width$}  {}", remote_name.0, state))
+            .collect()
+    }
+
+    fn draw(&mut self) {
+        let mut stderr = std::io::stderr().lock();
+        if self.drawn > 0 {
+            let _ = write!(stderr, "\x1b[{}A", self.drawn);
+        }
+        let lines = self.lines();
+        for line in &lines {
+            let _ = writeln!(stderr, "\x1b[2K{}", line);
+        }
+        self.drawn = lines.len();
+    }
+
+    fn update(&mut self, index: usize, state: PushState) {
+        self.states[index].1 = state;
+        if self.live {
+            self.draw();
+        } else {
+            eprintln!("{}: {}", self.states[index].0, state);
+        }
+    }
+}
+
+/// Pushes to every target at once for `push --concurrency-safe`, each from its own temporary
+/// directory. Deploy markers and hooks run afterwards, one remote at a time, in target order.
+#[allow(clippy::too_many_arguments)]
+fn push_isolated_all(
+    targets: Vec<(RemoteName, Remote)>,
+    config: &ClaspConfig,
+    manifest_path: &Path,
+    settings: &Settings,
+    clasp_args: &[&str],
+    message: Option<&str>,
+    mut report: Option<&mut ConcurrentReport>,
+    emit: &mut dyn FnMut(Value),
+) -> Result<(), ClubError> {
+    let total = targets.len();
+    for (remote_name, _) in &targets {
+        emit(push_event("push_start", remote_name, None));
+    }
+    let live = report.as_ref().is_some_and(|report| report.live);
+    let push_settings = &Settings {
+        silence_clasp: live,
+        capture_clasp_stderr: settings.capture_clasp_stderr || live,
+        ..settings.clone()
+    };
+    if let Some(report) = report.as_mut().filter(|report| report.live) {
+        report.draw();
+    }
+    let results: Vec<Result<(), ClubError>> = std::thread::scope(|scope| {
+        let (states, state_changes) = std::sync::mpsc::channel();
+        let handles: Vec<_> = targets
+            .iter()
+            .enumerate()
+            .map(|(index, (remote_name, remote))| {
+                let states = states.clone();
+                let announce = report.is_none();
+                scope.spawn(move || {
+                    if announce {
+                        announce_push(remote_name, index, total, push_settings);
+                    }
+                    let _ = states.send((index, PushState::Running));
+                    let result = push_isolated(
+                        remote_name,
+                        &remote.id,
+                        config,
+                        manifest_path,
+                        push_settings,
+                        clasp_args,
+                    );
+                    let state = match result {
+                        Ok(()) => PushState::Done,
+                        Err(_) => PushState::Failed,
+                    };
+                    let _ = states.send((index, state));
+                    result
+                })
+            })
+            .collect();
+        // The report follows the pushes until the last of them hangs up.
+        drop(states);
+        for (index, state) in state_changes {
+            if let Some(report) = report.as_mut() {
+                report.update(index, state);
+            }
+        }
+        handles.into_iter().map(join_push).collect()
+    });
+
+    let root_dir = config.resolved_root_dir(&get_project_dir(manifest_path));
+    let mut failed = Vec::new();
+    for ((remote_name, remote), result) in targets.iter().zip(results) {
+        let result = result.and_then(|_| {
+            finish_push(
+                remote_name,
+                &remote.id,
+                &root_dir,
+                manifest_path,
+                settings,
+                message,
+            )
+        });
+        emit(push_event("push_result", remote_name, Some(&result)));
+        if let Err(err) = result {
+            // Clasp's output only goes to stderr while --stdout-json events own stdout.
+            if !settings.clasp_output_to_stderr {
+                eprintln!("{}", err);
+            }
+            failed.push(remote_name.clone());
+        }
+    }
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(ClubError::PushFailed(failed))
+    }
+}
+
+/// The result of one remote's push thread. A panic becomes an error for that remote, so that the
+/// other pushes are still reported.
+fn join_push(
+    handle: std::thread::ScopedJoinHandle<'_, Result<(), ClubError>>,
+) -> Result<(), ClubError> {
+    handle.join().unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown error".to_string());
+        Err(ClubError::PushPanicked(message))
+    })
+}
+
+/// Runs `clasp push` for one remote from a fresh temporary directory holding a copy of rootDir, any
+/// .claspignore, and a manifest pointing at the remote. The directory is removed afterwards.
+fn push_isolated(
+    remote_name: &RemoteName,
+    remote_id: &RemoteId,
+    config: &ClaspConfig,
+    manifest_path: &Path,
+    settings: &Settings,
+    clasp_args: &[&str],
+) -> Result<(), ClubError> {
+    let isolation_err =
+        |e: std::io::Error| ClubError::IsolatedPushFail(remote_name.clone(), e.to_string());
+    let project_dir = get_project_dir(manifest_path);
+    let work_dir = create_work_dir(&format!("club-push-{}", remote_name)).map_err(isolation_err)?;
+    debug!("Pushing {} from {}", remote_name, work_dir.display());
+
+    let result = (|| {
+        copy_dir(
+            &config.resolved_root_dir(&project_dir),
+            &work_dir.join(ISOLATED_ROOT_DIR),
+        )
+        .map_err(isolation_err)?;
+        let claspignore = project_dir.join(".claspignore");
+        if claspignore.is_file() {
+            std::fs::copy(&claspignore, work_dir.join(".claspignore")).map_err(isolation_err)?;
+        }
+        let isolated_config = ClaspConfig {
+            root_dir: ISOLATED_ROOT_DIR.to_string(),
+            script_id: remote_id.0.clone(),
+            parent_ids: config.parent_ids.clone(),
+            club_remotes: None,
+            club_groups: IndexMap::new(),
+            club_settings: ClubSettings::default(),
+            env_remotes: None,
+            remote_file: None,
+            push_root_dir: None,
+        };
+        write_clasp_config(&isolated_config, &work_dir.join(MANIFEST_NAME))?;
+
+        if run_clasp(settings, &work_dir, clasp_args)?.success() {
+            Ok(())
+        } else {
+            Err(ClubError::ClaspError("clasp push failed".to_string()))
+        }
+    })();
+    let _ = std::fs::remove_dir_all(&work_dir);
+    result
+}
+
+/// Creates a new, empty directory in the system temp dir named after `name` with a random suffix,
+/// so that it's never one another process already made or is using.
+fn create_work_dir(name: &str) -> std::io::Result<PathBuf> {
+    let random = std::collections::hash_map::RandomState::new();
+    let mut attempt: u32 = 0;
+    loop {
+        let suffix = random.hash_one((std::process::id(), attempt, SystemTime::now()));
+        let dir = std::env::temp_dir().join(format!("{}-{:016x}", name, suffix));
+        match std::fs::create_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists && attempt < 100 => {
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Recursively copies the contents of `from` into `to`, creating `to` if needed. Symlinks are
+/// copied as links to the same target rather than followed, so a link cycle can't recurse forever.
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            // Relative links are resolved against the original directory, so that they still point
+            // at the same file from the copy.
+            copy_symlink(&from.join(std::fs::read_link(entry.path())?), &target)?;
+        } else if file_type.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn copy_symlink(original: &Path, link: &Path) -> std::io::Result<()> {
+    if original.is_dir() {
+        std::os::windows::fs::symlink_dir(original, link)
+    } else {
+        std::os::windows::fs::symlink_file(original, link)
+    }
+}
+
+/// Whether clasp syncs a file: scripts, HTML, and the project manifest.
+fn is_synced_source(path: &Path) -> bool {
+    path.file_name() == Some(OsStr::new("appsscript.json"))
+        || path.extension().is_some_and(|ext| {
+            ["gs", "js", "ts", "html"]
+                .iter()
+                .any(|synced| ext == *synced)
+        })
+}
+
+/// Directories that never hold source clasp pushes, which `source_fingerprints` doesn't look in.
+const SKIPPED_SOURCE_DIRS: [&str; 2] = [".git", "node_modules"];
+
+/// A fingerprint of each file clasp syncs under `root`, keyed by its path relative to `root`. `.gs`
+/// and `.ts` files are keyed as `.js`, since that's how clasp pulls them. TypeScript sources have no
+/// fingerprint, since clasp transpiles them before pushing. Symlinked directories aren't followed.
+fn source_fingerprints(root: &Path) -> std::io::Result<IndexMap<String, Option<String>>> {
+    fn walk(
+        root: &Path,
+        dir: &Path,
+        out: &mut IndexMap<String, Option<String>>,
+    ) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            // Symlinked directories aren't followed, so a link to a parent can't loop forever.
+            if entry.file_type()?.is_dir() {
+                if !SKIPPED_SOURCE_DIRS
+                    .iter()
+                    .any(|skipped| entry.file_name() == *skipped)
+                {
+                    walk(root, &path, out)?;
+                }
+            } else if path.is_file() && is_synced_source(&path) {
+                let relative = path
+                    .strip_prefix(root)
+                    .unwrap_or(&path)
+                    .with_extension(match path.extension().and_then(OsStr::to_str) {
+                        Some("gs" | "ts") => "js",
+                        other => other.unwrap_or_default(),
+                    });
+                let fingerprint = match path.extension() {
+                    Some(ext) if ext == "ts" => None,
+                    _ => Some(format!("{:016x}", fnv1a(&std::fs::read(&path)?))),
+                };
+                out.insert(relative.to_string_lossy().replace('\\', "/"), fingerprint);
+            }
+        }
+        Ok(())
+    }
+    let mut fingerprints = IndexMap::new();
+    walk(root, root, &mut fingerprints)?;
+    fingerprints.sort_keys();
+    Ok(fingerprints)
+}
+
+/// 64-bit FNV-1a, which is stable across Rust releases, unlike the standard library's hasher.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn read_pushed_state(project_dir: &Path) -> Value {
+    std::fs::read_to_string(project_dir.join(PUSHED_STATE_NAME))
+        .ok()
+        .and_then(|state| serde_json::from_str(&state).ok())
+        .filter(Value::is_object)
+        .unwrap_or_else(|| serde_json::json!({}))
+}
+
+/// Appends each push's result to the project's push history, one JSON object per line.
+fn append_history(manifest_path: &Path, entries: &[Value]) -> Result<(), ClubError> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let path = get_project_dir(manifest_path).join(HISTORY_NAME);
+    let write_err = |e: std::io::Error| ClubError::HistoryWriteFail(e.to_string());
+    let lines: String = entries.iter().map(|entry| format!("{}\n", entry)).collect();
+    debug!("Recording {} pushes in {}", entries.len(), path.display());
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(lines.as_bytes()))
+        .map_err(write_err)
+}
+
+/// The project's push history, oldest first. A missing history is empty, and lines that can't be
+/// parsed are skipped.
+fn read_history(manifest_path: &Path) -> Vec<Value> {
+    std::fs::read_to_string(get_project_dir(manifest_path).join(HISTORY_NAME))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// The history entries `club log` shows for its filters. Entries without a readable time are
+/// dropped when filtering by time.
+fn filter_history(
+    entries: Vec<Value>,
+    since: Option<SystemTime>,
+    remote: Option<&RemoteName>,
+    failed_only: bool,
+) -> Vec<Value> {
+    entries
+        .into_iter()
+        .filter(|entry| {
+            since.is_none_or(|since| {
+                entry["pushedAt"]
+                    .as_str()
+                    .and_then(parse_timestamp)
+                    .is_some_and(|pushed_at| pushed_at > since)
+            })
+        })
+        .filter(|entry| remote.is_none_or(|remote| entry["remote"] == remote.0.as_str()))
+        .filter(|entry| !failed_only || entry["ok"] != true)
+        .collect()
+}
+
+fn club_log(log_args: LogCommand, ctx: &Context, settings: &Settings) -> Result<(), ClubError> {
+    let remote = log_args.remote.map(RemoteName::try_from).transpose()?;
+    let entries = filter_history(
+        read_history(&ctx.manifest_path),
+        log_args.since,
+        remote.as_ref(),
+        log_args.failed_only,
+    );
+    if settings.json {
+        return print_json(&Value::Array(entries), settings);
+    }
+    for entry in entries {
+        let outcome = if entry["ok"] == true {
+            "ok".green()
+        } else {
+            format!(
+                "failed: {}",
+                entry["error"].as_str().unwrap_or("unknown error")
+            )
+            .red()
+        };
+        println!(
+            "{}  {}  {}  {}",
+            entry["pushedAt"].as_str().unwrap_or_default(),
+            entry["remote"].as_str().unwrap_or_default(),
+            entry["id"].as_str().unwrap_or_default(),
+            outcome
+        );
+    }
+    Ok(())
+}
+
+/// Records the fingerprints of the local source as what `pushed` remotes now hold, for later
+/// `push --if-clean-remote` checks. Runs after pushes with the flag, or after every push with the
+/// `trackPushes` setting.
+fn record_pushed_state(
+    config: &ClaspConfig,
+    manifest_path: &Path,
+    pushed: &[RemoteName],
+) -> Result<(), ClubError> {
+    if pushed.is_empty() {
+        return Ok(());
+    }
+    let project_dir = get_project_dir(manifest_path);
+    let write_err = |e: std::io::Error| ClubError::PushedStateWriteFail(e.to_string());
+    let fingerprints =
+        source_fingerprints(&config.resolved_root_dir(&project_dir)).map_err(write_err)?;
+    let mut state = read_pushed_state(&project_dir);
+    for remote_name in pushed {
+        state[&remote_name.0] = fingerprints
+            .iter()
+            .map(|(path, fingerprint)| (path.clone(), Value::from(fingerprint.clone())))
+            .collect();
+    }
+    debug!("Recording pushed source for {} remotes", pushed.len());
+    let state_str = json_text(&state, false)?;
+    std::fs::write(project_dir.join(PUSHED_STATE_NAME), state_str).map_err(write_err)
+}
+
+/// Runs `f` in a temporary directory holding an empty rootDir and a manifest pointing at a script,
+/// so that clasp can be run against the script without touching the project. The directory is
+/// removed afterwards, whatever `f` returns.
+fn with_script_dir<T>(
+    remote_name: &RemoteName,
+    remote_id: &RemoteId,
+    config: &ClaspConfig,
+    f: impl FnOnce(&Path) -> Result<T, ClubError>,
+) -> Result<T, ClubError> {
+    let dir_err = |e: std::io::Error| ClubError::ScriptDirFail(e.to_string());
+    let work_dir = create_work_dir(&format!("club-remote-{}", remote_name)).map_err(dir_err)?;
+    if let Err(err) = std::fs::create_dir(work_dir.join(ISOLATED_ROOT_DIR)) {
+        let _ = std::fs::remove_dir_all(&work_dir);
+        return Err(dir_err(err));
+    }
+    debug!(
+        "Running clasp for {} in {}",
+        remote_name,
+        work_dir.display()
+    );
+
+    let result = (|| {
+        let script_config = ClaspConfig {
+            root_dir: ISOLATED_ROOT_DIR.to_string(),
+            script_id: remote_id.0.clone(),
+            parent_ids: config.parent_ids.clone(),
+            club_remotes: None,
+            club_groups: IndexMap::new(),
+            club_settings: ClubSettings::default(),
+            env_remotes: None,
+            remote_file: None,
+            push_root_dir: None,
+        };
+        write_clasp_config(&script_config, &work_dir.join(MANIFEST_NAME))?;
+        f(&work_dir)
+    })();
+    let _ = std::fs::remove_dir_all(&work_dir);
+    result
+}
+
+/// Pulls a script into a temporary directory with a manifest of its own and runs `inspect` on the
+/// pulled rootDir. The directory is removed afterwards, whether or not the pull worked.
+fn with_pulled_script<T>(
+    remote_name: &RemoteName,
+    remote_id: &RemoteId,
+    config: &ClaspConfig,
+    settings: &Settings,
+    inspect: impl FnOnce(&Path) -> Result<T, ClubError>,
+) -> Result<T, ClubError> {
+    with_script_dir(remote_name, remote_id, config, |work_dir| {
+        if !run_clasp(settings, work_dir, ["pull"])?.success() {
+            return Err(ClubError::ClaspError("clasp pull failed".to_string()));
+        }
+        inspect(&work_dir.join(ISOLATED_ROOT_DIR))
+    })
+}
+
+/// The files that differ on a remote from what club last pushed to it, found by pulling it into a
+/// temporary directory, or None if club has no record of pushing to it. Files only in the local
+/// source aren't counted, since they may be excluded by .claspignore.
+fn remote_changes(
+    remote_name: &RemoteName,
+    remote_id: &RemoteId,
+    config: &ClaspConfig,
+    manifest_path: &Path,
+    settings: &Settings,
+) -> Result<Option<Vec<String>>, ClubError> {
+    let state = read_pushed_state(&get_project_dir(manifest_path));
+    let Some(pushed) = state[&remote_name.0].as_object() else {
+        return Ok(None);
+    };
+    with_pulled_script(remote_name, remote_id, config, settings, |pulled_dir| {
+        let pulled = source_fingerprints(pulled_dir)
+            .map_err(|e| ClubError::ClaspError(format!("clasp pull failed: {}", e)))?;
+        Ok(Some(
+            pulled
+                .into_iter()
+                .filter(|(path, fingerprint)| match pushed.get(path) {
+                    // Pushed from TypeScript, so only the file's presence can be checked.
+                    Some(Value::Null) => false,
+                    recorded => recorded.and_then(Value::as_str) != fingerprint.as_deref(),
+                })
+                .map(|(path, _)| path)
+                .collect(),
+        ))
+    })
+}
+
+/// What `club verify` found for one remote.
+#[derive(Debug, PartialEq)]
+enum VerifyStatus {
+    Reachable,
+    /// Pulled, with this many source files.
+    HasSource(usize),
+    /// Pulled, but without any source files.
+    Empty,
+    Unreachable(String),
+}
+
+impl VerifyStatus {
+    fn is_ok(&self) -> bool {
+        matches!(self, VerifyStatus::Reachable | VerifyStatus::HasSource(_))
+    }
+}
+
+impl Display for VerifyStatus {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            VerifyStatus::Reachable => write!(f, "reachable"),
+            VerifyStatus::HasSource(files) => write!(f, "reachable, {} source file(s)", files),
+            VerifyStatus::Empty => write!(f, "reachable, but has no source files"),
+            VerifyStatus::Unreachable(err) => write!(f, "unreachable: {}", err),
+        }
+    }
+}
+
+/// Checks one remote's script at `depth`, each from a temporary directory. Only a missing clasp is
+/// an error; anything else that goes wrong is the remote's status.
+fn verify_remote(
+    remote_name: &RemoteName,
+    remote_id: &RemoteId,
+    config: &ClaspConfig,
+    settings: &Settings,
+    depth: VerifyDepth,
+) -> Result<VerifyStatus, ClubError> {
+    let result = match depth {
+        VerifyDepth::Shallow => with_script_dir(remote_name, remote_id, config, |work_dir| {
+            clasp_stdout(settings, work_dir, ["deployments"]).map(|_| VerifyStatus::Reachable)
+        }),
+        VerifyDepth::Full => with_pulled_script(remote_name, remote_id, config, settings, |root| {
+            let files = source_fingerprints(root)
+                .map_err(|e| ClubError::ClaspError(e.to_string()))?
+                .len();
+            Ok(match files {
+                0 => VerifyStatus::Empty,
+                files => VerifyStatus::HasSource(files),
+            })
+        }),
+    };
+    match result {
+        Err(err @ ClubError::ClaspNotInstalled(_)) => Err(err),
+        Err(err) => Ok(VerifyStatus::Unreachable(err.to_string())),
+        status => status,
+    }
+}
+
+fn club_verify(
+    verify_args: VerifyCommand,
+    ctx: &Context,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    let config = ctx.config()?;
+    let remotes = config
+        .club_remotes
+        .as_ref()
+        .ok_or(ClubError::ClubNotSetup)?;
+    let targets: Vec<(RemoteName, RemoteId)> = if verify_args.remotes.is_empty() {
+        remotes
+            .iter()
+            .map(|(remote_name, remote)| (remote_name.clone(), remote.id.clone()))
+            .collect()
+    } else {
+        let mut targets = Vec::new();
+        for name in verify_args.remotes {
+            let remote_name = RemoteName::try_from(name)?;
+            let remote = remotes.get(&remote_name).ok_or(ClubError::RemoteNotFound)?;
+            targets.push((remote_name, remote.id.clone()));
+        }
+        targets
+    };
+    // Clasp's output would otherwise be mixed into the report on stdout.
+    let clasp_settings = &Settings {
+        clasp_output_to_stderr: true,
+        ..settings.clone()
+    };
+    let mut results = Vec::new();
+    for (remote_name, remote_id) in targets {
+        let status = verify_remote(
+            &remote_name,
+            &remote_id,
+            config,
+            clasp_settings,
+            verify_args.depth,
+        )?;
+        results.push((remote_name, remote_id, status));
+    }
+
+    if settings.json {
+        let json: Vec<Value> = results
+            .iter()
+            .map(|(remote_name, remote_id, status)| {
+                serde_json::json!({
+                    "name": remote_name.0,
+                    "id": remote_id.0,
+                    "ok": status.is_ok(),
+                    "status": status.to_string(),
+                })
+            })
+            .collect();
+        print_json(&Value::Array(json), settings)?;
+    } else {
+        let width = results
+            .iter()
+            .map(|(remote_name, _, _)| remote_name.0.len())
+            .max()
+            .unwrap_or(0);
+        for (remote_name, _, status) in &results {
+            let line = format!("{:width$}  {}", remote_name.0, status);
+            if status.is_ok() {
+                println!("{}", line.green());
+            } else {
+                println!("{}", line.red());
+            }
+        }
+    }
+    let failed: Vec<RemoteName> = results
+        .into_iter()
+        .filter(|(_, _, status)| !status.is_ok())
+        .map(|(remote_name, _, _)| remote_name)
+        .collect();
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(ClubError::VerifyFailed(failed))
+    }
+}
+
+/// Refuses to save a script ID for `set --validate-live` unless clasp can pull it, which catches
+/// mistyped IDs and scripts you can't access. Clasp's output goes to stderr.
+fn ensure_script_reachable(
+    remote_name: &RemoteName,
+    remote_id: &RemoteId,
+    config: &ClaspConfig,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    let settings = &Settings {
+        clasp_output_to_stderr: true,
+        ..settings.clone()
+    };
+    let pulled = with_script_dir(remote_name, remote_id, config, |work_dir| {
+        Ok(run_clasp(settings, work_dir, ["pull"])?.success())
+    })?;
+    if pulled {
+        Ok(())
+    } else {
+        Err(ClubError::ScriptUnreachable(
+            remote_name.clone(),
+            remote_id.clone(),
+        ))
+    }
+}
+
+/// Refuses the push if any target has changes club didn't push, before anything is pushed.
+fn ensure_clean_remotes(
+    targets: &[(RemoteName, Remote)],
+    config: &ClaspConfig,
+    manifest_path: &Path,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    let mut diverged = Vec::new();
+    for (remote_name, remote) in targets {
+        match remote_changes(remote_name, &remote.id, config, manifest_path, settings)? {
+            None => warn(
+                settings,
+                format!(
+                    "club has no record of pushing to {}, so its changes can't be checked.",
+                    remote_name
+                ),
+            )?,
+            Some(changed) if !changed.is_empty() => diverged.push((remote_name.clone(), changed)),
+            Some(_) => {}
+        }
+    }
+    if diverged.is_empty() {
+        Ok(())
+    } else {
+        Err(ClubError::RemoteDiverged(diverged))
+    }
+}
+
+/// Runs a `--pre-push` command in rootDir, with the remote about to be pushed to in its environment
+/// unless it runs once for the whole push. Its output goes to stderr, like the rest of the push's
+/// progress.
+fn run_pre_push_hook(
+    hook: &str,
+    target: Option<(&RemoteName, &RemoteId)>,
+    root_dir: &Path,
+) -> Result<(), ClubError> {
+    let remote_name = target.map(|(remote_name, _)| remote_name.clone());
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(hook)
+        .current_dir(root_dir)
+        .stdout(std::io::stderr());
+    if let Some((remote_name, remote_id)) = target {
+        command
+            .env("CLUB_REMOTE", &remote_name.0)
+            .env("CLUB_SCRIPT_ID", &remote_id.0);
+    }
+    debug!(
+        "Running pre-push command {:?} in {}",
+        hook,
+        root_dir.display()
+    );
+    let status = command
+        .status()
+        .map_err(|e| ClubError::PrePushFailed(remote_name.clone(), e.to_string()))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(ClubError::PrePushFailed(
+            remote_name,
+            format!("`{}` exited with {}", hook, status),
+        ))
+    }
+}
+
+/// Runs an after-push hook through the shell in the project directory, telling it which remote was
+/// pushed through `CLUB_REMOTE` and `CLUB_SCRIPT_ID`.
+fn run_after_push_hook(
+    hook: &str,
+    remote_name: &RemoteName,
+    remote_id: &RemoteId,
+    manifest_path: &Path,
+) -> Result<(), ClubError> {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(hook)
+        .current_dir(get_project_dir(manifest_path))
+        .env("CLUB_REMOTE", &remote_name.0)
+        .env("CLUB_SCRIPT_ID", &remote_id.0)
+        .status()
+        .map_err(|e| ClubError::HookFailed(remote_name.clone(), e.to_string()))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(ClubError::HookFailed(
+            remote_name.clone(),
+            format!("`{}` exited with {}", hook, status),
+        ))
+    }
+}
+
+/// Records a push in rootDir's DEPLOY_INFO file, a JSON object with the latest message and push time
+/// for each remote. A remote's earlier marker is replaced; other remotes' markers are kept.
+fn write_deploy_marker(
+    root_dir: &Path,
+    remote_name: &RemoteName,
+    message: &str,
+    pushed_at: SystemTime,
+) -> Result<(), ClubError> {
+    let path = root_dir.join(DEPLOY_INFO_NAME);
+    let mut markers = match std::fs::read_to_string(&path) {
+        Ok(markers_str) => serde_json::from_str(&markers_str)
+            .map_err(|e| ClubError::DeployInfoWriteFail(e.to_string()))?,
+        Err(_) => serde_json::json!({}),
+    };
+    if !markers.is_object() {
+        return Err(ClubError::DeployInfoWriteFail(
+            "expected an object mapping remote names to deploy markers".to_string(),
+        ));
+    }
+    markers[&remote_name.0] = serde_json::json!({
+        "message": message,
+        "pushedAt": format_timestamp(pushed_at),
+    });
+    let markers_str = serde_json::to_string_pretty(&markers)
+        .map_err(|e| ClubError::DeployInfoWriteFail(e.to_string()))?;
+    std::fs::write(&path, markers_str + "\n")
+        .map_err(|e| ClubError::DeployInfoWriteFail(e.to_string()))
+}
+
+/// Formats a time as a UTC RFC 3339 timestamp, e.g. `2024-03-09T17:04:05Z`.
+fn format_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+    // Converts days since the epoch to a civil date, after Howard Hinnant's `civil_from_days`.
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Parses a UTC or offset RFC 3339 timestamp, such as `2024-03-09T17:04:05Z` or
+/// `2024-03-09T19:04:05.250+02:00`. Fractions of a second are dropped.
+fn parse_timestamp(value: &str) -> Option<SystemTime> {
+    let re = Regex::new(
+        r"^(\d{4})-(\d{2})-(\d{2})[Tt ](\d{2}):(\d{2}):(\d{2})(?:\.\d+)?(?:([Zz])|([+-])(\d{2}):(\d{2}))$",
+    )
+    .unwrap();
+    let caps = re.captures(value)?;
+    let field = |index: usize| caps[index].parse::<i64>().ok();
+    let (year, month, day) = (field(1)?, field(2)?, field(3)?);
+    let (hour, minute, second) = (field(4)?, field(5)?, field(6)?);
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+    // Converts a civil date to days since the epoch, after Howard Hinnant's `days_from_civil`.
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    let mut secs = days * 86400 + hour * 3600 + minute * 60 + second;
+    if caps.get(7).is_none() {
+        let offset = field(9)? * 3600 + field(10)? * 60;
+        secs -= if &caps[8] == "-" { -offset } else { offset };
+    }
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(secs).ok()?))
+}
+
+/// Parses a `log --since` time.
+fn parse_since(value: &str) -> Result<SystemTime, String> {
+    parse_since_at(value, SystemTime::now())
+}
+
+/// Parses an RFC 3339 timestamp, or a span of time before `now` such as `7d`, `2h`, `30m`, `45s`,
+/// or `2w`.
+fn parse_since_at(value: &str, now: SystemTime) -> Result<SystemTime, String> {
+    let re = Regex::new(r"^(\d+)([smhdw])$").unwrap();
+    let Some(caps) = re.captures(value.trim()) else {
+        return parse_timestamp(value.trim()).ok_or_else(|| {
+            format!(
+                "`{}` is neither an RFC 3339 timestamp (like 2024-03-09T17:04:05Z) nor a time ago (like 7d or 2h)",
+                value
+            )
+        });
+    };
+    let unit = match &caps[2] {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => 7 * 86400,
+    };
+    caps[1]
+        .parse::<u64>()
+        .ok()
+        .and_then(|count| count.checked_mul(unit))
+        .and_then(|secs| now.checked_sub(Duration::from_secs(secs)))
+        .ok_or_else(|| format!("`{}` is too far back", value))
+}
+
+/// The error for clasp failing to start, so that a missing clasp executable is reported as such
+/// rather than as a bare OS error.
+fn clasp_spawn_error(settings: &Settings, e: std::io::Error) -> ClubError {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => ClubError::ClaspNotInstalled(settings.clasp_bin.clone()),
+        _ => ClubError::ClaspError(e.to_string()),
+    }
+}
+
+/// Runs clasp with `args` in `dir`.
+fn run_clasp<I, S>(settings: &Settings, dir: &Path, args: I) -> Result<ExitStatus, ClubError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut command = Command::new(&settings.clasp_bin);
+    command.args(args).current_dir(dir);
+    if settings.silence_clasp {
+        command.stdout(std::process::Stdio::null());
+    } else if settings.clasp_output_to_stderr {
+        command.stdout(std::io::stderr());
+    } else {
+        command.stdout(std::process::Stdio::inherit());
+    }
+    debug!("Running {:?} in {}", command, dir.display());
+    let spawn_err = |e| clasp_spawn_error(settings, e);
+    if !settings.capture_clasp_stderr {
+        let status = command.status().map_err(spawn_err)?;
+        debug!("clasp exited with {}", status);
+        return Ok(status);
+    }
+    let output = command
+        .stderr(std::process::Stdio::piped())
+        .output()
+        .map_err(spawn_err)?;
+    // Pass clasp's stderr along as usual once it's done, keeping a copy for the error.
+    if !settings.silence_clasp {
+        let _ = std::io::stderr().write_all(&output.stderr);
+    }
+    debug!("clasp exited with {}", output.status);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if output.status.success() || stderr.trim().is_empty() {
+        return Ok(output.status);
+    }
+    Err(ClubError::ClaspError(format!(
+        "{} ({})",
+        stderr.trim(),
+        output.status
+    )))
+}
+
+/// Like `run_clasp`, but captures clasp's stdout instead of passing it through.
+fn clasp_stdout<I, S>(settings: &Settings, dir: &Path, args: I) -> Result<String, ClubError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut command = Command::new(&settings.clasp_bin);
+    command.args(args).current_dir(dir);
+    debug!("Running {:?} in {}", command, dir.display());
+    let output = command
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .map_err(|e| clasp_spawn_error(settings, e))?;
+    debug!("clasp exited with {}", output.status);
+    if !output.status.success() {
+        return Err(ClubError::ClaspError(format!(
+            "clasp exited with {}",
+            output.status
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// How long one `run_clasp_on_remote` call spent on each step, for `push --profile`.
+#[derive(Debug, Default)]
+struct ClaspTimings {
+    write: Duration,
+    clasp: Duration,
+    restore: Duration,
+}
+
+impl ClaspTimings {
+    fn total(&self) -> Duration {
+        self.write + self.clasp + self.restore
+    }
+}
+
+/// Points the manifest at `remote_id`, runs clasp with `args`, and restores the original manifest
+/// whether or not clasp succeeded, recording how long each step took in `timings`.
+fn run_clasp_on_remote<S: AsRef<OsStr>>(
+    remote_id: RemoteId,
+    config: &ClaspConfig,
+    manifest_path: &Path,
+    settings: &Settings,
+    args: &[S],
+    timings: &mut ClaspTimings,
+) -> Result<(), ClubError> {
+    let status = with_manifest_on_remote(remote_id, config, manifest_path, timings, || {
+        run_clasp(
+            settings,
+            &get_project_dir(manifest_path),
+            clasp_project_args(manifest_path)
+                .into_iter()
+                .chain(args.iter().map(AsRef::as_ref)),
+        )
+    })?;
+    let subcommand = args
+        .first()
+        .map(|arg| arg.as_ref().to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if status.success() {
+        Ok(())
+    } else {
+        Err(ClubError::ClaspError(format!(
+            "clasp {} failed",
+            subcommand
+        )))
+    }
+}
+
+/// Points the manifest at `remote_id` while `run` runs clasp, then restores the original manifest
+/// whether or not it succeeded.
+fn with_manifest_on_remote<T>(
+    remote_id: RemoteId,
+    config: &ClaspConfig,
+    manifest_path: &Path,
+    timings: &mut ClaspTimings,
+    run: impl FnOnce() -> Result<T, ClubError>,
+) -> Result<T, ClubError> {
+    let mut config_copy = config.clone();
+    // Record where the manifest should point once we're done, so that `club repair` can restore it
+    // if we're interrupted before the restore below.
+    config_copy.club_settings.active_remote = intended_active_remote(config);
+    debug!(
+        "Pointing the manifest at {} for clasp, then restoring it to {}",
+        remote_id, config.script_id
+    );
+    config_copy.script_id = remote_id.0;
+    if let Some(push_root_dir) = config_copy.push_root_dir.take() {
+        config_copy.root_dir = push_root_dir;
+    }
+    let started = Instant::now();
+    write_clasp_config(&config_copy, manifest_path)?;
+    timings.write = started.elapsed();
+    let started = Instant::now();
+    let result = run();
+    timings.clasp = started.elapsed();
+    // Restore the original config
+    let started = Instant::now();
+    write_clasp_config(config, manifest_path)?;
+    timings.restore = started.elapsed();
+    result
+}
+
+fn club_clasp(
+    clasp_args: ClaspCommand,
+    ctx: &Context,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    let config = ctx.config()?;
+    let remotes = config
+        .club_remotes
+        .as_ref()
+        .ok_or(ClubError::ClubNotSetup)?;
+
+    let remote_name = RemoteName::try_from(clasp_args.remote)?;
+    let remote = remotes.get(&remote_name).ok_or(ClubError::RemoteNotFound)?;
+    run_clasp_on_remote(
+        remote.id.clone(),
+        config,
+        &ctx.manifest_path,
+        settings,
+        &clasp_args.args,
+        &mut ClaspTimings::default(),
+    )
+}
+
+fn club_open(open_args: OpenCommand, ctx: &Context, settings: &Settings) -> Result<(), ClubError> {
+    let config = ctx.config()?;
+    let remotes = config
+        .club_remotes
+        .as_ref()
+        .ok_or(ClubError::ClubNotSetup)?;
+    let remote_name = RemoteName::try_from(open_args.remote)?;
+    let remote = remotes.get(&remote_name).ok_or(ClubError::RemoteNotFound)?;
+    let url = if open_args.deployment {
+        let listing = with_manifest_on_remote(
+            remote.id.clone(),
+            config,
+            &ctx.manifest_path,
+            &mut ClaspTimings::default(),
+            || {
+                clasp_stdout(
+                    settings,
+                    &get_project_dir(&ctx.manifest_path),
+                    clasp_project_args(&ctx.manifest_path)
+                        .into_iter()
+                        .chain([OsStr::new("deployments")]),
+                )
+            },
+        )?;
+        web_app_url(&listing).ok_or(ClubError::NoWebAppDeployment(remote_name))?
+    } else {
+        format!("https://script.google.com/d/{}/edit", remote.id)
+    };
+    if open_args.print {
+        println!("{}", url);
+        return Ok(());
+    }
+    open_in_browser(&url)
+}
+
+/// The web app URL of the highest-versioned deployment in `clasp deployments` output, whose lines
+/// look like `- <deploymentId> @<version> - <description>`. The `@HEAD` deployment only serves the
+/// latest code to the script's editors, so it doesn't count.
+fn web_app_url(listing: &str) -> Option<String> {
+    let re = Regex::new(r"^-\s+([A-Za-z0-9_-]+)\s+@(\d+)\b").unwrap();
+    listing
+        .lines()
+        .filter_map(|line| re.captures(line.trim()))
+        .filter_map(|caps| Some((caps[2].parse::<u64>().ok()?, caps[1].to_string())))
+        .max_by_key(|(version, _)| *version)
+        .map(|(_, deployment_id)| {
+            format!("https://script.google.com/macros/s/{}/exec", deployment_id)
+        })
+}
+
+fn open_in_browser(url: &str) -> Result<(), ClubError> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    debug!("Opening {} with {:?}", url, command);
+    let status = command
+        .arg(url)
+        .status()
+        .map_err(|e| ClubError::BrowserOpenFail(e.to_string()))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(ClubError::BrowserOpenFail(format!(
+            "the opener exited with {}",
+            status
+        )))
+    }
+}
+
+fn club_env(env_args: EnvCommand, ctx: &Context, settings: &Settings) -> Result<(), ClubError> {
+    let config = ctx.config()?.clone();
+    let remotes = config.club_remotes.clone().ok_or(ClubError::ClubNotSetup)?;
+
+    let remote_name = match env_args.remote {
+        Some(remote) => RemoteName::try_from(remote)?,
+        None => intended_active_remote(&config).unwrap_or_else(|| settings.default_remote.clone()),
+    };
+    let remote = remotes.get(&remote_name).ok_or(ClubError::RemoteNotFound)?;
+    print!(
+        "{}",
+        env_exports(&remote_name, &remote.id, env_args.fish, env_args.powershell)
+    );
+    Ok(())
+}
+
+/// The lines `club env` prints: POSIX `export`s by default, or fish or PowerShell syntax.
+fn env_exports(
+    remote_name: &RemoteName,
+    remote_id: &RemoteId,
+    fish: bool,
+    powershell: bool,
+) -> String {
+    let mut exports = String::new();
+    for (key, value) in [
+        ("CLUB_REMOTE", &remote_name.0),
+        ("CLUB_SCRIPT_ID", &remote_id.0),
+    ] {
+        let line = if fish {
+            format!("set -gx {} {};", key, value)
+        } else if powershell {
+            format!("$env:{} = \"{}\"", key, value)
+        } else {
+            format!("export {}={}", key, value)
+        };
+        exports.push_str(&line);
+        exports.push('\n');
+    }
+    exports
+}
+
+/// Upgrades `config` to `CLUB_SCHEMA_VERSION` one version at a time, returning the version it was
+/// at and the remotes renamed along the way. Reading a config and writing it back already puts its
+/// remote entries in their current form, so only the names need migrating.
+fn migrate_club_config(
+    config: &mut ClaspConfig,
+) -> Result<(u64, Vec<(RemoteName, RemoteName)>), ClubError> {
+    let remotes = config
+        .club_remotes
+        .as_ref()
+        .ok_or(ClubError::ClubNotSetup)?;
+    let version = config.club_settings.version.unwrap_or(1);
+    let mut renames = Vec::new();
+    if version < 2 {
+        renames = normalized_names(remotes, sanitize_remote_name)?;
+        rename_remotes(config, &renames);
+    }
+    config.club_settings.version = Some(CLUB_SCHEMA_VERSION);
+    Ok((version, renames))
+}
+
+fn club_config_migrate(ctx: &Context, settings: &Settings) -> Result<(), ClubError> {
+    // Read without `config()`, whose warning about off-format names would fail a --strict run
+    // before they could be migrated.
+    let mut config = ctx.read_config(settings.validate_ids)?;
+    let (version, renames) = migrate_club_config(&mut config)?;
+    if version == CLUB_SCHEMA_VERSION {
+        if !settings.quiet {
+            eprintln!("__club__ is up to date (version {}).", version);
+        }
+        return Ok(());
+    }
+    ctx.write_config(&config)?;
+    if !settings.quiet {
+        for (old_name, new_name) in &renames {
+            eprintln!("Renamed {} -> {}", old_name, new_name);
+        }
+        eprintln!(
+            "Migrated __club__ from version {} to {}.",
+            version, CLUB_SCHEMA_VERSION
+        );
+    }
+    Ok(())
+}
+
+fn club_group(group_command: GroupCommand, ctx: &Context) -> Result<(), ClubError> {
+    let config = ctx.config()?.clone();
+    let remotes = config.club_remotes.clone().ok_or(ClubError::ClubNotSetup)?;
+    let mut groups = config.club_groups.clone();
+
+    match group_command {
+        GroupCommand::List(_) => {
+            for (group, members) in &groups {
+                let members: Vec<String> = members.iter().map(|m| m.to_string()).collect();
+                println!("{}: {}", group.bold(), members.join(", "));
+            }
+            return Ok(());
+        }
+        GroupCommand::Add(add_args) => {
+            // Only a new group's name has to meet the current rules, so that groups named under
+            // the old ones can still be added to.
+            let group = if groups.contains_key(&add_args.group) {
+                add_args.group
+            } else {
+                RemoteName::parse_new(add_args.group)?.0
+            };
+            let members = groups.entry(group).or_default();
+            for remote in add_args.remotes {
+                let remote_name = RemoteName::try_from(remote)?;
+                if !remotes.contains_key(&remote_name) {
+                    return Err(ClubError::RemoteNotFound);
+                }
+                if !members.contains(&remote_name) {
+                    members.push(remote_name);
+                }
+            }
+        }
+        GroupCommand::Remove(remove_args) => {
+            if remove_args.remotes.is_empty() {
+                groups
+                    .shift_remove(&remove_args.group)
+                    .ok_or(ClubError::GroupNotFound)?;
+            } else {
+                let members = groups
+                    .get_mut(&remove_args.group)
+                    .ok_or(ClubError::GroupNotFound)?;
+                for remote in remove_args.remotes {
+                    let remote_name = RemoteName::try_from(remote)?;
+                    let index = members
+                        .iter()
+                        .position(|member| *member == remote_name)
+                        .ok_or(ClubError::RemoteNotFound)?;
+                    members.remove(index);
+                }
+            }
+        }
+    }
+
+    let new_config = ClaspConfig {
+        club_groups: groups,
+        ..config
+    };
+    ctx.write_config(&new_config)
+}
+
+/// The remote the manifest is meant to point at when club isn't mid-operation: the persisted
+/// `activeRemote` if there is one, otherwise the first remote matching the current scriptId.
+fn intended_active_remote(config: &ClaspConfig) -> Option<RemoteName> {
+    let remotes = config.club_remotes.as_ref()?;
+    if let Some(active_remote) = &config.club_settings.active_remote {
+        if remotes.contains_key(active_remote) {
+            return Some(active_remote.clone());
+        }
+    }
+    remotes
+        .iter()
+        .find(|(_, remote)| remote.id.0 == config.script_id)
+        .map(|(remote_name, _)| remote_name.clone())
+}
+
+/// Returns the remote the manifest should point at if it was left swapped to another remote, or
+/// `None` if the manifest is consistent. The persisted `activeRemote` is trusted over the default
+/// remote.
+fn find_swapped_state(
+    config: &ClaspConfig,
+    default_remote: &RemoteName,
+) -> Option<(RemoteName, Remote)> {
+    let remotes = config.club_remotes.as_ref()?;
+    let target_name = config
+        .club_settings
+        .active_remote
+        .clone()
+        .filter(|name| remotes.contains_key(name))
+        .unwrap_or_else(|| default_remote.clone());
+    let target = remotes.get(&target_name)?;
+    if target.id.0 == config.script_id {
+        return None;
+    }
+    let swapped = remotes.iter().any(|(remote_name, remote)| {
+        *remote_name != target_name && remote.id.0 == config.script_id
+    });
+    swapped.then(|| (target_name, target.clone()))
+}
+
+fn club_switch(
+    switch_args: SwitchCommand,
+    ctx: &Context,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    let config = ctx.config()?.clone();
+    let remotes = config.club_remotes.clone().ok_or(ClubError::ClubNotSetup)?;
+
+    let remote_name = RemoteName::try_from(switch_args.name)?;
+    let remote = remotes.get(&remote_name).ok_or(ClubError::RemoteNotFound)?;
+    let mut new_config = config.clone();
+    new_config.script_id = remote.id.0.clone();
+    new_config.club_settings.active_remote = Some(remote_name.clone());
+    ctx.write_config(&new_config)?;
+    if settings.quiet {
+        return Ok(());
+    }
+    eprintln!("Switched to {} ({}).", remote_name, remote.id);
+    Ok(())
+}
+
+fn club_touch(
+    touch_args: TouchCommand,
+    ctx: &Context,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    if ctx.manifest_path.exists() && !touch_args.force {
+        return Err(ClubError::ManifestExists(ctx.manifest_path.clone()));
+    }
+    let remote_id = RemoteId::parse(touch_args.script_id, settings.validate_ids)?;
+    warn_if_legacy_id(settings, &remote_id)?;
+    let config = ClaspConfig {
+        root_dir: touch_args.root_dir.unwrap_or_default(),
+        script_id: remote_id.0.clone(),
+        parent_ids: Vec::new(),
+        club_remotes: Some(IndexMap::from([(
+            RemoteName("main".to_string()),
+            Remote::from(remote_id.clone()),
+        )])),
+        club_groups: IndexMap::new(),
+        club_settings: ClubSettings {
+            version: Some(CLUB_SCHEMA_VERSION),
+            ..ClubSettings::default()
+        },
+        env_remotes: None,
+        remote_file: settings.remote_file.clone(),
+        push_root_dir: None,
+    };
+    ctx.write_config(&config)?;
+    if !settings.quiet {
+        eprintln!(
+            "Created {} with main remote set to {}.",
+            ctx.manifest_path.display(),
+            remote_id
+        );
+    }
+    Ok(())
+}
+
+fn club_validate_name(validate_args: ValidateNameCommand) -> Result<(), ClubError> {
+    println!("{}", RemoteName::parse_new(validate_args.name)?);
+    Ok(())
+}
+
+fn club_validate_url(validate_args: ValidateUrlCommand) -> Result<(), ClubError> {
+    println!("{}", RemoteId::from_url(&validate_args.url)?);
+    Ok(())
+}
+
+/// The `__club__` block `club export` prints, with script IDs masked by `redact_id` if `redact` is
+/// set. Masked IDs aren't valid script IDs, so a redacted export can't be imported by mistake.
+fn export_json(config: &ClaspConfig, redact: bool) -> Result<Value, ClubError> {
+    let mut config = config.clone();
+    let remotes = config
+        .club_remotes
+        .as_mut()
+        .ok_or(ClubError::ClubNotSetup)?;
+    if redact {
+        for remote in remotes.values_mut() {
+            remote.id = RemoteId(redact_id(&remote.id.0));
+        }
+    }
+    Ok(Value::try_from(&config)?["__club__"].take())
+}
+
+fn club_export(
+    export_args: ExportCommand,
+    ctx: &Context,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    print_json(&export_json(ctx.config()?, export_args.redact)?, settings)
+}
+
+fn club_examples(examples_args: ExamplesCommand) -> Result<(), ClubError> {
+    print!("{}", examples_text(examples_args.command.as_deref())?);
+    Ok(())
+}
+
+/// The `club examples` output for one command, or for every command if none is given.
+fn examples_text(command: Option<&str>) -> Result<String, ClubError> {
+    let sections: Vec<_> = EXAMPLES
+        .iter()
+        .filter(|(name, _)| command.is_none_or(|command| command == *name))
+        .collect();
+    if let (Some(command), true) = (command, sections.is_empty()) {
+        return Err(ClubError::NoExamples(command.to_string()));
+    }
+    let mut text = String::new();
+    for (name, examples) in sections {
+        if command.is_none() {
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str(&format!("{}:\n", name));
+        }
+        for (description, line) in *examples {
+            text.push_str(&format!("  # {}\n  {}\n", description, line));
+        }
+    }
+    Ok(text)
+}
+
+fn club_repair(
+    repair_args: RepairCommand,
+    ctx: &Context,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    let config = ctx.config()?.clone();
+    if config.club_remotes.is_none() {
+        return Err(ClubError::ClubNotSetup);
+    }
+
+    let Some((remote_name, remote)) = find_swapped_state(&config, &settings.default_remote) else {
+        if !settings.quiet {
+            eprintln!("Nothing to repair.");
+        }
+        return Ok(());
+    };
+    let prompt = format!(
+        "The manifest's scriptId {} belongs to another remote. Restore it to {} ({})?",
+        config.script_id, remote_name, remote.id
+    );
+    if !repair_args.yes && !confirm(&prompt) {
+        eprintln!("Repair cancelled. Pass --yes to repair without confirmation.");
+        return Ok(());
+    }
+    let new_config = ClaspConfig {
+        script_id: remote.id.0.clone(),
+        ..config
+    };
+    ctx.write_config(&new_config)?;
+    if settings.quiet {
+        return Ok(());
+    }
+    eprintln!("Restored scriptId to {} ({}).", remote_name, remote.id);
+    Ok(())
+}
+
+/// A repair `club doctor --fix` can make.
+#[derive(Debug, PartialEq)]
+enum DoctorFix {
+    /// Point the manifest's scriptId back at the remote it belongs on, as `club repair` does.
+    RestoreScriptId(RemoteName, RemoteId),
+    /// Add the manifest's scriptId as the main remote of an empty `__club__`, as `club init` does.
+    SeedMain(RemoteId),
+    /// Replace a remote's off-format script ID with the valid one found in it.
+    RecoverId(RemoteName, RemoteId),
+}
+
+impl Display for DoctorFix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DoctorFix::RestoreScriptId(remote_name, remote_id) => write!(f, "The manifest was left pointing at another remote. Fix: restore scriptId to {} ({}).", remote_name, remote_id),
+            DoctorFix::SeedMain(remote_id) => write!(f, "__club__ has no remotes. Fix: add the manifest's scriptId {} as main.", remote_id),
+            DoctorFix::RecoverId(remote_name, remote_id) => write!(f, "{}'s script ID is off-format. Fix: replace it with the script ID in it, {}.", remote_name, remote_id),
+        }
+    }
+}
+
+impl DoctorFix {
+    fn apply(&self, config: &mut ClaspConfig) {
+        match self {
+            DoctorFix::RestoreScriptId(_, remote_id) => config.script_id = remote_id.0.clone(),
+            DoctorFix::SeedMain(remote_id) => {
+                config
+                    .club_remotes
+                    .get_or_insert_with(IndexMap::new)
+                    .insert(
+                        RemoteName("main".to_string()),
+                        Remote::from(remote_id.clone()),
+                    );
+                // With no remotes before, there was nothing to migrate.
+                config.club_settings.version = Some(CLUB_SCHEMA_VERSION);
+            }
+            DoctorFix::RecoverId(remote_name, remote_id) => {
+                if let Some(remote) = config
+                    .club_remotes
+                    .as_mut()
+                    .and_then(|remotes| remotes.get_mut(remote_name))
+                {
+                    remote.id = remote_id.clone();
+                }
+            }
+        }
+    }
+}
+
+/// Rewrites each script ID in a manifest's `__club__` that is off-format only because it has
+/// whitespace around it or was pasted as an Apps Script URL, returning the remotes it recovered.
+fn recover_remote_ids(manifest: &mut Value) -> Vec<(RemoteName, RemoteId)> {
+    let Some(entries) = manifest["__club__"].as_object_mut() else {
+        return Vec::new();
+    };
+    let mut recovered = Vec::new();
+    for (key, value) in entries.iter_mut() {
+        let Ok(remote_name) = RemoteName::try_from(key.clone()) else {
+            continue;
+        };
+        let id = match value {
+            Value::String(id) => id,
+            Value::Object(fields) => match fields.get_mut("id") {
+                Some(Value::String(id)) => id,
+                _ => continue,
+            },
+            _ => continue,
+        };
+        if RemoteId::try_from(id.clone()).is_ok() {
+            continue;
+        }
+        let trimmed = id.trim();
+        let Ok(remote_id) =
+            RemoteId::try_from(trimmed.to_string()).or_else(|_| RemoteId::from_url(trimmed))
+        else {
+            continue;
+        };
+        *id = remote_id.0.clone();
+        recovered.push((remote_name, remote_id));
+    }
+    recovered
+}
+
+/// The fixes for problems in the config itself: an empty `__club__` with a usable scriptId, or a
+/// manifest left swapped by an interrupted push.
+fn doctor_fixes(config: &ClaspConfig, settings: &Settings) -> Vec<DoctorFix> {
+    if config.club_remotes.as_ref().is_none_or(IndexMap::is_empty) {
+        return RemoteId::parse(config.script_id.clone(), settings.validate_ids)
+            .map(DoctorFix::SeedMain)
+            .into_iter()
+            .collect();
+    }
+    find_swapped_state(config, &settings.default_remote)
+        .map(|(remote_name, remote)| DoctorFix::RestoreScriptId(remote_name, remote.id))
+        .into_iter()
+        .collect()
+}
+
+fn club_doctor(
+    doctor_args: DoctorCommand,
+    ctx: &Context,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    // Problems doctor can only report.
+    let mut problems = Vec::new();
+    if clasp_version(&settings.clasp_bin).is_none() {
+        problems.push(ClubError::ClaspNotInstalled(settings.clasp_bin.clone()).to_string());
+    } else {
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        if matches!(
+            login_status(settings, home.as_deref()),
+            Ok(LoginStatus::LoggedOut)
+        ) {
+            problems.push(ClubError::NotLoggedIn.to_string());
+        }
+    }
+
+    // IDs are recovered in the manifest's own __club__, before parsing, since they wouldn't parse.
+    let mut manifest = read_manifest_json(&ctx.manifest_path)?;
+    let recovered = if ctx.remote_file.is_none() {
+        recover_remote_ids(&mut manifest)
+    } else {
+        Vec::new()
+    };
+    let mut config = ctx.with_remote_sources(ClaspConfig::parse(manifest, false)?, false)?;
+    for (remote_name, remote) in config.club_remotes.iter().flatten() {
+        if RemoteId::try_from(remote.id.0.clone()).is_err() {
+            problems.push(format!(
+                "{}'s script ID {} is off-format. Set the right one with `club reassign {} <scriptId>`.",
+                remote_name, remote.id, remote_name
+            ));
+        }
+    }
+    let mut fixes: Vec<DoctorFix> = recovered
+        .into_iter()
+        .map(|(remote_name, remote_id)| DoctorFix::RecoverId(remote_name, remote_id))
+        .collect();
+    fixes.extend(doctor_fixes(&config, settings));
+
+    if problems.is_empty() && fixes.is_empty() {
+        if !settings.quiet {
+            println!("No problems found.");
+        }
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("{}", format!("- {}", problem).red());
+    }
+    for fix in &fixes {
+        println!("{}", format!("- {}", fix).yellow());
+    }
+    if fixes.is_empty() {
+        return Err(ClubError::DoctorProblemsLeft(problems.len()));
+    }
+    if !doctor_args.fix {
+        eprintln!("Run `club doctor --fix` to fix what can be fixed.");
+        return Err(ClubError::DoctorProblemsLeft(problems.len() + fixes.len()));
+    }
+    if !doctor_args.yes && !confirm("Apply these fixes?") {
+        eprintln!("No fixes applied. Pass --yes to fix without confirmation.");
+        return Err(ClubError::DoctorProblemsLeft(problems.len() + fixes.len()));
+    }
+    for fix in &fixes {
+        fix.apply(&mut config);
+    }
+    ctx.write_config(&config)?;
+    if !settings.quiet {
+        eprintln!("Applied {} fix(es).", fixes.len());
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(ClubError::DoctorProblemsLeft(problems.len()))
+    }
+}
+
+/// The remotes whose ID is the manifest's scriptId, in order.
+fn remotes_at_script_id(config: &ClaspConfig) -> Vec<&RemoteName> {
+    config
+        .club_remotes
+        .iter()
+        .flatten()
+        .filter(|(_, remote)| remote.id.0 == config.script_id)
+        .map(|(remote_name, _)| remote_name)
+        .collect()
+}
+
+/// The remote the manifest's scriptId currently points at. When several remotes share the ID, the
+/// persisted active remote wins if it is one of them, and otherwise the first in order; see
+/// `active_remote_is_ambiguous`. `None` means the manifest is detached from every remote.
+fn active_remote(config: &ClaspConfig) -> Option<&RemoteName> {
+    let matches = remotes_at_script_id(config);
+    let persisted = config.club_settings.active_remote.as_ref();
+    matches
+        .iter()
+        .find(|remote_name| Some(**remote_name) == persisted)
+        .or(matches.first())
+        .copied()
+}
+
+/// Whether more than one remote shares the manifest's scriptId, so that `active_remote` had to pick.
+fn active_remote_is_ambiguous(config: &ClaspConfig) -> bool {
+    remotes_at_script_id(config).len() > 1
+}
+
+/// The last deploy marker `push --message` recorded for a remote, if there is a readable one.
+fn read_deploy_marker(root_dir: &Path, remote_name: &RemoteName) -> Option<Value> {
+    let markers_str = std::fs::read_to_string(root_dir.join(DEPLOY_INFO_NAME)).ok()?;
+    let markers: Value = serde_json::from_str(&markers_str).ok()?;
+    markers
+        .get(&remote_name.0)
+        .filter(|marker| marker.is_object())
+        .cloned()
+}
+
+fn show_json(
+    config: &ClaspConfig,
+    remote_name: &RemoteName,
+    root_dir: &Path,
+) -> Result<Value, ClubError> {
+    let remote = config
+        .club_remotes
+        .as_ref()
+        .ok_or(ClubError::ClubNotSetup)?
+        .get(remote_name)
+        .ok_or(ClubError::RemoteNotFound)?;
+    let groups: Vec<&String> = config
+        .club_groups
+        .iter()
+        .filter(|(_, members)| members.contains(remote_name))
+        .map(|(group, _)| group)
+        .collect();
+    let last_deploy = read_deploy_marker(root_dir, remote_name).map(|marker| {
+        serde_json::json!({
+            "message": marker["message"],
+            "pushed_at": marker["pushedAt"],
+        })
+    });
+    Ok(serde_json::json!({
+        "name": remote_name.0,
+        "id": remote.id.0,
+        "protected": remote.protected,
+        "description": remote.description,
+        "active": active_remote(config) == Some(remote_name),
+        "groups": groups,
+        "last_deploy": last_deploy,
+    }))
+}
+
+fn club_show(show_args: ShowCommand, ctx: &Context, settings: &Settings) -> Result<(), ClubError> {
+    let config = ctx.config()?;
+    let remote_name = RemoteName::try_from(show_args.name)?;
+    let root_dir = config.resolved_root_dir(&get_project_dir(&ctx.manifest_path));
+    let detail = show_json(config, &remote_name, &root_dir)?;
+    if settings.json {
+        return print_json(&detail, settings);
+    }
+
+    if detail["active"] == true {
+        println!("{} {}", remote_name.to_string().bold(), "(active)".green());
+    } else {
+        println!("{}", remote_name.to_string().bold());
+    }
+    println!("  Script ID: {}", detail["id"].as_str().unwrap_or_default());
+    if detail["protected"] == true {
+        println!("  Protected: {}", "yes".red());
+    } else {
+        println!("  Protected: no");
+    }
+    let groups: Vec<&str> = detail["groups"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    if !groups.is_empty() {
+        println!("  Groups: {}", groups.join(", "));
+    }
+    if let Some(description) = detail["description"].as_str() {
+        println!("  Description:");
+        for line in description.lines() {
+            println!("    {}", line);
+        }
+    }
+    if let Some(last_deploy) = detail["last_deploy"].as_object() {
+        println!(
+            "  Last deploy: {} ({})",
+            last_deploy["message"].as_str().unwrap_or_default(),
+            last_deploy["pushed_at"].as_str().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+fn print_json(value: &Value, settings: &Settings) -> Result<(), ClubError> {
+    print!("{}", json_text(value, settings.compact_json)?);
+    Ok(())
+}
+
+/// Renders `--json` output, indented or on one line for `--compact`, and always newline-terminated.
+fn json_text(value: &Value, compact: bool) -> Result<String, ClubError> {
+    let json_str = if compact {
+        serde_json::to_string(value)
+    } else {
+        serde_json::to_string_pretty(value)
+    };
+    json_str
+        .map(|json_str| json_str + "\n")
+        .map_err(|e| ClubError::JsonWriteFail(e.to_string()))
+}
+
+fn status_json(manifest_path: &Path, config: &ClaspConfig) -> Value {
+    serde_json::json!({
+        "manifest_path": manifest_path.display().to_string(),
+        "root_dir": config.root_dir,
+        "remote_count": config.club_remotes.as_ref().map_or(0, IndexMap::len),
+        "active_remote": active_remote(config).map(|name| &name.0),
+        "active_remote_ambiguous": active_remote_is_ambiguous(config),
+        "setup": config.club_remotes.is_some(),
+    })
+}
+
+fn club_status(ctx: &Context, settings: &Settings) -> Result<(), ClubError> {
+    let config = ctx.config()?;
+    if settings.json {
+        return print_json(&status_json(&ctx.manifest_path, config), settings);
+    }
+    let remote_count = config.club_remotes.as_ref().map_or(0, IndexMap::len);
+    let setup = config.club_remotes.is_some();
+    println!("Manifest: {}", ctx.manifest_path.display());
+    println!("Root directory: {}", config.root_dir);
+    if !setup {
+        println!("Club is not set up. Run `club init` to set up club.");
+        return Ok(());
+    }
+    println!("Remotes: {}", remote_count);
+    match active_remote(config) {
+        Some(remote_name) => println!(
+            "Active remote: {}{}",
+            remote_name.to_string().bold(),
+            ambiguity_note(config)
+        ),
+        None => println!("Active remote: none (scriptId {})", config.script_id),
+    }
+    Ok(())
+}
+
+/// A note for human output when other remotes share the active remote's script ID.
+fn ambiguity_note(config: &ClaspConfig) -> String {
+    let matches = remotes_at_script_id(config);
+    if matches.len() < 2 {
+        return String::new();
+    }
+    let names: Vec<String> = matches.iter().map(|r| r.to_string()).collect();
+    format!(" (ambiguous: {} share this script ID)", names.join(", "))
+}
+
+fn current_json(config: &ClaspConfig) -> Value {
+    serde_json::json!({
+        "active_remote": active_remote(config).map(|name| &name.0),
+        "active_remote_ambiguous": active_remote_is_ambiguous(config),
+    })
+}
+
+fn club_current(ctx: &Context, settings: &Settings) -> Result<(), ClubError> {
+    let config = ctx.config()?;
+    if config.club_remotes.is_none() {
+        return Err(ClubError::ClubNotSetup);
+    }
+    if settings.json {
+        return print_json(&current_json(config), settings);
+    }
+    match active_remote(config) {
+        Some(remote_name) => println!("{}{}", remote_name, ambiguity_note(config)),
+        None => println!("Detached: scriptId {} matches no remote.", config.script_id),
+    }
+    Ok(())
+}
+
+fn club_completions(
+    completions_args: CompletionsCommand,
+    settings: &Settings,
+) -> Result<(), ClubError> {
+    let shell = match completions_args.shell {
+        Some(shell) => shell,
+        None => detect_shell(std::env::var_os("SHELL"))?,
+    };
+    if !completions_args.install {
+        clap_complete::generate(
+            shell,
+            &mut ClubArgs::command(),
+            "club",
+            &mut std::io::stdout(),
+        );
+        return Ok(());
+    }
+    let home = std::env::var_os("HOME").ok_or(ClubError::CompletionInstallFail(
+        "$HOME is not set".to_string(),
+    ))?;
+    let path = completions_install_path(shell, Path::new(&home))?;
+    if completions_args.dry_run {
+        println!("Would install {} completions to {}", shell, path.display());
+        return Ok(());
+    }
+    install_completions(shell, &path)?;
+    if !settings.quiet {
+        eprintln!("Installed {} completions to {}", shell, path.display());
+    }
+    Ok(())
+}
+
+/// The shell named by a `$SHELL` path such as `/bin/zsh`.
+fn detect_shell(shell_path: Option<OsString>) -> Result<Shell, ClubError> {
+    shell_path
+        .and_then(Shell::from_shell_path)
+        .ok_or(ClubError::UnknownShell)
+}
+
+fn club_shell_aliases(aliases_args: ShellAliasesCommand) -> Result<(), ClubError> {
+    let shell = match aliases_args.shell {
+        Some(shell) => shell,
+        None => detect_shell(std::env::var_os("SHELL"))?,
+    };
+    print!("{}", shell_aliases(shell)?);
+    Ok(())
+}
+
+/// The `club shell-aliases` snippet for `shell`: a function per `SHELL_ALIASES` entry, each passing
+/// along any further arguments, with a comment saying what it does.
+fn shell_aliases(shell: Shell) -> Result<String, ClubError> {
+    let mut snippet = format!(
+        "# club shell aliases. Load them from your shell's startup file with:\n#   {}\n",
+        match shell {
+            Shell::Bash | Shell::Zsh => format!("eval \"$(club shell-aliases {})\"", shell),
+            Shell::Fish => "club shell-aliases fish | source".to_string(),
+            Shell::PowerShell =>
+                "club shell-aliases powershell | Out-String | Invoke-Expression".to_string(),
+            _ => return Err(ClubError::ShellAliasesUnsupported(shell)),
+        }
+    );
+    for (name, args, description) in SHELL_ALIASES {
+        snippet += &format!("\n# {}: {}\n", name, description);
+        snippet += &match shell {
+            Shell::Fish => format!("function {}\n    club {} $argv\nend\n", name, args),
+            Shell::PowerShell => format!("function {} {{ club {} @args }}\n", name, args),
+            _ => format!("{}() {{ club {} \"$@\"; }}\n", name, args),
+        };
+    }
+    Ok(snippet)
+}
+
+/// Where `completions --install` puts the script for `shell`, under the user's home directory.
+fn completions_install_path(shell: Shell, home: &Path) -> Result<PathBuf, ClubError> {
+    match shell {
+        Shell::Bash => Ok(home.join(".local/share/bash-completion/completions/club")),
+        Shell::Fish => Ok(home.join(".config/fish/completions/club.fish")),
+        Shell::Zsh => Ok(home.join(".zsh/completions/_club")),
+        _ => Err(ClubError::CompletionInstallUnsupported(shell)),
+    }
+}
+
+/// Writes the completion script for `shell` to `path`, creating its directory if needed.
+fn install_completions(shell: Shell, path: &Path) -> Result<(), ClubError> {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut ClubArgs::command(), "club", &mut script);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| ClubError::CompletionInstallFail(e.to_string()))?;
+    }
+    std::fs::write(path, script).map_err(|e| ClubError::CompletionInstallFail(e.to_string()))
+}
+
+fn club_login(login_args: LoginCommand, settings: &Settings) -> Result<(), ClubError> {
+    if login_args.status {
+        return club_whoami(settings);
+    }
+    if run_clasp(settings, Path::new("."), ["login"])?.success() {
+        Ok(())
+    } else {
+        Err(ClubError::ClaspError("clasp login failed".to_string()))
+    }
+}
+
+fn main() {
+    env_logger::init();
+    let args = ClubArgs::parse();
+    let result = get_manifest_path(args.manifest.clone(), args.project.as_deref()).and_then(
+        |manifest_path| {
+            let settings = load_settings(&args, &manifest_path)?;
+            match settings.color {
+                ColorChoice::Always => colored::control::set_override(true),
+                ColorChoice::Never => colored::control::set_override(false),
+                ColorChoice::Auto if !std::io::stdout().is_terminal() => {
+                    colored::control::set_override(false)
+                }
+                ColorChoice::Auto => {}
+            }
+            let ctx = Context::new(manifest_path, settings.validate_ids).with_settings(&settings);
+            run_command(args.command, &ctx, &settings)
+        },
+    );
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run_command(command: ClubCommand, ctx: &Context, settings: &Settings) -> Result<(), ClubError> {
+    if ctx.remotes_env.is_some()
+        && settings.remotes_env_mode == RemotesEnvMode::Override
+        && command.changes_remotes()
+    {
+        return Err(ClubError::RemotesEnvOverride);
+    }
+    if settings.no_write && command.runs_clasp_on_manifest() {
+        return Err(ClubError::NoWriteUnsupported);
+    }
+    let _lock = if settings.lock && !settings.no_write && command.writes_manifest() {
+        Some(ManifestLock::acquire(&ctx.manifest_path, LOCK_TIMEOUT)?)
+    } else {
+        None
+    };
+    match command {
+        ClubCommand::Audit(_) => club_audit(ctx, settings),
+        ClubCommand::DiffConfig(diff_args) => club_diff_config(diff_args, ctx, settings),
+        ClubCommand::Doctor(doctor_args) => club_doctor(doctor_args, ctx, settings),
+        ClubCommand::DumpConfig(dump_args) => club_dump_config(dump_args, ctx, settings),
+        ClubCommand::CheckManifest(_) => club_check_manifest(ctx, settings),
+        ClubCommand::Clasp(clasp_args) => club_clasp(clasp_args, ctx, settings),
+        ClubCommand::Completions(completions_args) => club_completions(completions_args, settings),
+        ClubCommand::Current(_) => club_current(ctx, settings),
+        ClubCommand::Ensure(ensure_args) => club_ensure(ensure_args, ctx, settings),
+        ClubCommand::Env(env_args) => club_env(env_args, ctx, settings),
+        ClubCommand::Examples(examples_args) => club_examples(examples_args),
+        ClubCommand::Export(export_args) => club_export(export_args, ctx, settings),
+        ClubCommand::Gc(gc_args) => club_gc(gc_args, ctx, settings),
+        ClubCommand::Group(group_command) => club_group(group_command, ctx),
+        ClubCommand::Config(ConfigCommand::Migrate(_)) => club_config_migrate(ctx, settings),
+        ClubCommand::Init(init_args) => club_init(init_args, ctx, settings),
+        ClubCommand::Import(import_args) => club_import(import_args, ctx, settings),
+        ClubCommand::List(list_args) => club_list(list_args, ctx, settings),
+        ClubCommand::Log(log_args) => club_log(log_args, ctx, settings),
+        ClubCommand::MvManifest(mv_args) => club_mv_manifest(mv_args, ctx, settings),
+        ClubCommand::NormalizeNames(normalize_args) => {
+            club_normalize_names(normalize_args, ctx, settings)
+        }
+        ClubCommand::Open(open_args) => club_open(open_args, ctx, settings),
+        ClubCommand::Set(set_args) => club_set(set_args, ctx, settings),
+        ClubCommand::ShellAliases(aliases_args) => club_shell_aliases(aliases_args),
+        ClubCommand::Show(show_args) => club_show(show_args, ctx, settings),
+        ClubCommand::Status(_) => club_status(ctx, settings),
+        ClubCommand::Swap(swap_args) => club_swap(swap_args, ctx, settings),
+        ClubCommand::Switch(switch_args) => club_switch(switch_args, ctx, settings),
+        ClubCommand::Touch(touch_args) => club_touch(touch_args, ctx, settings),
+        ClubCommand::ValidateName(validate_args) => club_validate_name(validate_args),
+        ClubCommand::ValidateUrl(validate_args) => club_validate_url(validate_args),
+        ClubCommand::Verify(verify_args) => club_verify(verify_args, ctx, settings),
+        ClubCommand::Version(_) => club_version(settings),
+        ClubCommand::Whoami(_) => club_whoami(settings),
+        ClubCommand::Remove(remove_args) => club_remove(remove_args, ctx),
+        ClubCommand::Rename(rename_args) => club_rename(rename_args, ctx, settings),
+        ClubCommand::Repair(repair_args) => club_repair(repair_args, ctx, settings),
+        ClubCommand::Reassign(reassign_args) => club_reassign(reassign_args, ctx, settings),
+        ClubCommand::Push(push_args) => club_push(push_args, ctx, settings),
+        ClubCommand::Login(login_args) => club_login(login_args, settings),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A valid script ID made of one repeated character.
+    fn script_id(c: char) -> String {
+        c.to_string().repeat(57)
+    }
+
+    /// A scratch directory, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "club-test-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            std::fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A clasp project in a scratch directory, with `src` as its rootDir and main's ID as its
+    /// scriptId.
+    struct Project {
+        dir: TempDir,
+        manifest_path: PathBuf,
+    }
+
+    impl Project {
+        fn new(club: Value) -> Self {
+            Project::with_manifest_name(club, MANIFEST_NAME)
+        }
+
+        /// A project whose manifest is named `manifest_name` instead of `.clasp.json`.
+        fn with_manifest_name(club: Value, manifest_name: &str) -> Self {
+            let dir = TempDir::new();
+            std::fs::create_dir(dir.path().join("src")).unwrap();
+            let manifest_path = dir.path().join(manifest_name);
+            let project = Project { dir, manifest_path };
+            project.write_manifest(serde_json::json!({
+                "scriptId": script_id('A'),
+                "rootDir": "src",
+                "parentId": [],
+                "__club__": club,
+            }));
+            project
+        }
+
+        /// A project with `main` and `staging` remotes.
+        fn with_remotes() -> Self {
+            Project::new(serde_json::json!({
+                "main": script_id('A'),
+                "staging": script_id('B'),
+            }))
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.dir.path().join(name)
+        }
+
+        fn write_manifest(&self, manifest: Value) {
+            std::fs::write(&self.manifest_path, manifest.to_string()).unwrap();
+        }
+
+        fn manifest(&self) -> Value {
+            serde_json::from_str(&self.manifest_text()).unwrap()
+        }
+
+        fn manifest_text(&self) -> String {
+            std::fs::read_to_string(&self.manifest_path).unwrap()
+        }
+
+        /// Writes an executable stand-in for clasp that logs each call to `clasp.log` as its
+        /// arguments and the manifest's scriptId at the time, then runs `body`.
+        #[cfg(unix)]
+        fn fake_clasp(&self, body: &str) -> String {
+            let path = self.path("fake-clasp");
+            let log = self.path("clasp.log");
+            std::fs::write(
+                &path,
+                format!(
+                    r#"#!/bin/sh
+id=$(sed -n 's/.*"scriptId": *"\([^"]*\)".*/\1/p' .clasp.json | head -n 1)
+echo "$*|$id" >> '{}'
+{}
+"#,
+                    log.display(),
+                    body
+                ),
+            )
+            .unwrap();
+            std::fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o755))
+                .unwrap();
+            path.to_string_lossy().into_owned()
+        }
+
+        /// The calls the fake clasp has logged, as `args|scriptId`.
+        #[cfg(unix)]
+        fn clasp_calls(&self) -> Vec<String> {
+            std::fs::read_to_string(self.path("clasp.log"))
+                .unwrap_or_default()
+                .lines()
+                .map(str::to_string)
+                .collect()
+        }
+
+        /// Runs a club command line against the project, as `main` would.
+        fn run(&self, args: &[&str]) -> Result<(), ClubError> {
+            self.run_with_context(args).0
+        }
+
+        /// The settings `args` would run with, keeping recent-ID history out of the real home
+        /// directory and ignoring any `CLUB_REMOTES` in the test's environment.
+        fn load_settings(&self, args: &ClubArgs) -> Result<Settings, ClubError> {
+            Ok(Settings {
+                recent_ids: Some(self.path("recent_ids")),
+                remotes_env: None,
+                ..load_settings(args, &self.manifest_path)?
+            })
+        }
+
+        /// Like `run`, but also returns the command's context so tests can inspect it afterwards.
+        fn run_with_context(&self, args: &[&str]) -> (Result<(), ClubError>, Context) {
+            self.run_with_remotes_env(None, args)
+        }
+
+        /// Like `run_with_context`, with `remotes_env` standing in for `CLUB_REMOTES`.
+        fn run_with_remotes_env(
+            &self,
+            remotes_env: Option<&str>,
+            args: &[&str],
+        ) -> (Result<(), ClubError>, Context) {
+            let manifest = self.manifest_path.to_string_lossy().to_string();
+            let args = ClubArgs::try_parse_from(
+                ["club", "--manifest", manifest.as_str()]
+                    .into_iter()
+                    .chain(args.iter().copied()),
+            )
+            .unwrap_or_else(|e| panic!("{}", e));
+            let settings = match self.load_settings(&args) {
+                Ok(settings) => Settings {
+                    remotes_env: remotes_env.map(str::to_string),
+                    ..settings
+                },
+                Err(err) => return (Err(err), Context::new(self.manifest_path.clone(), true)),
+            };
+            let ctx = Context::new(self.manifest_path.clone(), settings.validate_ids)
+                .with_settings(&settings);
+            (run_command(args.command, &ctx, &settings), ctx)
+        }
+    }
+
+    fn remotes_of(pairs: &[(&str, char)]) -> IndexMap<RemoteName, Remote> {
+        pairs
+            .iter()
+            .map(|(name, c)| {
+                (
+                    RemoteName(name.to_string()),
+                    Remote::from(RemoteId(script_id(*c))),
+                )
+            })
+            .collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn remote_file_keeps_remotes_out_of_the_manifest() {
+        let project = Project::with_remotes();
+        let remote_file = project.path("remotes.json");
+        let remote_file_arg = remote_file.to_string_lossy().to_string();
+        let extra_id = script_id('E');
+        project
+            .run(&["--remote-file", &remote_file_arg, "set", "extra", &extra_id])
+            .unwrap();
+        let remotes: Value =
+            serde_json::from_str(&std::fs::read_to_string(&remote_file).unwrap()).unwrap();
+        assert_eq!(
+            remotes,
+            serde_json::json!({
+                "main": script_id('A'),
+                "staging": script_id('B'),
+                "extra": script_id('E'),
+            })
+        );
+        assert!(project.manifest().get("__club__").is_none());
+
+        let clasp = project.fake_clasp("");
+        project
+            .run(&[
+                "--remote-file",
+                &remote_file_arg,
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "extra",
+            ])
+            .unwrap();
+        assert_eq!(project.clasp_calls(), [format!("push|{}", script_id('E'))]);
+        let manifest = project.manifest();
+        assert_eq!(manifest["scriptId"], script_id('A'));
+        assert!(manifest.get("__club__").is_none());
+    }
+
+    #[test]
+    fn remote_file_setting_is_relative_to_the_project() {
+        let project = Project::with_remotes();
+        std::fs::write(
+            project.path(SETTINGS_NAME),
+            r#"{ "remoteFile": "club-remotes.json" }"#,
+        )
+        .unwrap();
+        project.run(&["remove", "staging"]).unwrap();
+        let remotes: Value = serde_json::from_str(
+            &std::fs::read_to_string(project.path("club-remotes.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(remotes, serde_json::json!({ "main": script_id('A') }));
+        assert!(project.manifest().get("__club__").is_none());
+    }
+
+    #[test]
+    fn remotes_env_merges_over_the_manifest_without_being_written() {
+        let project = Project::with_remotes();
+        let remotes_env =
+            serde_json::json!({ "staging": script_id('C'), "ci": script_id('D') }).to_string();
+
+        let (result, ctx) = project.run_with_remotes_env(Some(&remotes_env), &["list"]);
+        result.unwrap();
+        assert_eq!(
+            ctx.config().unwrap().club_remotes,
+            Some(remotes_of(&[("main", 'A'), ("staging", 'C'), ("ci", 'D')]))
+        );
+
+        let extra_id = script_id('E');
+        let (result, _) =
+            project.run_with_remotes_env(Some(&remotes_env), &["set", "extra", &extra_id]);
+        result.unwrap();
+        assert_eq!(
+            project.manifest()["__club__"],
+            serde_json::json!({
+                "main": script_id('A'),
+                "staging": script_id('B'),
+                "extra": script_id('E'),
+            })
+        );
+
+        // Setting a remote to the environment's own ID still saves it.
+        let ci_id = script_id('D');
+        let (result, _) = project.run_with_remotes_env(Some(&remotes_env), &["set", "ci", &ci_id]);
+        result.unwrap();
+        assert_eq!(project.manifest()["__club__"]["ci"], ci_id);
+        assert_eq!(project.manifest()["__club__"]["staging"], script_id('B'));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn remotes_env_remote_is_not_saved_as_the_active_remote() {
+        let project = Project::with_remotes();
+        let remotes_env = serde_json::json!({ "ci": script_id('D') }).to_string();
+        let clasp = project.fake_clasp("");
+        let (result, _) = project.run_with_remotes_env(
+            Some(&remotes_env),
+            &["--clasp-bin", &clasp, "push", "main", "--restore-to", "ci"],
+        );
+        result.unwrap();
+        let manifest = project.manifest();
+        assert!(manifest["__club__"].get("ci").is_none());
+        assert!(manifest["__club__"].get(ACTIVE_REMOTE_KEY).is_none());
+    }
+
+    #[test]
+    fn remotes_env_override_replaces_the_manifest_remotes_and_refuses_writes() {
+        let project = Project::with_remotes();
+        let remotes_env = serde_json::json!({ "ci": script_id('D') }).to_string();
+        let before = project.manifest_text();
+
+        let (result, ctx) = project.run_with_remotes_env(
+            Some(&remotes_env),
+            &["--remotes-env-mode", "override", "list"],
+        );
+        result.unwrap();
+        assert_eq!(
+            ctx.config().unwrap().club_remotes,
+            Some(remotes_of(&[("ci", 'D')]))
+        );
+
+        let extra_id = script_id('E');
+        let (result, _) = project.run_with_remotes_env(
+            Some(&remotes_env),
+            &["--remotes-env-mode", "override", "set", "extra", &extra_id],
+        );
+        assert!(matches!(result, Err(ClubError::RemotesEnvOverride)));
+        assert_eq!(project.manifest_text(), before);
+
+        let (result, _) = project.run_with_remotes_env(
+            Some(&remotes_env),
+            &["--remotes-env-mode", "override", "switch", "ci"],
+        );
+        result.unwrap();
+        let manifest = project.manifest();
+        assert_eq!(manifest["scriptId"], script_id('D'));
+        assert_eq!(manifest["__club__"]["main"], script_id('A'));
+        assert!(manifest["__club__"].get("ci").is_none());
+    }
+
+    #[test]
+    fn remotes_env_rejects_club_settings() {
+        let project = Project::with_remotes();
+        let remotes_env = serde_json::json!({ "activeRemote": "main" }).to_string();
+        let (result, _) = project.run_with_remotes_env(Some(&remotes_env), &["list"]);
+        assert!(matches!(result, Err(ClubError::RemotesEnvInvalid(_))));
+    }
+
+    #[test]
+    fn import_plan_for_an_overwriting_merge_adds_and_updates() {
+        let existing = remotes_of(&[("main", 'A'), ("staging", 'B')]);
+        let incoming = vec![
+            ("main".to_string(), Value::String(script_id('A'))),
+            ("staging".to_string(), Value::String(script_id('C'))),
+            ("qa".to_string(), Value::String(script_id('D'))),
+        ];
+        let plan = ImportPlan::new(&existing, incoming, false, true, true);
+        assert_eq!(
+            plan.added,
+            vec![(
+                RemoteName("qa".to_string()),
+                Remote::from(RemoteId(script_id('D')))
+            )]
+        );
+        assert_eq!(
+            plan.updated,
+            vec![(
+                RemoteName("staging".to_string()),
+                RemoteId(script_id('B')),
+                Remote::from(RemoteId(script_id('C')))
+            )]
+        );
+        assert_eq!(plan.unchanged, vec![RemoteName("main".to_string())]);
+        assert!(plan.removed.is_empty() && plan.invalid.is_empty());
+    }
+
+    #[test]
+    fn import_plan_reports_invalid_entries_and_replaced_removals() {
+        let existing = remotes_of(&[("main", 'A'), ("staging", 'B')]);
+        let incoming = vec![
+            ("main".to_string(), Value::String(script_id('A'))),
+            ("bad".to_string(), Value::String("nope".to_string())),
+        ];
+        let plan = ImportPlan::new(&existing, incoming, true, false, true);
+        assert_eq!(plan.invalid.len(), 1);
+        assert_eq!(plan.invalid[0].0, "bad");
+        assert_eq!(plan.removed, vec![RemoteName("staging".to_string())]);
+    }
+
+    fn clean_git(_: &Path, _: &[&str]) -> Option<String> {
+        Some(String::new())
+    }
+
+    fn dirty_git(_: &Path, _: &[&str]) -> Option<String> {
+        Some(" M src/Code.js\n".to_string())
+    }
+
+    fn no_git(_: &Path, _: &[&str]) -> Option<String> {
+        None
+    }
+
+    fn history_git(_: &Path, args: &[&str]) -> Option<String> {
+        let manifest = |id: &str| format!(r#"{{"scriptId": "{}", "rootDir": "src"}}"#, id);
+        match args {
+            ["log", ..] => Some("a1\nb2\nc3\nd4\ne5\n".to_string()),
+            ["show", "a1:./.clasp.json"] => Some(manifest(&script_id('A'))),
+            ["show", "b2:./.clasp.json"] => Some(manifest(&script_id('A'))),
+            ["show", "c3:./.clasp.json"] => Some(manifest("not-an-id")),
+            ["show", "d4:./.clasp.json"] => Some(manifest(&script_id('B'))),
+            ["show", "e5:./.clasp.json"] => Some(manifest(&script_id('C'))),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn detect_remotes_adds_each_distinct_historical_id() {
+        let existing = remotes_of(&[("main", 'C')]);
+        let scan = detect_historical_remotes(
+            Path::new("/project/.clasp.json"),
+            &existing,
+            true,
+            history_git,
+        )
+        .unwrap();
+        let found: Vec<(&str, &str, RemoteId)> = scan
+            .found
+            .iter()
+            .map(|(commit, name, id)| (commit.as_str(), name.0.as_str(), id.clone()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("a1", "historical-1", RemoteId(script_id('A'))),
+                ("d4", "historical-2", RemoteId(script_id('B'))),
+            ]
+        );
+        assert_eq!(scan.skipped.len(), 1);
+        assert_eq!(scan.skipped[0].0, "c3");
+
+        assert!(matches!(
+            detect_historical_remotes(Path::new("/project/.clasp.json"), &existing, true, no_git),
+            Err(ClubError::NoGitHistory(_))
+        ));
+    }
+
+    fn clean_git_config() -> ClaspConfig {
+        ClaspConfig {
+            root_dir: "src".to_string(),
+            script_id: script_id('A'),
+            parent_ids: vec![],
+            club_remotes: Some(remotes_of(&[("main", 'A'), ("staging", 'B')])),
+            club_groups: IndexMap::new(),
+            club_settings: ClubSettings {
+                require_clean_git: true,
+                ..ClubSettings::default()
+            },
+            env_remotes: None,
+            remote_file: None,
+            push_root_dir: None,
+        }
+    }
+
+    #[test]
+    fn require_clean_git_allows_a_clean_tree() {
+        let manifest_path = Path::new("project/.clasp.json");
+        let staging = PushSelection::Remote(RemoteName("staging".to_string()));
+        assert_eq!(
+            git_tree_is_dirty(manifest_path, &[], clean_git),
+            Some(false)
+        );
+        assert!(ensure_clean_git(
+            &clean_git_config(),
+            &staging,
+            manifest_path,
+            &Settings::default(),
+            clean_git
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn require_clean_git_refuses_a_dirty_tree() {
+        let manifest_path = Path::new("project/.clasp.json");
+        let staging = PushSelection::Remote(RemoteName("staging".to_string()));
+        assert!(matches!(
+            ensure_clean_git(
+                &clean_git_config(),
+                &staging,
+                manifest_path,
+                &Settings::default(),
+                dirty_git
+            ),
+            Err(ClubError::DirtyWorkingTree)
+        ));
+        let main = PushSelection::Remote(RemoteName("main".to_string()));
+        assert!(ensure_clean_git(
+            &clean_git_config(),
+            &main,
+            manifest_path,
+            &Settings::default(),
+            dirty_git
+        )
+        .is_ok());
+        let mut config = clean_git_config();
+        config.club_settings.require_clean_git = false;
+        assert!(ensure_clean_git(
+            &config,
+            &staging,
+            manifest_path,
+            &Settings::default(),
+            dirty_git
+        )
+        .is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn require_clean_git_ignores_clubs_own_files_in_a_real_repo() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "staging": script_id('B'),
+            "requireCleanGit": true,
+        }));
+        let mut manifest = project.manifest();
+        manifest["rootDir"] = Value::String(String::new());
+        project.write_manifest(manifest);
+        let clasp = project.fake_clasp("");
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args(["-c", "user.name=club", "-c", "user.email=club@example.com"])
+                .args(args)
+                .current_dir(project.dir.path())
+                .status()
+                .is_ok_and(|status| status.success())
+        };
+        if !git(&["init", "-q"]) {
+            // No git to test against.
+            return;
+        }
+        // Club writes the manifest in its own layout, which a push restores it to.
+        project.run(&["set", "staging", &script_id('B')]).unwrap();
+        std::fs::write(project.path(".gitignore"), "fake-clasp\nclasp.log\n").unwrap();
+        std::fs::write(project.path("Code.js"), "function main() {}\n").unwrap();
+        assert!(git(&["add", "-A"]) && git(&["commit", "-qm", "Initial commit"]));
+
+        let push = || {
+            project.run(&[
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "staging",
+                "--message",
+                "Ship",
+            ])
+        };
+        push().unwrap();
+        // The lock, deploy marker, and other files club wrote don't make the tree dirty.
+        push().unwrap();
+        std::fs::write(project.path("Code.js"), "function main() { return 1; }\n").unwrap();
+        assert!(matches!(push(), Err(ClubError::DirtyWorkingTree)));
+    }
+
+    #[test]
+    fn require_clean_git_skips_a_tree_outside_git() {
+        let manifest_path = Path::new("project/.clasp.json");
+        let all = PushSelection::All(PushFilters::default());
+        assert_eq!(git_tree_is_dirty(manifest_path, &[], no_git), None);
+        assert!(ensure_clean_git(
+            &clean_git_config(),
+            &all,
+            manifest_path,
+            &Settings::default(),
+            no_git
+        )
+        .is_ok());
+    }
+
+    fn on_feature_branch(_: &Path, args: &[&str]) -> Option<String> {
+        assert_eq!(args, ["rev-parse", "--abbrev-ref", "HEAD"]);
+        Some("feature/login\n".to_string())
+    }
+
+    fn on_detached_head(_: &Path, _: &[&str]) -> Option<String> {
+        Some("HEAD\n".to_string())
+    }
+
+    #[test]
+    fn remote_from_branch_finds_the_remote_named_after_the_branch() {
+        let project_dir = Path::new("project");
+        let remotes = remotes_of(&[("main", 'A'), ("feature-login", 'B')]);
+        assert_eq!(
+            branch_remote(project_dir, &IndexMap::new(), &remotes, on_feature_branch).unwrap(),
+            RemoteName("feature-login".to_string())
+        );
+        let remotes = remotes_of(&[("main", 'A'), ("staging", 'B')]);
+        let branch_remote_map = IndexMap::from([(
+            "feature/login".to_string(),
+            RemoteName("staging".to_string()),
+        )]);
+        assert_eq!(
+            branch_remote(project_dir, &branch_remote_map, &remotes, on_feature_branch).unwrap(),
+            RemoteName("staging".to_string())
+        );
+    }
+
+    #[test]
+    fn remote_from_branch_fails_without_a_matching_remote_or_branch() {
+        let project_dir = Path::new("project");
+        let remotes = remotes_of(&[("main", 'A'), ("staging", 'B')]);
+        assert!(matches!(
+            branch_remote(project_dir, &IndexMap::new(), &remotes, on_feature_branch),
+            Err(ClubError::NoRemoteForBranch(branch, RemoteName(remote)))
+                if branch == "feature/login" && remote == "feature-login"
+        ));
+        assert!(matches!(
+            branch_remote(project_dir, &IndexMap::new(), &remotes, no_git),
+            Err(ClubError::NoGitBranch(_))
+        ));
+        assert!(matches!(
+            branch_remote(project_dir, &IndexMap::new(), &remotes, on_detached_head),
+            Err(ClubError::NoGitBranch(_))
+        ));
+    }
+
+    #[test]
+    fn require_clean_git_exempts_the_configured_default_remote() {
+        let manifest_path = Path::new("project/.clasp.json");
+        let settings = Settings {
+            default_remote: RemoteName("staging".to_string()),
+            ..Settings::default()
+        };
+        let config = clean_git_config();
+        let staging = PushSelection::Remote(RemoteName("staging".to_string()));
+        assert!(ensure_clean_git(&config, &staging, manifest_path, &settings, dirty_git).is_ok());
+        let main = PushSelection::Remote(RemoteName("main".to_string()));
+        assert!(matches!(
+            ensure_clean_git(&config, &main, manifest_path, &settings, dirty_git),
+            Err(ClubError::DirtyWorkingTree)
+        ));
+    }
+
+    #[test]
+    fn normalize_names_lowercases_remotes_groups_and_the_active_remote() {
+        let project = Project::new(serde_json::json!({
+            "Prod": { "id": script_id('A'), "protected": true },
+            "Staging": script_id('B'),
+            "qa": script_id('C'),
+            "__groups__": { "release": ["Prod", "qa"] },
+            "activeRemote": "Staging",
+        }));
+        project.run(&["normalize-names", "--lower"]).unwrap();
+        assert_eq!(
+            project.manifest()["__club__"],
+            serde_json::json!({
+                "prod": { "id": script_id('A'), "protected": true },
+                "staging": script_id('B'),
+                "qa": script_id('C'),
+                "__groups__": { "release": ["prod", "qa"] },
+                "activeRemote": "staging",
+            })
+        );
+    }
+
+    #[test]
+    fn normalize_names_aborts_on_a_collision() {
+        let project = Project::new(serde_json::json!({
+            "Prod": script_id('A'),
+            "prod": script_id('B'),
+            "Staging": script_id('C'),
+        }));
+        let before = project.manifest_text();
+        match project.run(&["normalize-names", "--lower"]) {
+            Err(ClubError::NameCollision(collisions)) => assert_eq!(
+                collisions,
+                [(
+                    vec![
+                        RemoteName("Prod".to_string()),
+                        RemoteName("prod".to_string())
+                    ],
+                    RemoteName("prod".to_string())
+                )]
+            ),
+            other => panic!("expected a name collision, got {:?}", other),
+        }
+        assert_eq!(project.manifest_text(), before);
+    }
+
+    #[test]
+    fn redact_id_keeps_only_the_last_four_characters() {
+        assert_eq!(redact_id("abcdefgh"), "****efgh");
+        assert_eq!(redact_id(&script_id('A')), "*".repeat(53) + "AAAA");
+        assert_eq!(redact_id("abcd"), "****");
+        assert_eq!(redact_id(""), "");
+    }
+
+    #[test]
+    fn dump_config_includes_the_manifest_path_and_redacts_ids() {
+        let project = Project::with_remotes();
+        let args = ClubArgs::try_parse_from(["club", "--dump-config"]).unwrap();
+        let settings = project.load_settings(&args).unwrap();
+        let ctx = Context::new(project.manifest_path.clone(), true);
+
+        let dump = dump_config_json(&ctx, &settings, true, Some("2.4.2".to_string()));
+        assert_eq!(
+            dump["manifestPath"],
+            project.manifest_path.display().to_string()
+        );
+        assert_eq!(dump["manifest"]["scriptId"], redact_id(&script_id('A')));
+        assert_eq!(
+            dump["manifest"]["remotes"]["staging"]["id"],
+            redact_id(&script_id('B'))
+        );
+        assert_eq!(dump["clasp"]["version"], "2.4.2");
+
+        let dump = dump_config_json(&ctx, &settings, false, None);
+        assert_eq!(dump["manifest"]["scriptId"], script_id('A'));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn version_reports_the_version_clasp_prints() {
+        let project = Project::with_remotes();
+        let dir = &project.dir;
+        let settings = |clasp_bin: String| Settings {
+            clasp_bin,
+            ..Settings::default()
+        };
+        let clasp = settings(project.fake_clasp("echo 2.4.2"));
+
+        let version = version_json(&clasp, Some(dir.path()));
+        assert_eq!(version["club"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(version["clasp"]["version"], "2.4.2");
+        assert_eq!(version["loggedIn"], false);
+
+        std::fs::write(dir.path().join(CLASPRC_NAME), "{}").unwrap();
+        assert_eq!(version_json(&clasp, Some(dir.path()))["loggedIn"], true);
+        let missing = settings(project.path("no-such-clasp").to_string_lossy().into_owned());
+        let version = version_json(&missing, Some(dir.path()));
+        assert_eq!(version["clasp"]["version"], Value::Null);
+        assert_eq!(version["loggedIn"], Value::Null);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn login_status_finds_the_account_clasp_reports() {
+        assert_eq!(
+            parse_login_status("You are logged in as dev@example.com.\n"),
+            Some(LoginStatus::LoggedIn(Some("dev@example.com".to_string())))
+        );
+        assert_eq!(
+            parse_login_status("You are logged in as an unknown user."),
+            Some(LoginStatus::LoggedIn(None))
+        );
+        assert_eq!(
+            parse_login_status("You are not logged in."),
+            Some(LoginStatus::LoggedOut)
+        );
+        assert_eq!(parse_login_status(""), None);
+
+        let project = Project::with_remotes();
+        let home = TempDir::new();
+        let settings = |clasp: String| Settings {
+            clasp_bin: clasp,
+            ..Settings::default()
+        };
+        let clasp = project.fake_clasp("echo 'You are logged in as dev@example.com.'");
+        std::fs::write(
+            home.path().join(CLASPRC_NAME),
+            r#"{"token": {"access_token": "a", "refresh_token": "r"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            login_status_json(&settings(clasp), Some(home.path())).unwrap(),
+            serde_json::json!({
+                "loggedIn": true,
+                "account": "dev@example.com",
+                "credentialsValid": true,
+            })
+        );
+        assert_eq!(project.clasp_calls(), ["login --status|"]);
+
+        let clasp = project.fake_clasp("echo 'You are not logged in.'; exit 1");
+        assert!(matches!(
+            login_status_json(&settings(clasp.clone()), Some(home.path())),
+            Err(ClubError::NotLoggedIn)
+        ));
+        assert!(matches!(
+            project.run(&["--clasp-bin", &clasp, "login", "--status"]),
+            Err(ClubError::NotLoggedIn)
+        ));
+    }
+
+    #[test]
+    fn ensure_leaves_a_matching_remote_unwritten() {
+        let project = Project::with_remotes();
+        let before = project.manifest_text();
+        project
+            .run(&["ensure", "staging", &script_id('B')])
+            .unwrap();
+        project
+            .run(&["ensure", "staging", &script_id('B'), "--check"])
+            .unwrap();
+        assert_eq!(project.manifest_text(), before);
+    }
+
+    #[test]
+    fn ensure_updates_or_creates_a_differing_remote() {
+        let project = Project::with_remotes();
+        project
+            .run(&["ensure", "staging", &script_id('C')])
+            .unwrap();
+        project.run(&["ensure", "qa", &script_id('D')]).unwrap();
+        let club = &project.manifest()["__club__"];
+        assert_eq!(club["staging"], script_id('C'));
+        assert_eq!(club["qa"], script_id('D'));
+        assert!(matches!(
+            project.run(&["ensure", "qa", "not-an-id"]),
+            Err(ClubError::InvalidRemoteId)
+        ));
+    }
+
+    #[test]
+    fn ensure_check_reports_drift_without_writing() {
+        let project = Project::with_remotes();
+        let before = project.manifest_text();
+        match project.run(&["ensure", "staging", &script_id('C'), "--check"]) {
+            Err(ClubError::RemoteDrift(name, current, expected)) => {
+                assert_eq!(name.0, "staging");
+                assert_eq!(current, Some(RemoteId(script_id('B'))));
+                assert_eq!(expected, RemoteId(script_id('C')));
+            }
+            other => panic!("expected drift, got {:?}", other),
+        }
+        assert!(matches!(
+            project.run(&["ensure", "qa", &script_id('D'), "--check"]),
+            Err(ClubError::RemoteDrift(_, None, _))
+        ));
+        assert_eq!(project.manifest_text(), before);
+    }
+
+    #[test]
+    fn rename_swap_exchanges_two_remotes_names() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "blue": {"id": script_id('B'), "protected": true},
+            "green": script_id('C'),
+            "__groups__": {"live": ["blue"]},
+            "activeRemote": "blue",
+        }));
+        project.write_manifest(serde_json::json!({
+            "scriptId": script_id('B'),
+            "rootDir": "src",
+            "parentId": [],
+            "__club__": project.manifest()["__club__"],
+        }));
+        project
+            .run(&["rename", "blue", "green", "--swap", "--dry-run"])
+            .unwrap();
+        assert_eq!(project.manifest()["__club__"]["green"], script_id('C'));
+
+        project.run(&["rename", "blue", "green", "--swap"]).unwrap();
+        let manifest = project.manifest();
+        let club = &manifest["__club__"];
+        assert_eq!(club["blue"], script_id('C'));
+        assert_eq!(club["green"]["id"], script_id('B'));
+        assert_eq!(club["green"]["protected"], true);
+        assert_eq!(club["__groups__"]["live"], serde_json::json!(["green"]));
+        assert_eq!(club["activeRemote"], "green");
+        assert_eq!(manifest["scriptId"], script_id('B'));
+    }
+
+    #[test]
+    fn rename_onto_an_existing_name_without_swap_fails() {
+        let project = Project::with_remotes();
+        let before = project.manifest_text();
+        assert!(matches!(
+            project.run(&["rename", "staging", "main"]),
+            Err(ClubError::RemoteAlreadyExists)
+        ));
+        assert_eq!(project.manifest_text(), before);
+        // --swap on a free name is a plain rename.
+        project.run(&["rename", "staging", "qa", "--swap"]).unwrap();
+        assert_eq!(project.manifest()["__club__"]["qa"], script_id('B'));
+        assert!(project.manifest()["__club__"].get("staging").is_none());
+    }
+
+    #[test]
+    fn case_only_rename_succeeds_with_a_note() {
+        let project = Project::with_remotes();
+        project.run(&["rename", "staging", "Staging"]).unwrap();
+        let club = &project.manifest()["__club__"];
+        assert_eq!(club["Staging"], script_id('B'));
+        assert!(club.get("staging").is_none());
+
+        let staging = RemoteName("staging".to_string());
+        let note = case_only_rename_note(&staging, &RemoteName("Staging".to_string()));
+        assert!(note.unwrap().contains("differ only in case"));
+        assert_eq!(
+            case_only_rename_note(&staging, &RemoteName("qa".to_string())),
+            None
+        );
+    }
+
+    #[test]
+    fn rename_dry_run_reports_without_writing() {
+        assert_eq!(
+            describe_rename(
+                &RemoteName("staging".to_string()),
+                &RemoteName("qa".to_string())
+            ),
+            "Would rename staging -> qa"
+        );
+    }
+
+    #[test]
+    fn remove_dry_run_reports_without_writing() {
+        assert_eq!(
+            describe_removal(&RemoteName("staging".to_string())),
+            "Would remove staging"
+        );
+    }
+
+    #[test]
+    fn check_manifest_succeeds_only_for_a_parseable_manifest() {
+        let project = Project::with_remotes();
+        assert!(project.run(&["check-manifest", "--quiet"]).is_ok());
+        std::fs::write(project.path(MANIFEST_NAME), "{ \"scriptId\": ").unwrap();
+        assert!(project.run(&["check-manifest", "--quiet"]).is_err());
+    }
+
+    #[test]
+    fn check_manifest_still_accepts_its_own_quiet_flag() {
+        // Editor integrations pass `--quiet` after the subcommand, from before it became global.
+        let project = Project::with_remotes();
+        for flag in ["--quiet", "-q"] {
+            let args = ClubArgs::try_parse_from(["club", "check-manifest", flag]).unwrap();
+            assert!(project.load_settings(&args).unwrap().quiet);
+        }
+    }
+
+    fn target_names(targets: Vec<(RemoteName, Remote)>) -> Vec<String> {
+        targets.into_iter().map(|(name, _)| name.0).collect()
+    }
+
+    #[test]
+    fn group_add_creates_the_group() {
+        let project = Project::with_remotes();
+        project
+            .run(&["group", "add", "qa", "staging", "main"])
+            .unwrap();
+        let groups = &project.manifest()["__club__"][GROUPS_KEY];
+        assert_eq!(groups["qa"], serde_json::json!(["staging", "main"]));
+        assert!(matches!(
+            project.run(&["group", "add", "qa", "ghost"]),
+            Err(ClubError::RemoteNotFound)
+        ));
+    }
+
+    #[test]
+    fn group_add_checks_new_group_names_like_new_remote_names() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "__groups__": { "old.qa": [] },
+        }));
+        assert!(matches!(
+            project.run(&["group", "add", "new.qa", "main"]),
+            Err(ClubError::InvalidRemoteName)
+        ));
+        project.run(&["group", "add", "old.qa", "main"]).unwrap();
+        assert_eq!(
+            project.manifest()["__club__"][GROUPS_KEY],
+            serde_json::json!({ "old.qa": ["main"] })
+        );
+    }
+
+    #[test]
+    fn group_push_selects_the_group_members() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "staging": script_id('B'),
+            "qa": script_id('C'),
+            "__groups__": { "test": ["qa", "staging"] },
+        }));
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        let selection = PushSelection::Group("test".to_string(), PushFilters::default());
+        assert_eq!(
+            target_names(selection.targets(&config).unwrap()),
+            ["qa", "staging"]
+        );
+    }
+
+    #[test]
+    fn group_push_rejects_a_dangling_member() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "__groups__": { "test": ["main", "ghost"] },
+        }));
+        assert!(matches!(
+            project.run(&["push", "--group", "test"]),
+            Err(ClubError::DanglingGroupMember(group, member)) if group == "test" && member.0 == "ghost"
+        ));
+    }
+
+    fn push_command(args: &[&str]) -> PushCommand {
+        let args = ClubArgs::try_parse_from(["club", "push"].iter().chain(args))
+            .unwrap_or_else(|e| panic!("{}", e));
+        match args.command {
+            ClubCommand::Push(push_args) => push_args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn concurrency_safe_pushes_take_the_lock_only_to_register() {
+        let id = script_id('C');
+        let writes = |args: &[&str]| ClubCommand::Push(push_command(args)).writes_manifest();
+        assert!(!writes(&["--all", "--concurrency-safe"]));
+        assert!(writes(&[
+            "--scriptid",
+            &id,
+            "--and-register",
+            "qa",
+            "--concurrency-safe"
+        ]));
+        let runs_clasp = ClubCommand::Push(push_command(&[
+            "--scriptid",
+            &id,
+            "--and-register",
+            "qa",
+            "--concurrency-safe",
+        ]))
+        .runs_clasp_on_manifest();
+        assert!(!runs_clasp);
+    }
+
+    fn push_selection(args: &[&str]) -> Result<PushSelection, ClubError> {
+        PushSelection::try_from(&push_command(args))
+    }
+
+    #[test]
+    fn push_selection_accepts_each_single_selection() {
+        assert_eq!(
+            push_selection(&["staging"]).unwrap(),
+            PushSelection::Remote(RemoteName("staging".to_string()))
+        );
+        assert_eq!(
+            push_selection(&["--all", "--exclude", "qa*"]).unwrap(),
+            PushSelection::All(PushFilters {
+                from: None,
+                exclude: vec!["qa*".to_string()],
+            })
+        );
+        assert_eq!(
+            push_selection(&["--group", "qa"]).unwrap(),
+            PushSelection::Group("qa".to_string(), PushFilters::default())
+        );
+        assert!(matches!(
+            push_selection(&[]),
+            Err(ClubError::RemoteRequired)
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_remote_regex_selects_the_whole_names_it_matches() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "qa-1": script_id('B'),
+            "qa-2": script_id('C'),
+            "qa-20": script_id('D'),
+            "staging-qa-3": script_id('E'),
+        }));
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        let selection = push_selection(&["--remote-regex", r"qa-\d"]).unwrap();
+        assert_eq!(
+            target_names(selection.targets(&config).unwrap()),
+            ["qa-1", "qa-2"]
+        );
+        let selection = push_selection(&[
+            "--remote-regex",
+            r"qa-\d+",
+            "--from",
+            "qa-2",
+            "--exclude",
+            "*0",
+        ])
+        .unwrap();
+        assert_eq!(target_names(selection.targets(&config).unwrap()), ["qa-2"]);
+
+        let clasp = project.fake_clasp("");
+        project
+            .run(&["--clasp-bin", &clasp, "push", "--remote-regex", "qa-[12]"])
+            .unwrap();
+        assert_eq!(
+            project.clasp_calls(),
+            [
+                format!("push|{}", script_id('B')),
+                format!("push|{}", script_id('C'))
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_remote_regex_fails_when_nothing_matches_unless_allowed() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        assert!(matches!(
+            project.run(&["--clasp-bin", &clasp, "push", "--remote-regex", "prod.*"]),
+            Err(ClubError::NoRemotesMatch(pattern)) if pattern == "prod.*"
+        ));
+        project
+            .run(&[
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "--remote-regex",
+                "prod.*",
+                "--allow-empty",
+            ])
+            .unwrap();
+        assert!(project.clasp_calls().is_empty());
+        assert!(matches!(
+            push_selection(&["--remote-regex", "qa("]),
+            Err(ClubError::InvalidRemoteRegex(..))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_dry_run_lists_the_filtered_targets_without_pushing() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "staging": script_id('B'),
+            "qa": script_id('C'),
+            "qa-2": script_id('D'),
+        }));
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        let selection = push_selection(&["--all", "--exclude", "qa*"]).unwrap();
+        let escapes = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+        let lines: Vec<String> = push_plan(&selection, &config)
+            .unwrap()
+            .iter()
+            .map(|line| escapes.replace_all(line, "").into_owned())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                format!("  NAME     SCRIPT ID{}  PROTECTED", " ".repeat(48)),
+                format!("* main     {}", script_id('A')),
+                format!("  staging  {}", script_id('B')),
+            ]
+        );
+
+        let before = project.manifest_text();
+        let clasp = project.fake_clasp("");
+        project
+            .run(&[
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "--all",
+                "--dry-run",
+                "--exclude",
+                "qa*",
+            ])
+            .unwrap();
+        assert!(project.clasp_calls().is_empty());
+        assert_eq!(project.manifest_text(), before);
+    }
+
+    #[test]
+    fn push_selection_rejects_remote_with_all() {
+        assert!(matches!(
+            push_selection(&["staging", "--all"]),
+            Err(ClubError::BothRemoteAndAllPassed)
+        ));
+    }
+
+    #[test]
+    fn push_selection_rejects_remote_with_group() {
+        assert!(matches!(
+            push_selection(&["staging", "--group", "qa"]),
+            Err(ClubError::BothRemoteAndGroupPassed)
+        ));
+    }
+
+    #[test]
+    fn push_selection_rejects_all_with_group() {
+        assert!(matches!(
+            push_selection(&["--all", "--group", "qa"]),
+            Err(ClubError::BothAllAndGroupPassed)
+        ));
+    }
+
+    #[test]
+    fn push_selection_rejects_filters_on_a_single_remote() {
+        assert!(matches!(
+            push_selection(&["staging", "--from", "qa"]),
+            Err(ClubError::FilterWithoutMultiPush("--from"))
+        ));
+        assert!(matches!(
+            push_selection(&["staging", "--exclude", "qa"]),
+            Err(ClubError::FilterWithoutMultiPush("--exclude"))
+        ));
+    }
+
+    fn protected_project() -> Project {
+        Project::new(serde_json::json!({
+            "main": { "id": script_id('A'), "protected": true },
+            "staging": script_id('B'),
+        }))
+    }
+
+    #[test]
+    fn protected_remote_blocks_an_unconfirmed_push_all() {
+        let project = protected_project();
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        let targets = PushSelection::All(PushFilters::default())
+            .targets(&config)
+            .unwrap();
+        assert!(matches!(
+            confirm_protected(&targets, |_| false),
+            Err(ClubError::ProtectedRemoteNotConfirmed(names)) if names == [RemoteName("main".to_string())]
+        ));
+        assert!(confirm_protected(&targets, |_| true).is_ok());
+        let staging = PushSelection::Remote(RemoteName("staging".to_string()));
+        assert!(confirm_protected(&staging.targets(&config).unwrap(), |_| false).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn force_reaches_clasp_but_not_the_protected_remote_check() {
+        let project = protected_project();
+        let clasp = project.fake_clasp("");
+        project
+            .run(&["--clasp-bin", &clasp, "push", "staging", "--force"])
+            .unwrap();
+        assert_eq!(
+            project.clasp_calls(),
+            [format!("push --force|{}", script_id('B'))]
+        );
+        assert!(matches!(
+            project.run(&["--clasp-bin", &clasp, "push", "main", "--force"]),
+            Err(ClubError::ProtectedRemoteNotConfirmed(_))
+        ));
+        assert_eq!(project.clasp_calls().len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn protected_remote_is_pushed_with_confirm_prod() {
+        let project = protected_project();
+        let clasp = project.fake_clasp("");
+        project
+            .run(&["--clasp-bin", &clasp, "push", "--all", "--confirm-prod"])
+            .unwrap();
+        assert_eq!(
+            project.clasp_calls(),
+            [
+                format!("push|{}", script_id('A')),
+                format!("push|{}", script_id('B'))
+            ]
+        );
+    }
+
+    #[test]
+    fn push_announcement_counts_remotes() {
+        let names = ["main", "staging", "qa"].map(|name| RemoteName(name.to_string()));
+        let lines: Vec<String> = names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| push_announcement(name, index, names.len(), true))
+            .collect();
+        assert!(lines[1].contains("[2/3]") && lines[1].contains("staging"));
+        assert_eq!(
+            push_announcement(&names[1], 1, 3, false),
+            "Pushing to staging"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn clasp_passthrough_forwards_args_and_restores_the_manifest() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        project
+            .run(&[
+                "--clasp-bin",
+                &clasp,
+                "clasp",
+                "staging",
+                "--",
+                "logs",
+                "--watch",
+            ])
+            .unwrap();
+        assert_eq!(
+            project.clasp_calls(),
+            [format!("logs --watch|{}", script_id('B'))]
+        );
+        assert_eq!(project.manifest()["scriptId"], script_id('A'));
+    }
+
+    /// `clasp deployments` output for a script with a HEAD deployment and two versions.
+    const DEPLOYMENTS_LISTING: &str = "3 Deployments.
+- AKfycbHEAD @HEAD 
+- AKfycbVersion3 @3 - Spring release
+- AKfycbVersion12 @12 - Summer release
+";
+
+    #[test]
+    fn web_app_url_picks_the_latest_versioned_deployment() {
+        assert_eq!(
+            web_app_url(DEPLOYMENTS_LISTING).as_deref(),
+            Some("https://script.google.com/macros/s/AKfycbVersion12/exec")
+        );
+        assert_eq!(web_app_url("1 Deployment.\n- AKfycbHEAD @HEAD \n"), None);
+        assert_eq!(web_app_url("No deployments.\n"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn open_deployment_asks_clasp_for_the_remote_and_restores_the_manifest() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp(&format!("printf '{}'", DEPLOYMENTS_LISTING));
+        project
+            .run(&[
+                "--clasp-bin",
+                &clasp,
+                "open",
+                "staging",
+                "--deployment",
+                "--print",
+            ])
+            .unwrap();
+        assert_eq!(
+            project.clasp_calls(),
+            [format!("deployments|{}", script_id('B'))]
+        );
+        assert_eq!(project.manifest()["scriptId"], script_id('A'));
+
+        let clasp = project.fake_clasp("echo 'No deployments.'");
+        match project.run(&[
+            "--clasp-bin",
+            &clasp,
+            "open",
+            "main",
+            "--deployment",
+            "--print",
+        ]) {
+            Err(ClubError::NoWebAppDeployment(remote)) => assert_eq!(remote.0, "main"),
+            other => panic!("expected no deployment, got {:?}", other),
+        }
+        assert_eq!(project.manifest()["scriptId"], script_id('A'));
+    }
+
+    /// Splits one CSV row into fields, undoing `csv_escape`'s quoting.
+    fn parse_csv_row(row: &str) -> Vec<String> {
+        let mut fields = vec![String::new()];
+        let mut quoted = false;
+        let mut chars = row.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    fields.last_mut().unwrap().push('"');
+                }
+                '"' => quoted = !quoted,
+                ',' if !quoted => fields.push(String::new()),
+                c => fields.last_mut().unwrap().push(c),
+            }
+        }
+        fields
+    }
+
+    #[test]
+    fn list_csv_round_trips_field_values() {
+        let remotes = remotes_of(&[("main", 'A'), ("staging", 'B')]);
+        let csv = remotes_csv(&remotes, true);
+        let rows: Vec<Vec<String>> = csv.lines().map(parse_csv_row).collect();
+        assert_eq!(rows[0], ["name", "id", "is_main"]);
+        assert_eq!(
+            rows[1],
+            ["main".to_string(), script_id('A'), "true".to_string()]
+        );
+        assert_eq!(
+            rows[2],
+            ["staging".to_string(), script_id('B'), "false".to_string()]
+        );
+        assert_eq!(remotes_csv(&remotes, false).lines().count(), 2);
+    }
+
+    #[test]
+    fn csv_escape_quotes_delimiters_and_quotes() {
+        let field = "a,b \"c\"";
+        assert_eq!(csv_escape(field), "\"a,b \"\"c\"\"\"");
+        assert_eq!(parse_csv_row(&csv_escape(field)), [field]);
+        assert_eq!(csv_escape("plain"), "plain");
+    }
+
+    #[test]
+    fn list_csv_conflicts_with_other_output_modes() {
+        assert!(
+            ClubArgs::try_parse_from(["club", "list", "--names-only", "--format", "csv"]).is_err()
+        );
+        assert!(ClubArgs::try_parse_from(["club", "list", "--format", "csv"]).is_ok());
+    }
+
+    /// A project whose manifest was left pointing at staging.
+    fn swapped_project() -> Project {
+        let project = Project::with_remotes();
+        let mut manifest = project.manifest();
+        manifest["scriptId"] = Value::String(script_id('B'));
+        project.write_manifest(manifest);
+        project
+    }
+
+    #[test]
+    fn repair_detects_a_swapped_manifest() {
+        let main = RemoteName("main".to_string());
+        let config = get_clasp_config(&swapped_project().manifest_path, true).unwrap();
+        assert_eq!(
+            find_swapped_state(&config, &main).map(|(name, _)| name),
+            Some(main.clone())
+        );
+        let config = get_clasp_config(&Project::with_remotes().manifest_path, true).unwrap();
+        assert_eq!(find_swapped_state(&config, &main), None);
+    }
+
+    #[test]
+    fn repair_restores_the_default_remote() {
+        let project = swapped_project();
+        project.run(&["repair", "--yes"]).unwrap();
+        assert_eq!(project.manifest()["scriptId"], script_id('A'));
+        let before = project.manifest_text();
+        project.run(&["repair", "--yes"]).unwrap();
+        assert_eq!(project.manifest_text(), before);
+    }
+
+    /// A fake clasp body that reports being logged in, so doctor doesn't fall back to `~/.clasprc.json`.
+    #[cfg(unix)]
+    const LOGGED_IN_CLASP: &str =
+        "if [ \"$1\" = login ]; then echo 'You are logged in as dev@example.com.'; fi";
+
+    #[cfg(unix)]
+    #[test]
+    fn doctor_fix_restores_a_swapped_manifest() {
+        let project = swapped_project();
+        let clasp = project.fake_clasp(LOGGED_IN_CLASP);
+        assert!(matches!(
+            project.run(&["--clasp-bin", &clasp, "doctor"]),
+            Err(ClubError::DoctorProblemsLeft(1))
+        ));
+        assert_eq!(project.manifest()["scriptId"], script_id('B'));
+        project
+            .run(&["--clasp-bin", &clasp, "doctor", "--fix", "--yes"])
+            .unwrap();
+        assert_eq!(project.manifest()["scriptId"], script_id('A'));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn doctor_fix_seeds_an_empty_club_and_recovers_pasted_ids() {
+        let project = Project::new(serde_json::json!({}));
+        let clasp = project.fake_clasp(LOGGED_IN_CLASP);
+        project
+            .run(&["--clasp-bin", &clasp, "doctor", "--fix", "--yes"])
+            .unwrap();
+        assert_eq!(
+            project.manifest()["__club__"],
+            serde_json::json!({ VERSION_KEY: CLUB_SCHEMA_VERSION, "main": script_id('A') })
+        );
+
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "staging": format!("  {}\n", script_id('B')),
+            "qa": { "id": format!("https://script.google.com/d/{}/edit", script_id('C')), "protected": true },
+        }));
+        let mut manifest = project.manifest();
+        assert_eq!(
+            recover_remote_ids(&mut manifest),
+            vec![
+                (RemoteName("staging".to_string()), RemoteId(script_id('B'))),
+                (RemoteName("qa".to_string()), RemoteId(script_id('C'))),
+            ]
+        );
+        project
+            .run(&["--clasp-bin", &clasp, "doctor", "--fix", "--yes"])
+            .unwrap();
+        assert_eq!(
+            project.manifest()["__club__"],
+            serde_json::json!({
+                "main": script_id('A'),
+                "staging": script_id('B'),
+                "qa": { "id": script_id('C'), "protected": true },
+            })
+        );
+        project.run(&["--clasp-bin", &clasp, "doctor"]).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn doctor_fails_while_problems_it_cannot_fix_remain() {
+        let project = swapped_project();
+        let clasp = project
+            .fake_clasp("if [ \"$1\" = login ]; then echo 'You are not logged in.'; exit 1; fi");
+        assert!(matches!(
+            project.run(&["--clasp-bin", &clasp, "doctor", "--fix", "--yes"]),
+            Err(ClubError::DoctorProblemsLeft(1))
+        ));
+        assert_eq!(project.manifest()["scriptId"], script_id('A'));
+    }
+
+    #[test]
+    fn switch_persists_the_active_remote() {
+        let project = Project::with_remotes();
+        project.run(&["switch", "staging"]).unwrap();
+        let manifest = project.manifest();
+        assert_eq!(manifest["scriptId"], script_id('B'));
+        assert_eq!(manifest["__club__"][ACTIVE_REMOTE_KEY], "staging");
+    }
+
+    #[test]
+    fn repair_restores_the_persisted_active_remote() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "staging": script_id('B'),
+            "qa": script_id('C'),
+        }));
+        project.run(&["switch", "staging"]).unwrap();
+        let mut manifest = project.manifest();
+        manifest["scriptId"] = Value::String(script_id('C'));
+        project.write_manifest(manifest);
+        project.run(&["repair", "--yes"]).unwrap();
+        assert_eq!(project.manifest()["scriptId"], script_id('B'));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_keeps_the_active_remote() {
+        let project = Project::with_remotes();
+        project.run(&["switch", "staging"]).unwrap();
+        let clasp = project.fake_clasp("");
+        project
+            .run(&["--clasp-bin", &clasp, "push", "main"])
+            .unwrap();
+        let manifest = project.manifest();
+        assert_eq!(manifest["scriptId"], script_id('B'));
+        assert_eq!(manifest["__club__"][ACTIVE_REMOTE_KEY], "staging");
+    }
+
+    fn names(remotes: &IndexMap<RemoteName, Remote>) -> Vec<&str> {
+        remotes.keys().map(|name| name.0.as_str()).collect()
+    }
+
+    #[test]
+    fn list_active_only_keeps_the_active_remote() {
+        let mut remotes = remotes_of(&[("main", 'A'), ("staging", 'B')]);
+        retain_active(&mut remotes, &script_id('B'));
+        assert_eq!(names(&remotes), ["staging"]);
+    }
+
+    #[test]
+    fn list_active_only_is_empty_when_detached() {
+        let mut remotes = remotes_of(&[("main", 'A'), ("staging", 'B')]);
+        retain_active(&mut remotes, &script_id('Z'));
+        assert!(remotes.is_empty());
+    }
+
+    #[test]
+    fn list_active_only_keeps_every_remote_sharing_the_id() {
+        let mut remotes = remotes_of(&[("main", 'A'), ("staging", 'B'), ("prod", 'A')]);
+        retain_active(&mut remotes, &script_id('A'));
+        assert_eq!(names(&remotes), ["main", "prod"]);
+    }
+
+    #[test]
+    fn set_stdin_parses_every_valid_line() {
+        let input = format!(
+            "# remotes\nqa {}\n\n  staging   {}\n",
+            script_id('C'),
+            script_id('B')
+        );
+        let entries = parse_remote_lines(std::io::Cursor::new(input), true).unwrap();
+        assert_eq!(
+            entries,
+            [
+                (RemoteName("qa".to_string()), RemoteId(script_id('C'))),
+                (RemoteName("staging".to_string()), RemoteId(script_id('B'))),
+            ]
+        );
+    }
+
+    #[test]
+    fn set_stdin_rejects_the_whole_batch_for_one_bad_line() {
+        let input = format!("qa {}\nstaging not-an-id\nprod\n", script_id('C'));
+        assert!(matches!(
+            parse_remote_lines(std::io::Cursor::new(input), true),
+            Err(ClubError::InvalidBatchLine(2, _))
+        ));
+        let input = format!("qa {}\nprod\n", script_id('C'));
+        assert!(matches!(
+            parse_remote_lines(std::io::Cursor::new(input), true),
+            Err(ClubError::InvalidBatchLine(2, _))
+        ));
+    }
+
+    #[test]
+    fn env_exports_use_each_shell_syntax() {
+        let staging = RemoteName("staging".to_string());
+        let id = RemoteId(script_id('B'));
+        assert_eq!(
+            env_exports(&staging, &id, false, false),
+            format!(
+                "export CLUB_REMOTE=staging\nexport CLUB_SCRIPT_ID={}\n",
+                script_id('B')
+            )
+        );
+        assert_eq!(
+            env_exports(&staging, &id, true, false),
+            format!(
+                "set -gx CLUB_REMOTE staging;\nset -gx CLUB_SCRIPT_ID {};\n",
+                script_id('B')
+            )
+        );
+    }
+
+    fn settings_for(project: &Project, args: &[&str]) -> Settings {
+        let manifest = project.manifest_path.to_string_lossy().to_string();
+        let args =
+            ClubArgs::try_parse_from(["club", "--manifest", manifest.as_str()].iter().chain(args))
+                .unwrap();
+        project.load_settings(&args).unwrap()
+    }
+
+    #[test]
+    fn clubrc_values_are_applied() {
+        let project = Project::with_remotes();
+        std::fs::write(
+            project.path(SETTINGS_NAME),
+            r#"{ "claspBin": "/opt/clasp", "quiet": true, "defaultRemote": "staging" }"#,
+        )
+        .unwrap();
+        let settings = settings_for(&project, &["list"]);
+        assert_eq!(settings.clasp_bin, "/opt/clasp");
+        assert!(settings.quiet);
+        assert_eq!(settings.default_remote.0, "staging");
+    }
+
+    #[test]
+    fn clubrc_can_turn_off_recent_ids() {
+        let project = Project::with_remotes();
+        std::fs::write(project.path(SETTINGS_NAME), r#"{ "recentIds": false }"#).unwrap();
+        let args = ClubArgs::try_parse_from(["club", "list"]).unwrap();
+        assert_eq!(
+            load_settings(&args, &project.manifest_path)
+                .unwrap()
+                .recent_ids,
+            None
+        );
+    }
+
+    #[test]
+    fn command_line_flags_override_clubrc() {
+        let project = Project::with_remotes();
+        std::fs::write(
+            project.path(SETTINGS_NAME),
+            r#"{ "claspBin": "/opt/clasp", "color": "always" }"#,
+        )
+        .unwrap();
+        let settings = settings_for(
+            &project,
+            &["--clasp-bin", "./clasp", "--color", "never", "list"],
+        );
+        assert_eq!(settings.clasp_bin, "./clasp");
+        assert_eq!(settings.color, ColorChoice::Never);
+    }
+
+    fn is_valid_id(id: &str) -> bool {
+        RemoteId::try_from(id.to_string()).is_ok()
+    }
+
+    #[test]
+    fn remote_id_accepts_the_documented_character_set() {
+        assert!(is_valid_id(&script_id('A')));
+        assert!(is_valid_id(&format!("-{}", "a".repeat(56))));
+        assert!(is_valid_id(&format!("{}-", "a".repeat(56))));
+        assert!(is_valid_id(&format!("_{}_", "9".repeat(55))));
+        assert!(is_valid_id(&"7".repeat(57)));
+        assert!(is_valid_id(&"aB3_-".repeat(12)[..57]));
+    }
+
+    #[test]
+    fn remote_id_rejects_wrong_lengths_and_characters() {
+        assert!(!is_valid_id(""));
+        assert!(!is_valid_id(&"a".repeat(56)));
+        assert!(!is_valid_id(&"a".repeat(58)));
+        assert!(!is_valid_id(&format!("{}!", "a".repeat(57))));
+        assert!(!is_valid_id(&format!(" {}", "a".repeat(57))));
+        assert!(!is_valid_id(&format!(
+            "{}.{}",
+            "a".repeat(28),
+            "a".repeat(28)
+        )));
+        assert!(!is_valid_id(&format!("{}é", "a".repeat(56))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_restore_to_leaves_the_manifest_on_another_remote() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "staging": script_id('B'),
+            "qa": script_id('C'),
+        }));
+        let clasp = project.fake_clasp("");
+        project
+            .run(&[
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "staging",
+                "--restore-to",
+                "qa",
+            ])
+            .unwrap();
+        assert_eq!(project.clasp_calls(), [format!("push|{}", script_id('B'))]);
+        let manifest = project.manifest();
+        assert_eq!(manifest["scriptId"], script_id('C'));
+        assert_eq!(manifest["__club__"][ACTIVE_REMOTE_KEY], "qa");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_restore_to_an_unknown_remote_aborts_before_pushing() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        assert!(matches!(
+            project.run(&[
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "staging",
+                "--restore-to",
+                "ghost"
+            ]),
+            Err(ClubError::RemoteNotFound)
+        ));
+        assert!(project.clasp_calls().is_empty());
+        assert_eq!(project.manifest()["scriptId"], script_id('A'));
+    }
+
+    #[test]
+    fn clasp_passthrough_rejects_an_unknown_remote() {
+        let project = Project::with_remotes();
+        let before = project.manifest_text();
+        assert!(matches!(
+            project.run(&["clasp", "nope", "--", "logs"]),
+            Err(ClubError::RemoteNotFound)
+        ));
+        assert_eq!(project.manifest_text(), before);
+    }
+
+    /// The shape `club status --json` promises to consumers.
+    #[derive(Debug, PartialEq)]
+    struct StatusOutput {
+        manifest_path: String,
+        root_dir: String,
+        remote_count: u64,
+        active_remote: Option<String>,
+        setup: bool,
+    }
+
+    impl StatusOutput {
+        fn parse(json: &str) -> Self {
+            let value: Value = serde_json::from_str(json).unwrap();
+            StatusOutput {
+                manifest_path: value["manifest_path"].as_str().unwrap().to_string(),
+                root_dir: value["root_dir"].as_str().unwrap().to_string(),
+                remote_count: value["remote_count"].as_u64().unwrap(),
+                active_remote: active_remote_of(&value),
+                setup: value["setup"].as_bool().unwrap(),
+            }
+        }
+    }
+
+    /// Reads `active_remote`, which must be present and either a string or null.
+    fn active_remote_of(value: &Value) -> Option<String> {
+        match &value["active_remote"] {
+            Value::Null => {
+                assert!(value.as_object().unwrap().contains_key("active_remote"));
+                None
+            }
+            Value::String(name) => Some(name.clone()),
+            other => panic!("unexpected active_remote: {}", other),
+        }
+    }
+
+    #[test]
+    fn status_json_describes_the_project() {
+        let project = Project::with_remotes();
+        project.run(&["switch", "staging"]).unwrap();
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        let json = serde_json::to_string_pretty(&status_json(&project.manifest_path, &config));
+        assert_eq!(
+            StatusOutput::parse(&json.unwrap()),
+            StatusOutput {
+                manifest_path: project.manifest_path.display().to_string(),
+                root_dir: "src".to_string(),
+                remote_count: 2,
+                active_remote: Some("staging".to_string()),
+                setup: true,
+            }
+        );
+    }
+
+    #[test]
+    fn status_json_for_an_unset_project_has_no_active_remote() {
+        let project = Project::with_remotes();
+        let mut manifest = project.manifest();
+        manifest.as_object_mut().unwrap().remove("__club__");
+        project.write_manifest(manifest);
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        let json = serde_json::to_string(&status_json(&project.manifest_path, &config)).unwrap();
+        let output = StatusOutput::parse(&json);
+        assert!(!output.setup);
+        assert_eq!(output.remote_count, 0);
+        assert_eq!(output.active_remote, None);
+    }
+
+    #[test]
+    fn current_json_is_null_when_detached() {
+        let project = Project::with_remotes();
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        let json = serde_json::to_string(&current_json(&config)).unwrap();
+        assert_eq!(
+            active_remote_of(&serde_json::from_str(&json).unwrap()),
+            Some("main".to_string())
+        );
+
+        let mut manifest = project.manifest();
+        manifest["scriptId"] = Value::String(script_id('Z'));
+        project.write_manifest(manifest);
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        let json = serde_json::to_string(&current_json(&config)).unwrap();
+        assert_eq!(
+            active_remote_of(&serde_json::from_str(&json).unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn current_rejects_an_unset_project() {
+        let project = Project::with_remotes();
+        let mut manifest = project.manifest();
+        manifest.as_object_mut().unwrap().remove("__club__");
+        project.write_manifest(manifest);
+        assert!(matches!(
+            project.run(&["--json", "current"]),
+            Err(ClubError::ClubNotSetup)
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn require_remote_rejects_a_bare_push() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        assert!(matches!(
+            project.run(&["--clasp-bin", &clasp, "push", "--require-remote"]),
+            Err(ClubError::RemoteRequired)
+        ));
+        assert!(project.clasp_calls().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn require_remote_allows_a_named_remote() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        project
+            .run(&["--clasp-bin", &clasp, "push", "main", "--require-remote"])
+            .unwrap();
+        assert_eq!(project.clasp_calls(), [format!("push|{}", script_id('A'))]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn require_remote_can_be_set_in_clubrc() {
+        let project = Project::with_remotes();
+        std::fs::write(project.path(SETTINGS_NAME), r#"{ "requireRemote": true }"#).unwrap();
+        let clasp = project.fake_clasp("");
+        assert!(matches!(
+            project.run(&["--clasp-bin", &clasp, "push"]),
+            Err(ClubError::RemoteRequired)
+        ));
+        project
+            .run(&["--clasp-bin", &clasp, "push", "main"])
+            .unwrap();
+        assert_eq!(project.clasp_calls(), [format!("push|{}", script_id('A'))]);
+    }
+
+    #[test]
+    fn context_reads_the_manifest_once() {
+        let project = Project::with_remotes();
+        let ctx = Context::new(project.manifest_path.clone(), true);
+        assert_eq!(ctx.manifest_reads.get(), 0);
+        ctx.config().unwrap();
+        ctx.config().unwrap();
+        assert_eq!(ctx.manifest_reads.get(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_reads_the_manifest_once() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        let (result, ctx) = project.run_with_context(&["--clasp-bin", &clasp, "push", "--all"]);
+        result.unwrap();
+        assert_eq!(project.clasp_calls().len(), 2);
+        assert_eq!(ctx.manifest_reads.get(), 1);
+    }
+
+    #[test]
+    fn context_retries_a_failed_read() {
+        let project = Project::with_remotes();
+        let manifest = project.manifest_text();
+        std::fs::write(&project.manifest_path, "not json").unwrap();
+        let ctx = Context::new(project.manifest_path.clone(), true);
+        assert!(matches!(
+            ctx.config(),
+            Err(ClubError::ManifestParseError(..))
+        ));
+        std::fs::write(&project.manifest_path, manifest).unwrap();
+        assert!(ctx.config().is_ok());
+        assert_eq!(ctx.manifest_reads.get(), 2);
+    }
+
+    #[test]
+    fn legacy_ids_are_rejected_by_default() {
+        let legacy_id = "a".repeat(60);
+        assert!(matches!(
+            RemoteId::parse(legacy_id.clone(), true),
+            Err(ClubError::InvalidRemoteId)
+        ));
+        let project = Project::with_remotes();
+        assert!(matches!(
+            project.run(&["set", "legacy", &legacy_id]),
+            Err(ClubError::InvalidRemoteId)
+        ));
+    }
+
+    #[test]
+    fn no_validate_ids_accepts_legacy_ids() {
+        let legacy_id = "a".repeat(60);
+        assert_eq!(
+            RemoteId::parse(legacy_id.clone(), false).unwrap(),
+            RemoteId(legacy_id.clone())
+        );
+        assert!(RemoteId::parse(String::new(), false).is_err());
+        assert!(RemoteId::parse(format!("{}!", legacy_id), false).is_err());
+
+        let project = Project::with_remotes();
+        project
+            .run(&["--no-validate-ids", "set", "legacy", &legacy_id])
+            .unwrap();
+        assert_eq!(project.manifest()["__club__"]["legacy"], legacy_id);
+        // Once a legacy ID is in the manifest, strict commands refuse to read it.
+        assert!(matches!(
+            project.run(&["list"]),
+            Err(ClubError::ManifestShapeError(_))
+        ));
+        project.run(&["--no-validate-ids", "list"]).unwrap();
+        assert!(
+            ClubArgs::try_parse_from(["club", "--no-validate-ids", "--strict", "list"]).is_err()
+        );
+
+        // A strict .clubrc turns the warning about a legacy ID into an error.
+        std::fs::write(project.path(SETTINGS_NAME), r#"{ "strict": true }"#).unwrap();
+        assert!(matches!(
+            project.run(&["--no-validate-ids", "set", "legacy2", &legacy_id]),
+            Err(ClubError::StrictWarning(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    fn deploy_info(project: &Project) -> Option<Value> {
+        let markers = std::fs::read_to_string(project.path("src").join(DEPLOY_INFO_NAME)).ok()?;
+        Some(serde_json::from_str(&markers).unwrap())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_message_records_a_deploy_marker() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        project
+            .run(&["--clasp-bin", &clasp, "push", "staging", "-m", "Fix totals"])
+            .unwrap();
+        project
+            .run(&["--clasp-bin", &clasp, "push", "main", "-m", "First"])
+            .unwrap();
+        project
+            .run(&["--clasp-bin", &clasp, "push", "main", "-m", "Second"])
+            .unwrap();
+        let markers = deploy_info(&project).unwrap();
+        assert_eq!(markers["staging"]["message"], "Fix totals");
+        assert_eq!(markers["main"]["message"], "Second");
+        assert!(markers["main"]["pushedAt"].as_str().unwrap().ends_with('Z'));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_message_is_not_recorded_for_a_failed_push() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("exit 1");
+        assert!(project
+            .run(&["--clasp-bin", &clasp, "push", "staging", "-m", "Fix totals"])
+            .is_err());
+        assert_eq!(deploy_info(&project), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_without_a_message_writes_no_marker() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        project.run(&["--clasp-bin", &clasp, "push"]).unwrap();
+        assert_eq!(deploy_info(&project), None);
+    }
+
+    #[test]
+    fn format_timestamp_renders_utc() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        let leap_day = UNIX_EPOCH + std::time::Duration::from_secs(1_709_226_245);
+        assert_eq!(format_timestamp(leap_day), "2024-02-29T17:04:05Z");
+    }
+
+    #[test]
+    fn since_parses_relative_durations_and_timestamps() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_709_226_245);
+        assert_eq!(
+            parse_since_at("2h", now).unwrap(),
+            now - Duration::from_secs(2 * 3600)
+        );
+        assert_eq!(
+            parse_since_at("7d", now).unwrap(),
+            now - Duration::from_secs(7 * 86400)
+        );
+        assert_eq!(parse_since_at("2024-02-29T17:04:05Z", now).unwrap(), now);
+        assert_eq!(
+            parse_since_at("2024-02-29T19:04:05.5+02:00", now).unwrap(),
+            now
+        );
+        assert!(parse_since_at("yesterday", now).is_err());
+        assert!(parse_since_at("2024-13-01T00:00:00Z", now).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn log_filters_pushes_by_time_remote_and_outcome() {
+        let entry = |remote: &str, ok: bool, pushed_at: &str| serde_json::json!({ "remote": remote, "ok": ok, "pushedAt": pushed_at });
+        let entries = vec![
+            entry("main", true, "2024-02-28T09:00:00Z"),
+            entry("staging", false, "2024-02-29T09:00:00Z"),
+            entry("main", true, "2024-03-01T09:00:00Z"),
+            entry("staging", true, "2024-03-02T09:00:00Z"),
+        ];
+        let cutoff = parse_timestamp("2024-02-29T09:00:00Z");
+        let remotes = |entries: Vec<Value>| -> Vec<String> {
+            entries
+                .iter()
+                .map(|entry| format!("{}@{}", entry["remote"], entry["pushedAt"]))
+                .collect()
+        };
+        assert_eq!(
+            remotes(filter_history(entries.clone(), cutoff, None, false)),
+            [
+                r#""main"@"2024-03-01T09:00:00Z""#,
+                r#""staging"@"2024-03-02T09:00:00Z""#
+            ]
+        );
+        let staging = RemoteName("staging".to_string());
+        assert_eq!(
+            remotes(filter_history(entries.clone(), None, Some(&staging), true)),
+            [r#""staging"@"2024-02-29T09:00:00Z""#]
+        );
+
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp(&format!(
+            "if [ \"$id\" = {} ]; then exit 1; fi",
+            script_id('B')
+        ));
+        let push = [
+            "--clasp-bin",
+            &clasp,
+            "push",
+            "--all",
+            "--max-failures",
+            "5",
+        ];
+        let _ = project.run(&push);
+        assert!(!project.path(HISTORY_NAME).exists());
+        std::fs::write(project.path(SETTINGS_NAME), r#"{ "history": true }"#).unwrap();
+        let _ = project.run(&push);
+        let history = read_history(&project.manifest_path);
+        let outcomes: Vec<(Value, Value)> = history
+            .iter()
+            .map(|entry| (entry["remote"].clone(), entry["ok"].clone()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                (Value::from("main"), Value::from(true)),
+                (Value::from("staging"), Value::from(false))
+            ]
+        );
+        assert!(history.iter().all(|entry| entry["pushedAt"]
+            .as_str()
+            .and_then(parse_timestamp)
+            .is_some()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_to_record_history_does_not_fail_a_strict_push() {
+        let project = Project::with_remotes();
+        std::fs::write(project.path(SETTINGS_NAME), r#"{ "history": true }"#).unwrap();
+        std::fs::create_dir(project.path(HISTORY_NAME)).unwrap();
+        let clasp = project.fake_clasp("");
+        project
+            .run(&["--clasp-bin", &clasp, "--strict", "push", "main"])
+            .unwrap();
+    }
+
+    #[test]
+    fn project_resolves_the_manifest_variant() {
+        let dir = TempDir::new();
+        let default_path = dir.path().join(MANIFEST_NAME);
+        assert_eq!(resolve_manifest_path(dir.path(), None), default_path);
+        assert_eq!(resolve_manifest_path(dir.path(), Some("dev")), default_path);
+
+        let dev_path = dir.path().join(".clasp.dev.json");
+        std::fs::write(&dev_path, "{}").unwrap();
+        assert_eq!(resolve_manifest_path(dir.path(), Some("dev")), dev_path);
+        assert_eq!(resolve_manifest_path(dir.path(), None), default_path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn clasp_receives_the_manifest_variant() {
+        let project = Project::with_manifest_name(
+            serde_json::json!({ "main": script_id('A'), "staging": script_id('B') }),
+            ".clasp.dev.json",
+        );
+        let clasp = project.fake_clasp("");
+        project
+            .run(&["--clasp-bin", &clasp, "push", "staging"])
+            .unwrap();
+        let calls = project.clasp_calls();
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].starts_with("-P .clasp.dev.json push|"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn clasp_is_not_given_the_default_manifest() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        project
+            .run(&["--clasp-bin", &clasp, "push", "staging"])
+            .unwrap();
+        assert_eq!(project.clasp_calls(), [format!("push|{}", script_id('B'))]);
+    }
+
+    /// Runs a push command line against the project, returning the `--stdout-json` events it wrote.
+    #[cfg(unix)]
+    fn push_events(project: &Project, args: &[&str]) -> (Result<(), ClubError>, Vec<Value>) {
+        let manifest = project.manifest_path.to_string_lossy().to_string();
+        let args = ClubArgs::try_parse_from(
+            ["club", "--manifest", manifest.as_str()]
+                .into_iter()
+                .chain(args.iter().copied()),
+        )
+        .unwrap();
+        let settings = project.load_settings(&args).unwrap();
+        let ClubCommand::Push(push_args) = args.command else {
+            panic!("not a push command");
+        };
+        let ctx = Context::new(project.manifest_path.clone(), true);
+        let mut events = Vec::new();
+        let result = push_with_events(push_args, &ctx, &settings, &mut events);
+        let events = String::from_utf8(events)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        (result, events)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_profile_reports_a_row_per_pushed_remote() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("sleep 0.01");
+        let (result, events) = push_events(
+            &project,
+            &[
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "--all",
+                "--stdout-json",
+                "--profile",
+            ],
+        );
+        result.unwrap();
+        let profile: Vec<Value> = events
+            .into_iter()
+            .filter(|event| event["event"] == "push_profile")
+            .collect();
+        assert_eq!(profile.len(), 2);
+        for (event, remote) in profile.iter().zip(["main", "staging"]) {
+            assert_eq!(event["remote"], remote);
+            let millis: Vec<u64> = ["write_ms", "clasp_ms", "restore_ms", "total_ms"]
+                .iter()
+                .map(|column| event[column].as_u64().unwrap())
+                .collect();
+            assert!(millis[1] >= 10, "clasp slept for 10ms: {:?}", millis);
+            assert!(millis[3] >= millis[0] + millis[1] + millis[2]);
+        }
+
+        let table = profile_table(&profile);
+        assert_eq!(table.len(), 4);
+        let remotes: Vec<&str> = table
+            .iter()
+            .map(|line| line.split_whitespace().next().unwrap())
+            .collect();
+        assert_eq!(remotes, ["remote", "main", "staging", "total"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stdout_json_streams_an_event_per_push_step() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("echo clasp output");
+        let (result, events) = push_events(
+            &project,
+            &["--clasp-bin", &clasp, "push", "--all", "--stdout-json"],
+        );
+        result.unwrap();
+        assert_eq!(
+            events,
+            [
+                serde_json::json!({ "event": "push_start", "remote": "main" }),
+                serde_json::json!({ "event": "push_result", "remote": "main", "ok": true }),
+                serde_json::json!({ "event": "push_start", "remote": "staging" }),
+                serde_json::json!({ "event": "push_result", "remote": "staging", "ok": true }),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn max_failures_stops_the_all_loop_at_the_threshold() {
+        let project = Project::new(serde_json::json!({
+            "r1": script_id('A'),
+            "r2": script_id('B'),
+            "r3": script_id('C'),
+            "r4": script_id('D'),
+            "r5": script_id('E'),
+        }));
+        let clasp = project.fake_clasp("exit 1");
+        let result = project.run(&[
+            "--clasp-bin",
+            &clasp,
+            "push",
+            "--all",
+            "--max-failures",
+            "3",
+        ]);
+        match result {
+            Err(ClubError::PushStopped(failed, skipped)) => {
+                assert_eq!(
+                    failed,
+                    ["r1", "r2", "r3"].map(|n| RemoteName(n.to_string()))
+                );
+                assert_eq!(skipped, ["r4", "r5"].map(|n| RemoteName(n.to_string())));
+            }
+            other => panic!("expected the push to stop, got {:?}", other),
+        }
+        assert_eq!(project.clasp_calls().len(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn max_failures_carries_on_below_the_threshold() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp(&format!(
+            "if [ \"$id\" = {} ]; then exit 1; fi",
+            script_id('A')
+        ));
+        let result = project.run(&[
+            "--clasp-bin",
+            &clasp,
+            "push",
+            "--all",
+            "--max-failures",
+            "2",
+        ]);
+        assert!(
+            matches!(result, Err(ClubError::PushFailed(failed)) if failed == [RemoteName("main".to_string())])
+        );
+        assert_eq!(project.clasp_calls().len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stdout_json_reports_a_failed_push() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("exit 1");
+        let (result, events) = push_events(
+            &project,
+            &["--clasp-bin", &clasp, "push", "--all", "--stdout-json"],
+        );
+        assert!(matches!(result, Err(ClubError::ClaspError(_))));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1]["event"], "push_result");
+        assert_eq!(events[1]["ok"], false);
+        assert_eq!(events[1]["error"], "Error running clasp: clasp push failed");
+    }
+
+    /// A project whose fake clasp pulls the contents of its `remote` directory, with `source`
+    /// as both the local `Code.gs` and the remote's `Code.js`.
+    #[cfg(unix)]
+    fn pull_project(source: &str) -> (Project, String) {
+        let project = Project::with_remotes();
+        std::fs::write(project.path("src/Code.gs"), source).unwrap();
+        std::fs::create_dir(project.path("remote")).unwrap();
+        std::fs::write(project.path("remote/Code.js"), source).unwrap();
+        let clasp = project.fake_clasp(&format!(
+            "if [ \"$1\" = pull ]; then cp -R '{}'/. {}/; fi",
+            project.path("remote").display(),
+            ISOLATED_ROOT_DIR
+        ));
+        (project, clasp)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn if_clean_remote_pushes_when_the_remote_is_unchanged() {
+        let (project, clasp) = pull_project("function main() {}");
+        let push = [
+            "--clasp-bin",
+            &clasp,
+            "push",
+            "staging",
+            "--if-clean-remote",
+        ];
+        // The first push has nothing to compare against, so it only records what was pushed.
+        project.run(&push).unwrap();
+        project.run(&push).unwrap();
+        assert_eq!(
+            project.clasp_calls(),
+            [
+                format!("push|{}", script_id('B')),
+                format!("pull|{}", script_id('B')),
+                format!("push|{}", script_id('B')),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn if_clean_remote_refuses_to_overwrite_editor_changes() {
+        let (project, clasp) = pull_project("function main() {}");
+        let push = [
+            "--clasp-bin",
+            &clasp,
+            "push",
+            "staging",
+            "--if-clean-remote",
+        ];
+        project.run(&push).unwrap();
+        std::fs::write(project.path("remote/Code.js"), "function edited() {}").unwrap();
+        match project.run(&push) {
+            Err(ClubError::RemoteDiverged(diverged)) => assert_eq!(
+                diverged,
+                [(
+                    RemoteName("staging".to_string()),
+                    vec!["Code.js".to_string()]
+                )]
+            ),
+            other => panic!("expected the push to be refused, got {:?}", other),
+        }
+        assert_eq!(
+            project.clasp_calls(),
+            [
+                format!("push|{}", script_id('B')),
+                format!("pull|{}", script_id('B')),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn plain_pushes_record_what_they_sent_only_with_track_pushes() {
+        let (project, clasp) = pull_project("function main() {}");
+        project
+            .run(&["--clasp-bin", &clasp, "push", "staging"])
+            .unwrap();
+        assert!(!project.path(PUSHED_STATE_NAME).exists());
+
+        std::fs::write(project.path(SETTINGS_NAME), r#"{ "trackPushes": true }"#).unwrap();
+        project
+            .run(&["--clasp-bin", &clasp, "push", "staging"])
+            .unwrap();
+        std::fs::write(project.path("remote/Code.js"), "function edited() {}").unwrap();
+        assert!(matches!(
+            project.run(&[
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "staging",
+                "--if-clean-remote"
+            ]),
+            Err(ClubError::RemoteDiverged(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn if_clean_remote_checks_only_the_presence_of_typescript_files() {
+        let (project, clasp) = pull_project("function main() {}");
+        std::fs::remove_file(project.path("src/Code.gs")).unwrap();
+        std::fs::write(project.path("src/Code.ts"), "function main(): void {}").unwrap();
+        let push = [
+            "--clasp-bin",
+            &clasp,
+            "push",
+            "staging",
+            "--if-clean-remote",
+        ];
+        project.run(&push).unwrap();
+        project.run(&push).unwrap();
+        std::fs::write(project.path("remote/Extra.js"), "function added() {}").unwrap();
+        match project.run(&push) {
+            Err(ClubError::RemoteDiverged(diverged)) => {
+                assert_eq!(diverged[0].1, vec!["Extra.js".to_string()])
+            }
+            other => panic!("expected the push to be refused, got {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn source_fingerprints_skip_symlinked_dirs_and_dependencies() {
+        let project = Project::with_remotes();
+        std::fs::write(project.path("src/Code.gs"), "function main() {}").unwrap();
+        std::os::unix::fs::symlink(project.path("src"), project.path("src/loop")).unwrap();
+        std::fs::create_dir_all(project.path("src/node_modules/dep")).unwrap();
+        std::fs::write(project.path("src/node_modules/dep/index.js"), "").unwrap();
+        std::fs::create_dir(project.path("src/.git")).unwrap();
+        std::fs::write(project.path("src/.git/hook.js"), "").unwrap();
+        let fingerprints = source_fingerprints(&project.path("src")).unwrap();
+        assert_eq!(fingerprints.keys().collect::<Vec<_>>(), ["Code.js"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_failure_to_record_the_push_does_not_fail_it_under_strict() {
+        let (project, clasp) = pull_project("function main() {}");
+        std::fs::write(project.path(SETTINGS_NAME), r#"{ "trackPushes": true }"#).unwrap();
+        std::fs::create_dir(project.path(PUSHED_STATE_NAME)).unwrap();
+        let plan_file = project.path("plan.json");
+        project
+            .run(&[
+                "--strict",
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "staging",
+                "--plan-file",
+                plan_file.to_str().unwrap(),
+            ])
+            .unwrap();
+        assert!(plan_file.exists());
+    }
+
+    /// A log destination the test can read back, standing in for stderr.
+    #[derive(Clone, Default)]
+    struct LogBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for LogBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn debug_logging_traces_a_push() {
+        let buffer = LogBuffer::default();
+        // The logger is process-wide, so other tests' records may land in the buffer too.
+        env_logger::Builder::new()
+            .parse_filters("club=debug")
+            .target(env_logger::Target::Pipe(Box::new(buffer.clone())))
+            .try_init()
+            .unwrap();
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        project
+            .run(&["--clasp-bin", &clasp, "push", "staging"])
+            .unwrap();
+        let logs = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let manifest = project.manifest_path.display().to_string();
+        assert!(logs
+            .lines()
+            .any(|line| line.contains("DEBUG") && line.contains(&manifest)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_without_stdout_json_writes_no_events() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        let (result, events) = push_events(&project, &["--clasp-bin", &clasp, "push", "--all"]);
+        result.unwrap();
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn completions_detects_the_shell_from_its_path() {
+        assert_eq!(
+            detect_shell(Some(OsString::from("/bin/zsh"))).unwrap(),
+            Shell::Zsh
+        );
+        assert!(matches!(
+            detect_shell(Some(OsString::from("/bin/tcsh"))),
+            Err(ClubError::UnknownShell)
+        ));
+        assert!(matches!(detect_shell(None), Err(ClubError::UnknownShell)));
+    }
+
+    #[test]
+    fn completions_install_writes_the_zsh_script() {
+        let home = TempDir::new();
+        let path = completions_install_path(Shell::Zsh, home.path()).unwrap();
+        assert_eq!(path, home.path().join(".zsh/completions/_club"));
+        install_completions(Shell::Zsh, &path).unwrap();
+        let script = std::fs::read_to_string(&path).unwrap();
+        assert!(script.starts_with("#compdef club"));
+    }
+
+    #[test]
+    fn completions_install_rejects_unsupported_shells() {
+        let home = TempDir::new();
+        assert!(matches!(
+            completions_install_path(Shell::Elvish, home.path()),
+            Err(ClubError::CompletionInstallUnsupported(Shell::Elvish))
+        ));
+    }
+
+    #[test]
+    fn bash_completions_complete_file_paths_for_manifest() {
+        let home = TempDir::new();
+        let path = completions_install_path(Shell::Bash, home.path()).unwrap();
+        install_completions(Shell::Bash, &path).unwrap();
+        let script = std::fs::read_to_string(&path).unwrap();
+        let start = script.find("--manifest)").unwrap();
+        let end = start + script[start..].find(";;").unwrap();
+        assert!(script[start..end].contains("compopt -o filenames"));
+    }
+
+    #[test]
+    fn shell_aliases_define_functions_wrapping_club() {
+        let bash = shell_aliases(Shell::Bash).unwrap();
+        assert!(bash.contains("\ncpush() { club push \"$@\"; }\n"));
+        assert!(bash.contains("\ncpa() { club push --all \"$@\"; }\n"));
+        assert!(bash.contains("eval \"$(club shell-aliases bash)\""));
+        let fish = shell_aliases(Shell::Fish).unwrap();
+        assert!(fish.contains("function cpush\n    club push $argv\nend\n"));
+        assert!(matches!(
+            shell_aliases(Shell::Elvish),
+            Err(ClubError::ShellAliasesUnsupported(Shell::Elvish))
+        ));
+    }
+
+    #[test]
+    fn reassign_keeps_the_remote_settings() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "staging": { "id": script_id('B'), "protected": true },
+            "qa": script_id('C'),
+        }));
+        project
+            .run(&["reassign", "staging", &script_id('D')])
+            .unwrap();
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        let remotes = config.club_remotes.unwrap();
+        assert_eq!(remotes.len(), 3);
+        let staging = &remotes[&RemoteName("staging".to_string())];
+        assert_eq!(staging.id, RemoteId(script_id('D')));
+        assert!(staging.protected);
+        assert_eq!(config.script_id, script_id('A'));
+    }
+
+    #[test]
+    fn reassign_moves_the_manifest_off_the_old_id() {
+        let project = Project::with_remotes();
+        project.run(&["reassign", "main", &script_id('D')]).unwrap();
+        assert_eq!(project.manifest()["scriptId"], script_id('D'));
+    }
+
+    #[test]
+    fn reassign_rejects_an_absent_remote() {
+        let project = Project::with_remotes();
+        let before = project.manifest_text();
+        assert!(matches!(
+            project.run(&["reassign", "qa", &script_id('D')]),
+            Err(ClubError::RemoteNotFound)
+        ));
+        assert!(matches!(
+            project.run(&["reassign", "staging", "nope"]),
+            Err(ClubError::InvalidRemoteId)
+        ));
+        assert_eq!(project.manifest_text(), before);
+    }
+
+    fn list_args(args: &[&str]) -> ListCommand {
+        let args = ClubArgs::try_parse_from(["club", "list"].iter().chain(args)).unwrap();
+        let ClubCommand::List(list_args) = args.command else {
+            panic!("not a list command");
+        };
+        list_args
+    }
+
+    #[test]
+    fn list_hints_at_club_set_when_empty() {
+        assert_eq!(
+            list_empty_hint(&list_args(&[]), false),
+            Some("No remotes configured. Add one with `club set <name> <id>`.")
+        );
+        let project = Project::new(serde_json::json!({}));
+        project.run(&["list"]).unwrap();
+    }
+
+    #[test]
+    fn list_machine_modes_are_empty_when_empty() {
+        assert_eq!(list_empty_hint(&list_args(&[]), true), None);
+        assert_eq!(list_empty_hint(&list_args(&["--names-only"]), false), None);
+        assert_eq!(
+            list_empty_hint(&list_args(&["--format", "csv"]), false),
+            None
+        );
+        let remotes = IndexMap::new();
+        assert_eq!(remotes_json(&remotes), serde_json::json!([]));
+        assert_eq!(remotes_csv(&remotes, true), "name,id,is_main\n");
+        assert_eq!(remotes_csv(&remotes, false), "");
+    }
+
+    #[test]
+    fn list_json_describes_each_remote() {
+        let mut remotes = remotes_of(&[("main", 'A'), ("staging", 'B')]);
+        remotes[1].protected = true;
+        assert_eq!(
+            remotes_json(&remotes),
+            serde_json::json!([
+                { "name": "main", "id": script_id('A'), "protected": false },
+                { "name": "staging", "id": script_id('B'), "protected": true },
+            ])
+        );
+    }
+
+    #[test]
+    fn manifest_lock_blocks_a_second_holder() {
+        let project = Project::with_remotes();
+        let lock = ManifestLock::acquire(&project.manifest_path, Duration::ZERO).unwrap();
+        assert!(project.path(".clasp.json.lock").exists());
+        assert!(matches!(
+            ManifestLock::acquire(&project.manifest_path, Duration::from_millis(200)),
+            Err(ClubError::ManifestLocked(_))
+        ));
+        drop(lock);
+        assert!(!project.path(".clasp.json.lock").exists());
+        ManifestLock::acquire(&project.manifest_path, Duration::ZERO).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn manifest_lock_takes_over_a_lock_left_by_an_exited_process() {
+        let project = Project::with_remotes();
+        let mut exited = Command::new("true").spawn().unwrap();
+        exited.wait().unwrap();
+        std::fs::write(
+            project.path(".clasp.json.lock"),
+            format!("{}\n", exited.id()),
+        )
+        .unwrap();
+        let _lock = ManifestLock::acquire(&project.manifest_path, Duration::ZERO).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(project.path(".clasp.json.lock")).unwrap(),
+            format!("{}\n", std::process::id())
+        );
+    }
+
+    #[test]
+    fn manifest_lock_waits_for_release() {
+        let project = Project::with_remotes();
+        let lock = ManifestLock::acquire(&project.manifest_path, Duration::ZERO).unwrap();
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            drop(lock);
+        });
+        ManifestLock::acquire(&project.manifest_path, Duration::from_secs(5)).unwrap();
+        release.join().unwrap();
+    }
+
+    #[test]
+    fn commands_skip_the_lock_when_they_cannot_write() {
+        let project = Project::with_remotes();
+        let _lock = ManifestLock::acquire(&project.manifest_path, Duration::ZERO).unwrap();
+        project.run(&["list"]).unwrap();
+        project.run(&["remove", "staging", "--dry-run"]).unwrap();
+        project.run(&["--no-lock", "switch", "staging"]).unwrap();
+        assert_eq!(project.manifest()["scriptId"], script_id('B'));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn show_json_describes_every_field() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "staging": { "id": script_id('B'), "protected": true, "description": "QA copy" },
+            "__groups__": { "qa": ["staging"], "everything": ["main", "staging"] },
+        }));
+        let clasp = project.fake_clasp("");
+        project
+            .run(&["--clasp-bin", &clasp, "switch", "staging"])
+            .unwrap();
+        project
+            .run(&[
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "staging",
+                "--confirm-prod",
+                "-m",
+                "Fix totals",
+            ])
+            .unwrap();
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        let detail = show_json(
+            &config,
+            &RemoteName("staging".to_string()),
+            &project.path("src"),
+        )
+        .unwrap();
+        let pushed_at = detail["last_deploy"]["pushed_at"].clone();
+        assert!(pushed_at.is_string());
+        assert_eq!(
+            detail,
+            serde_json::json!({
+                "name": "staging",
+                "id": script_id('B'),
+                "protected": true,
+                "description": "QA copy",
+                "active": true,
+                "groups": ["qa", "everything"],
+                "last_deploy": { "message": "Fix totals", "pushed_at": pushed_at },
+            })
+        );
+    }
+
+    #[test]
+    fn set_description_from_file_keeps_every_line() {
+        let project = Project::with_remotes();
+        std::fs::write(
+            project.path("staging.md"),
+            "# Staging\r\n\nShared with the *QA* team.\n\n",
+        )
+        .unwrap();
+        let description = project.path("staging.md").to_string_lossy().into_owned();
+        project
+            .run(&[
+                "set",
+                "staging",
+                &script_id('B'),
+                "--description-from-file",
+                &description,
+            ])
+            .unwrap();
+        assert_eq!(
+            project.manifest()["__club__"]["staging"],
+            serde_json::json!({
+                "id": script_id('B'),
+                "description": "# Staging\n\nShared with the *QA* team.",
+            })
+        );
+        project.run(&["show", "staging"]).unwrap();
+
+        std::fs::write(project.path("staging.md"), "").unwrap();
+        project
+            .run(&[
+                "set",
+                "staging",
+                &script_id('B'),
+                "--description-from-file",
+                &description,
+            ])
+            .unwrap();
+        assert_eq!(project.manifest()["__club__"]["staging"], script_id('B'));
+    }
+
+    #[test]
+    fn set_description_from_file_rejects_long_or_control_text() {
+        let project = Project::with_remotes();
+        let description = project.path("staging.md").to_string_lossy().into_owned();
+        let set = || {
+            project.run(&[
+                "set",
+                "staging",
+                &script_id('B'),
+                "--description-from-file",
+                &description,
+            ])
+        };
+        std::fs::write(
+            project.path("staging.md"),
+            "x".repeat(DESCRIPTION_LIMIT + 1),
+        )
+        .unwrap();
+        assert!(matches!(
+            set(),
+            Err(ClubError::DescriptionTooLong(length)) if length == DESCRIPTION_LIMIT + 1
+        ));
+        std::fs::write(project.path("staging.md"), "Ring the bell\u{7}").unwrap();
+        assert!(matches!(
+            set(),
+            Err(ClubError::DescriptionControlChar('\u{7}'))
+        ));
+        assert_eq!(project.manifest()["__club__"]["staging"], script_id('B'));
+    }
+
+    #[test]
+    fn mv_manifest_keeps_root_dir_pointing_at_the_same_source() {
+        let project = Project::with_remotes();
+        std::fs::write(project.path(PUSHED_STATE_NAME), "{}").unwrap();
+        let new_dir = project.path("apps/web");
+        project
+            .run(&["mv-manifest", &new_dir.to_string_lossy()])
+            .unwrap();
+        assert!(!project.manifest_path.exists());
+        assert!(new_dir.join(PUSHED_STATE_NAME).exists());
+        let moved = get_clasp_config(&new_dir.join(MANIFEST_NAME), true).unwrap();
+        assert_eq!(moved.root_dir, "../../src");
+        assert_eq!(moved.script_id, script_id('A'));
+        assert_eq!(moved.club_remotes.as_ref().unwrap().len(), 2);
+        assert_eq!(
+            normalize_path(&moved.resolved_root_dir(&new_dir)),
+            project.path("src")
+        );
+
+        assert_eq!(
+            relative_path(Path::new("/repo/apps"), Path::new("/repo")),
+            Path::new("..")
+        );
+        assert_eq!(
+            relative_path(Path::new("/repo"), Path::new("/repo")),
+            Path::new("")
+        );
+    }
+
+    #[test]
+    fn mv_manifest_takes_clubrc_and_the_manifest_name_along() {
+        let mut project = Project::with_remotes();
+        let variant = project.path(".clasp.dev.json");
+        std::fs::rename(&project.manifest_path, &variant).unwrap();
+        project.manifest_path = variant;
+        std::fs::write(
+            project.path(SETTINGS_NAME),
+            r#"{ "defaultRemote": "staging", "remoteFile": "remotes.json" }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            project.path("remotes.json"),
+            serde_json::json!({ "main": script_id('A'), "staging": script_id('B') }).to_string(),
+        )
+        .unwrap();
+        let new_dir = project.path("apps/web");
+        project
+            .run(&["mv-manifest", &new_dir.to_string_lossy()])
+            .unwrap();
+        assert!(new_dir.join(".clasp.dev.json").exists());
+        assert!(!new_dir.join(MANIFEST_NAME).exists());
+        assert!(!project.path(SETTINGS_NAME).exists());
+        let settings: Value =
+            serde_json::from_str(&std::fs::read_to_string(new_dir.join(SETTINGS_NAME)).unwrap())
+                .unwrap();
+        assert_eq!(
+            settings,
+            serde_json::json!({ "defaultRemote": "staging", "remoteFile": "../../remotes.json" })
+        );
+    }
+
+    #[test]
+    fn mv_manifest_refuses_to_overwrite_a_manifest() {
+        let project = Project::with_remotes();
+        let new_dir = project.path("other");
+        std::fs::create_dir_all(&new_dir).unwrap();
+        std::fs::write(new_dir.join(MANIFEST_NAME), "{}").unwrap();
+        let before = project.manifest_text();
+        assert!(matches!(
+            project.run(&["mv-manifest", &new_dir.to_string_lossy()]),
+            Err(ClubError::MoveTargetExists(path)) if path == new_dir.join(MANIFEST_NAME)
+        ));
+        assert_eq!(project.manifest_text(), before);
+        assert_eq!(
+            std::fs::read_to_string(new_dir.join(MANIFEST_NAME)).unwrap(),
+            "{}"
+        );
+    }
+
+    fn manifest_remote_names(project: &Project) -> Vec<String> {
+        project.manifest()["__club__"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn set_appends_new_remotes_and_leaves_existing_ones_in_place() {
+        let project = Project::with_remotes();
+        project.run(&["set", "qa", &script_id('C')]).unwrap();
+        project.run(&["set", "main", &script_id('D')]).unwrap();
+        assert_eq!(manifest_remote_names(&project), ["main", "staging", "qa"]);
+        assert_eq!(project.manifest()["__club__"]["main"], script_id('D'));
+    }
+
+    #[test]
+    fn set_position_start_puts_the_remote_first() {
+        let project = Project::with_remotes();
+        project
+            .run(&["set", "qa", &script_id('C'), "--position", "start"])
+            .unwrap();
+        assert_eq!(manifest_remote_names(&project), ["qa", "main", "staging"]);
+        project
+            .run(&["set", "staging", &script_id('B'), "--position", "start"])
+            .unwrap();
+        assert_eq!(manifest_remote_names(&project), ["staging", "qa", "main"]);
+    }
+
+    #[test]
+    fn set_position_after_a_remote_puts_it_next() {
+        let project = Project::with_remotes();
+        project
+            .run(&["set", "qa", &script_id('C'), "--position", "after:main"])
+            .unwrap();
+        assert_eq!(manifest_remote_names(&project), ["main", "qa", "staging"]);
+        project
+            .run(&[
+                "set",
+                "main",
+                &script_id('A'),
+                "--position",
+                "before:staging",
+            ])
+            .unwrap();
+        assert_eq!(manifest_remote_names(&project), ["qa", "main", "staging"]);
+
+        let before = project.manifest_text();
+        assert!(matches!(
+            project.run(&["set", "dev", &script_id('D'), "--position", "after:ghost"]),
+            Err(ClubError::PositionAnchorNotFound(name)) if name.0 == "ghost"
+        ));
+        assert!(matches!(
+            project.run(&["set", "qa", &script_id('C'), "--position", "after:qa"]),
+            Err(ClubError::PositionAnchorMoved(_))
+        ));
+        assert_eq!(project.manifest_text(), before);
+        assert!(
+            ClubArgs::try_parse_from(["club", "set", "qa", "x", "--position", "middle"]).is_err()
+        );
+    }
+
+    /// Verifies `verify-a` (script A) and `verify-b` (script B) at `depth` with a fake clasp that
+    /// can't reach script B, and whose pulls of script A bring down one file.
+    #[cfg(unix)]
+    fn verify_statuses(depth: VerifyDepth) -> (Vec<VerifyStatus>, Vec<String>) {
+        let project = Project::new(serde_json::json!({
+            "verify-a": script_id('A'),
+            "verify-b": script_id('B'),
+        }));
+        let clasp = project.fake_clasp(&format!(
+            r#"[ "$id" = "{}" ] && exit 1
+[ "$1" = pull ] && echo 'function main() {{}}' > {}/Code.js
+exit 0"#,
+            script_id('B'),
+            ISOLATED_ROOT_DIR
+        ));
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        let settings = Settings {
+            clasp_bin: clasp,
+            clasp_output_to_stderr: true,
+            ..Settings::default()
+        };
+        let statuses = config
+            .club_remotes
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|(remote_name, remote)| {
+                verify_remote(remote_name, &remote.id, &config, &settings, depth).unwrap()
+            })
+            .collect();
+        (statuses, project.clasp_calls())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn verify_shallow_probes_each_remote_without_pulling() {
+        let (statuses, calls) = verify_statuses(VerifyDepth::Shallow);
+        assert_eq!(statuses[0], VerifyStatus::Reachable);
+        assert!(matches!(statuses[1], VerifyStatus::Unreachable(_)));
+        assert_eq!(
+            calls,
+            [
+                format!("deployments|{}", script_id('A')),
+                format!("deployments|{}", script_id('B'))
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn verify_full_pulls_each_remote_and_counts_its_source() {
+        let (statuses, calls) = verify_statuses(VerifyDepth::Full);
+        assert_eq!(statuses[0], VerifyStatus::HasSource(1));
+        assert!(matches!(statuses[1], VerifyStatus::Unreachable(_)));
+        assert_eq!(
+            calls,
+            [
+                format!("pull|{}", script_id('A')),
+                format!("pull|{}", script_id('B'))
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn verify_fails_if_any_remote_fails() {
+        let project = Project::new(serde_json::json!({
+            "check-a": script_id('A'),
+            "check-b": script_id('B'),
+        }));
+        let clasp = project.fake_clasp(&format!(
+            r#"[ "$id" = "{}" ] && exit 1; exit 0"#,
+            script_id('B')
+        ));
+        assert!(matches!(
+            project.run(&["--clasp-bin", &clasp, "verify"]),
+            Err(ClubError::VerifyFailed(failed)) if failed == [RemoteName("check-b".to_string())]
+        ));
+        // A full check also fails remotes with no source, which this fake clasp never pulls.
+        assert!(matches!(
+            project.run(&["--clasp-bin", &clasp, "verify", "--depth", "full"]),
+            Err(ClubError::VerifyFailed(failed)) if failed.len() == 2
+        ));
+        project
+            .run(&["--clasp-bin", &clasp, "verify", "check-a"])
+            .unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn set_validate_live_refuses_an_id_clasp_cannot_pull() {
+        let project = Project::with_remotes();
+        let dirs_log = project.path("dirs.log");
+        let clasp = project.fake_clasp(&format!(
+            r#"pwd > '{}'
+[ "$id" = "{}" ] && exit 1
+exit 0"#,
+            dirs_log.display(),
+            script_id('D')
+        ));
+        let result = project.run(&[
+            "--clasp-bin",
+            &clasp,
+            "set",
+            "unreachable",
+            &script_id('D'),
+            "--validate-live",
+        ]);
+        assert!(matches!(
+            result,
+            Err(ClubError::ScriptUnreachable(name, id)) if name.0 == "unreachable" && id.0 == script_id('D')
+        ));
+        assert!(project.manifest()["__club__"].get("unreachable").is_none());
+        assert_eq!(project.clasp_calls(), [format!("pull|{}", script_id('D'))]);
+        let pull_dir = std::fs::read_to_string(&dirs_log).unwrap();
+        assert!(!Path::new(pull_dir.trim_end()).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn set_validate_live_saves_an_id_clasp_can_pull() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        project
+            .run(&[
+                "--clasp-bin",
+                &clasp,
+                "set",
+                "reachable",
+                &script_id('C'),
+                "--validate-live",
+            ])
+            .unwrap();
+        assert_eq!(project.manifest()["__club__"]["reachable"], script_id('C'));
+        assert_eq!(project.clasp_calls(), [format!("pull|{}", script_id('C'))]);
+        assert_eq!(project.manifest()["scriptId"], script_id('A'));
+    }
+
+    #[test]
+    fn show_json_for_a_bare_remote() {
+        let project = Project::with_remotes();
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        let detail = show_json(
+            &config,
+            &RemoteName("staging".to_string()),
+            &project.path("src"),
+        )
+        .unwrap();
+        assert_eq!(detail["active"], false);
+        assert_eq!(detail["groups"], serde_json::json!([]));
+        assert_eq!(detail["last_deploy"], Value::Null);
+    }
+
+    #[test]
+    fn show_rejects_an_unknown_remote() {
+        let project = Project::with_remotes();
+        assert!(matches!(
+            project.run(&["show", "qa"]),
+            Err(ClubError::RemoteNotFound)
+        ));
+        project.run(&["show", "staging"]).unwrap();
+    }
+
+    fn no_picker(
+        _: &[RemoteName],
+        _: Option<&RemoteName>,
+        _: Option<&RemoteName>,
+    ) -> Option<RemoteName> {
+        panic!("should not prompt");
+    }
+
+    #[test]
+    fn bare_push_defaults_to_main_without_a_terminal() {
+        let project = Project::with_remotes();
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        let settings = Settings::default();
+        assert_eq!(
+            bare_push_target(&config, &settings, false, false, no_picker).unwrap(),
+            RemoteName("main".to_string())
+        );
+        assert!(matches!(
+            bare_push_target(&config, &settings, true, false, no_picker),
+            Err(ClubError::RemoteRequired)
+        ));
+    }
+
+    #[test]
+    fn bare_push_asks_in_a_terminal() {
+        let project = Project::with_remotes();
+        project.run(&["switch", "staging"]).unwrap();
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        fn pick_staging(
+            remote_names: &[RemoteName],
+            active: Option<&RemoteName>,
+            default: Option<&RemoteName>,
+        ) -> Option<RemoteName> {
+            assert_eq!(remote_names.len(), 2);
+            assert_eq!(active.map(|name| name.0.as_str()), Some("staging"));
+            assert_eq!(default, None);
+            Some(RemoteName("staging".to_string()))
+        }
+        assert_eq!(
+            bare_push_target(&config, &Settings::default(), true, true, pick_staging).unwrap(),
+            RemoteName("staging".to_string())
+        );
+    }
+
+    #[test]
+    fn remote_choice_accepts_numbers_names_and_the_default() {
+        let remote_names = [
+            RemoteName("main".to_string()),
+            RemoteName("staging".to_string()),
+        ];
+        let main = &remote_names[0];
+        let choose = |answer| parse_remote_choice(answer, &remote_names, Some(main));
+        assert_eq!(choose("2").unwrap().0, "staging");
+        assert_eq!(choose("staging").unwrap().0, "staging");
+        assert_eq!(choose("").unwrap().0, "main");
+        assert_eq!(choose("0"), None);
+        assert_eq!(choose("3"), None);
+        assert_eq!(choose("qa"), None);
+        assert_eq!(parse_remote_choice("", &remote_names, None), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_only_targets_a_flag_like_remote() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "--all": script_id('B'),
+        }));
+        let clasp = project.fake_clasp("");
+        project
+            .run(&["--clasp-bin", &clasp, "push", "--only", "--all"])
+            .unwrap();
+        project
+            .run(&["--clasp-bin", &clasp, "push", "-o", "main"])
+            .unwrap();
+        assert_eq!(
+            project.clasp_calls(),
+            [
+                format!("push|{}", script_id('B')),
+                format!("push|{}", script_id('A'))
+            ]
+        );
+    }
+
+    #[test]
+    fn push_only_conflicts_with_other_selections() {
+        for args in [
+            &["club", "push", "--all", "--only", "main"][..],
+            &["club", "push", "--group", "qa", "-o", "main"],
+            &["club", "push", "staging", "-o", "main"],
+        ] {
+            assert!(ClubArgs::try_parse_from(args).is_err());
+        }
+    }
+
+    #[test]
+    fn audit_reports_duplicate_ids_and_dangling_members() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "prod": script_id('A'),
+            "staging": script_id('B'),
+            "legacy": "a".repeat(60),
+            "__groups__": { "qa": ["staging", "ghost"] },
+        }));
+        let config = get_clasp_config(&project.manifest_path, false).unwrap();
+        let audit = Audit::new(&config, &RemoteName("main".to_string())).unwrap();
+        assert_eq!(
+            audit.to_json(),
+            serde_json::json!({
+                "duplicate_ids": [{ "id": script_id('A'), "remotes": ["main", "prod"] }],
+                "invalid_ids": [{ "remote": "legacy", "id": "a".repeat(60) }],
+                "dangling_group_members": [{ "group": "qa", "remote": "ghost" }],
+                "active_remote": "main",
+                "default_remote": "main",
+                "default_remote_exists": true,
+            })
+        );
+        let before = project.manifest_text();
+        project.run(&["audit"]).unwrap();
+        assert_eq!(project.manifest_text(), before);
+    }
+
+    #[test]
+    fn audit_of_a_clean_project_is_empty() {
+        let project = Project::with_remotes();
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        let audit = Audit::new(&config, &RemoteName("qa".to_string())).unwrap();
+        assert!(audit.duplicate_ids.is_empty());
+        assert!(audit.invalid_ids.is_empty());
+        assert!(audit.dangling_group_members.is_empty());
+        assert!(!audit.default_remote_exists);
+    }
+
+    #[test]
+    fn import_merge_keeps_conflicting_remotes() {
+        let existing = remotes_of(&[("main", 'A'), ("staging", 'B')]);
+        let incoming = vec![
+            ("main".to_string(), Value::String(script_id('A'))),
+            ("staging".to_string(), Value::String(script_id('C'))),
+        ];
+        let plan = ImportPlan::new(&existing, incoming, false, false, true);
+        assert!(plan.updated.is_empty());
+        assert_eq!(
+            plan.conflicts,
+            vec![(
+                RemoteName("staging".to_string()),
+                RemoteId(script_id('B')),
+                RemoteId(script_id('C'))
+            )]
+        );
+        assert_eq!(plan.unchanged, vec![RemoteName("main".to_string())]);
+    }
+
+    #[test]
+    fn remote_ops_apply_in_order_like_one_at_a_time() {
+        let name = |n: &str| RemoteName(n.to_string());
+        let mut remotes = remotes_of(&[("main", 'A'), ("staging", 'B'), ("qa", 'C')]);
+        remotes[1].protected = true;
+        let ops = [
+            RemoteOp::Remove(name("main")),
+            RemoteOp::Rename(name("qa"), name("main")),
+            RemoteOp::Set(name("staging"), RemoteId(script_id('D'))),
+            RemoteOp::Set(name("qa"), RemoteId(script_id('E'))),
+        ];
+        apply_remote_ops(&mut remotes, &ops).unwrap();
+        let mut expected = remotes_of(&[("staging", 'D'), ("main", 'C'), ("qa", 'E')]);
+        expected[0].protected = true;
+        assert_eq!(remotes, expected);
+    }
+
+    #[test]
+    fn remote_ops_change_nothing_if_any_op_is_invalid() {
+        let name = |n: &str| RemoteName(n.to_string());
+        let mut remotes = remotes_of(&[("main", 'A'), ("staging", 'B')]);
+        let ops = [
+            RemoteOp::Set(name("qa"), RemoteId(script_id('C'))),
+            RemoteOp::Remove(name("staging")),
+            RemoteOp::Rename(name("qa"), name("main")),
+        ];
+        assert!(matches!(
+            apply_remote_ops(&mut remotes, &ops),
+            Err(ClubError::InvalidRemoteOp(op, err))
+                if op == "rename qa to main" && matches!(*err, ClubError::RemoteAlreadyExists)
+        ));
+        assert!(matches!(
+            apply_remote_ops(&mut remotes, &[RemoteOp::Remove(name("staging")), RemoteOp::Remove(name("staging"))]),
+            Err(ClubError::InvalidRemoteOp(_, err)) if matches!(*err, ClubError::RemoteNotFound)
+        ));
+        assert_eq!(remotes, remotes_of(&[("main", 'A'), ("staging", 'B')]));
+    }
+
+    #[test]
+    fn remote_ops_apply_500_changes_in_order() {
+        let id = |i: usize| RemoteId(format!("{:0>57}", i));
+        let name = |i: usize| RemoteName(format!("remote-{}", i));
+        let mut remotes: IndexMap<RemoteName, Remote> =
+            (0..500).map(|i| (name(i), Remote::from(id(i)))).collect();
+        let ops: Vec<RemoteOp> = (0..500)
+            .map(|i| match i % 4 {
+                0 => RemoteOp::Remove(name(i)),
+                1 => RemoteOp::Set(name(i), id(i + 1000)),
+                2 => RemoteOp::Rename(name(i), name(i + 1000)),
+                _ => RemoteOp::Set(name(i + 2000), id(i + 2000)),
+            })
+            .collect();
+
+        apply_remote_ops(&mut remotes, &ops).unwrap();
+        assert_eq!(remotes.len(), 500);
+        assert!(!remotes.contains_key(&name(0)));
+        assert_eq!(remotes[&name(1)].id, id(1001));
+        assert_eq!(remotes[&name(1002)].id, id(2));
+        assert_eq!(remotes[&name(3)].id, id(3));
+        // Renamed and added remotes go to the end, in the order their ops came.
+        let tail: Vec<&RemoteName> = remotes.keys().skip(250).take(2).collect();
+        assert_eq!(tail, [&name(1002), &name(2003)]);
+        assert_eq!(remotes.keys().last(), Some(&name(2499)));
+    }
+
+    #[test]
+    fn import_of_500_remotes_writes_them_all() {
+        let project = Project::with_remotes();
+        let file = project.path("remotes.json");
+        let incoming: serde_json::Map<String, Value> = (0..500)
+            .map(|i| {
+                (
+                    format!("remote-{:03}", i),
+                    Value::String(format!("{:0>57}", i)),
+                )
+            })
+            .collect();
+        std::fs::write(&file, Value::Object(incoming).to_string()).unwrap();
+        project
+            .run(&["import", &file.to_string_lossy(), "--replace"])
+            .unwrap();
+        let manifest = project.manifest();
+        let remotes = manifest["__club__"].as_object().unwrap();
+        assert_eq!(remotes.len(), 500);
+        assert_eq!(remotes["remote-499"], format!("{:0>57}", 499));
+    }
+
+    #[test]
+    fn import_overwrite_replaces_conflicting_remotes() {
+        let project = Project::with_remotes();
+        let file = project.path("remotes.json");
+        std::fs::write(
+            &file,
+            serde_json::json!({ "main": script_id('A'), "staging": script_id('C') }).to_string(),
+        )
+        .unwrap();
+        project.run(&["import", file.to_str().unwrap()]).unwrap();
+        assert_eq!(project.manifest()["__club__"]["staging"], script_id('B'));
+        project
+            .run(&["import", file.to_str().unwrap(), "--overwrite"])
+            .unwrap();
+        assert_eq!(project.manifest()["__club__"]["staging"], script_id('C'));
+    }
+
+    #[test]
+    fn export_redact_masks_ids_and_keeps_the_layout() {
+        let project = Project::new(serde_json::json!({
+            "staging": { "id": script_id('B'), "description": "QA copy" },
+            "main": script_id('A'),
+            "__groups__": { "all": ["staging", "main"] },
+        }));
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        assert_eq!(
+            export_json(&config, false).unwrap(),
+            project.manifest()["__club__"]
+        );
+
+        let masked = |c: char| format!("{}{}", "*".repeat(53), c.to_string().repeat(4));
+        let redacted = export_json(&config, true).unwrap();
+        assert_eq!(
+            redacted,
+            serde_json::json!({
+                "staging": { "id": masked('B'), "description": "QA copy" },
+                "main": masked('A'),
+                "__groups__": { "all": ["staging", "main"] },
+            })
+        );
+        assert_eq!(
+            redacted.as_object().unwrap().keys().collect::<Vec<_>>(),
+            ["staging", "main", "__groups__"]
+        );
+
+        let file = project.path("redacted.json");
+        std::fs::write(&file, redacted.to_string()).unwrap();
+        assert!(project.run(&["import", &file.to_string_lossy()]).is_err());
+    }
+
+    #[test]
+    fn import_replace_drops_removed_remotes_from_groups_and_active_remote() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "staging": script_id('B'),
+            "__groups__": { "all": ["main", "staging"] },
+            "activeRemote": "staging",
+        }));
+        let file = project.path("remotes.json");
+        std::fs::write(
+            &file,
+            serde_json::json!({ "main": script_id('A') }).to_string(),
+        )
+        .unwrap();
+        project
+            .run(&["import", file.to_str().unwrap(), "--replace"])
+            .unwrap();
+        let club = &project.manifest()["__club__"];
+        assert!(club.get("staging").is_none());
+        assert_eq!(club["__groups__"]["all"], serde_json::json!(["main"]));
+        assert!(club.get("activeRemote").is_none());
+    }
+
+    #[test]
+    fn an_export_imports_back_with_its_settings() {
+        let source = Project::new(serde_json::json!({
+            "staging": { "id": script_id('B'), "protected": true, "description": "QA copy" },
+            "main": script_id('A'),
+            "__groups__": { "all": ["staging", "main"] },
+            "activeRemote": "main",
+            "__version__": CLUB_SCHEMA_VERSION,
+        }));
+        let config = get_clasp_config(&source.manifest_path, true).unwrap();
+        let exported = export_json(&config, false).unwrap();
+
+        let target = Project::new(serde_json::json!({ "qa": script_id('C') }));
+        let file = target.path("export.json");
+        std::fs::write(&file, exported.to_string()).unwrap();
+        target
+            .run(&["import", file.to_str().unwrap(), "--replace"])
+            .unwrap();
+        let club = &target.manifest()["__club__"];
+        assert_eq!(club["staging"], exported["staging"]);
+        assert_eq!(club["main"], exported["main"]);
+        assert!(club.get("qa").is_none());
+
+        // Importing the export into the project it came from changes nothing.
+        let file = source.path("export.json");
+        std::fs::write(&file, exported.to_string()).unwrap();
+        let before = source.manifest();
+        source
+            .run(&["import", file.to_str().unwrap(), "--replace"])
+            .unwrap();
+        assert_eq!(source.manifest(), before);
+        source
+            .run(&["diff-config", file.to_str().unwrap()])
+            .unwrap();
+        let remotes = config.club_remotes.unwrap();
+        let plan = ImportPlan::new(
+            &remotes,
+            read_remotes_file(&file).unwrap(),
+            true,
+            true,
+            true,
+        );
+        assert_eq!(plan.unchanged.len(), 2);
+        assert!(plan.added.is_empty() && plan.updated.is_empty() && plan.invalid.is_empty());
+    }
+
+    #[test]
+    fn import_format_is_sniffed_from_content_without_an_extension() {
+        let json = format!("{{\n  \"qa\": \"{}\"\n}}\n", script_id('C'));
+        let yaml = format!(
+            "# exported remotes\n---\nqa: {}\nprod: {}\n",
+            script_id('C'),
+            script_id('D')
+        );
+        let toml = format!(
+            "# exported remotes\nqa = \"{}\"\nprod = \"{}\"\n",
+            script_id('C'),
+            script_id('D')
+        );
+        let toml_section = format!("[prod]\nid = \"{}\"\n", script_id('D'));
+        let cases = [
+            (&json, InputFormat::Json),
+            (&yaml, InputFormat::Yaml),
+            (&toml, InputFormat::Toml),
+            (&toml_section, InputFormat::Toml),
+        ];
+        for (text, expected) in cases {
+            let format = detect_input_format(InputFormat::Auto, Some(Path::new("remotes")), text);
+            assert_eq!(format, expected, "{}", text);
+            let remotes = parse_remotes(text, format).unwrap();
+            assert!(remotes
+                .iter()
+                .any(|(name, _)| name == "qa" || name == "prod"));
+        }
+        assert_eq!(sniff_input_format(""), InputFormat::Json);
+        assert_eq!(
+            detect_input_format(InputFormat::Auto, Some(Path::new("remotes.yml")), &json),
+            InputFormat::Yaml
+        );
+        assert_eq!(
+            detect_input_format(InputFormat::Toml, None, &json),
+            InputFormat::Toml
+        );
+
+        let project = Project::with_remotes();
+        for (name, text) in [("remotes-yaml", &yaml), ("remotes-toml", &toml)] {
+            let file = project.path(name);
+            std::fs::write(&file, text).unwrap();
+            project
+                .run(&["import", file.to_str().unwrap(), "--replace"])
+                .unwrap();
+            let club = &project.manifest()["__club__"];
+            assert_eq!(club["qa"], script_id('C'), "{}", name);
+            assert_eq!(club["prod"], script_id('D'), "{}", name);
+        }
+    }
+
+    #[test]
+    fn no_write_leaves_the_manifest_alone() {
+        let project = Project::with_remotes();
+        let before = project.manifest_text();
+        project
+            .run(&["--no-write", "set", "qa", &script_id('C')])
+            .unwrap();
+        assert_eq!(project.manifest_text(), before);
+
+        assert!(matches!(
+            project.run(&["--no-write", "push", "staging"]),
+            Err(ClubError::NoWriteUnsupported)
+        ));
+        assert_eq!(project.manifest_text(), before);
+    }
+
+    #[test]
+    fn writing_a_borrowed_config_round_trips_the_manifest() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "staging": script_id('B'),
+            "__groups__": {"all": ["main", "staging"]},
+            "requireCleanGit": true,
+            "activeRemote": "main",
+        }));
+        let before = project.manifest();
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        write_clasp_config(&config, &project.manifest_path).unwrap();
+        assert_eq!(project.manifest(), before);
+        // The config is still ours after writing, so a second write sees the same state.
+        write_clasp_config(&config, &project.manifest_path).unwrap();
+        assert_eq!(project.manifest(), before);
+        assert_eq!(Value::try_from(&config).unwrap(), before);
+    }
+
+    #[test]
+    fn remotes_without_settings_are_written_as_plain_strings() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "staging": { "id": script_id('B') },
+            "qa": { "id": script_id('C'), "protected": false },
+            "prod": { "id": script_id('D'), "protected": true },
+            "demo": { "id": script_id('E'), "description": "For demos" },
+        }));
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        write_clasp_config(&config, &project.manifest_path).unwrap();
+        assert_eq!(
+            project.manifest()["__club__"],
+            serde_json::json!({
+                "main": script_id('A'),
+                "staging": script_id('B'),
+                "qa": script_id('C'),
+                "prod": { "id": script_id('D'), "protected": true },
+                "demo": { "id": script_id('E'), "description": "For demos" },
+            })
+        );
+        let reread = get_clasp_config(&project.manifest_path, true).unwrap();
+        assert_eq!(reread.club_remotes, config.club_remotes);
+    }
+
+    #[test]
+    fn config_migrate_upgrades_a_legacy_config_once() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "staging": { "id": script_id('B'), "protected": false },
+            "activeRemote": "staging",
+        }));
+        project.run(&["config", "migrate"]).unwrap();
+        assert_eq!(
+            project.manifest()["__club__"],
+            serde_json::json!({
+                "__version__": CLUB_SCHEMA_VERSION,
+                "main": script_id('A'),
+                "staging": script_id('B'),
+                "activeRemote": "staging",
+            })
+        );
+
+        // A second run finds nothing to do and leaves the manifest exactly as it was.
+        let migrated = project.manifest_text();
+        project.run(&["config", "migrate"]).unwrap();
+        assert_eq!(project.manifest_text(), migrated);
+
+        // Other commands keep the recorded version.
+        project.run(&["set", "qa", &script_id('C')]).unwrap();
+        assert_eq!(
+            project.manifest()["__club__"][VERSION_KEY],
+            CLUB_SCHEMA_VERSION
+        );
+    }
+
+    #[test]
+    fn config_migrate_renames_off_format_remotes_even_under_strict() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "prod.eu": script_id('B'),
+            "__groups__": { "live": ["main", "prod.eu"] },
+        }));
+        project.run(&["switch", "prod.eu"]).unwrap();
+        assert!(matches!(
+            project.run(&["--strict", "list"]),
+            Err(ClubError::StrictWarning(_))
+        ));
+        project.run(&["--strict", "config", "migrate"]).unwrap();
+        assert_eq!(
+            project.manifest()["__club__"],
+            serde_json::json!({
+                "__version__": CLUB_SCHEMA_VERSION,
+                "main": script_id('A'),
+                "prod-eu": script_id('B'),
+                "activeRemote": "prod-eu",
+                "__groups__": { "live": ["main", "prod-eu"] },
+            })
+        );
+        project.run(&["--strict", "list"]).unwrap();
+
+        // A rename that would collide with another remote migrates nothing.
+        let project = Project::new(serde_json::json!({
+            "prod": script_id('A'),
+            "prod.": script_id('B'),
+        }));
+        let original = project.manifest_text();
+        assert!(matches!(
+            project.run(&["config", "migrate"]),
+            Err(ClubError::NameCollision(_))
+        ));
+        assert_eq!(project.manifest_text(), original);
+    }
+
+    #[test]
+    fn every_command_refuses_a_newer_version() {
+        let project = Project::new(serde_json::json!({
+            "__version__": CLUB_SCHEMA_VERSION + 1,
+            "main": script_id('A'),
+        }));
+        let original = project.manifest_text();
+        for args in [
+            &["config", "migrate"][..],
+            &["list"],
+            &["set", "qa", &script_id('C')],
+        ] {
+            assert!(matches!(
+                project.run(args),
+                Err(ClubError::ConfigVersionTooNew(version)) if version == CLUB_SCHEMA_VERSION + 1
+            ));
+        }
+        assert_eq!(project.manifest_text(), original);
+    }
+
+    #[test]
+    fn manifest_syntax_errors_are_parse_errors() {
+        let project = Project::with_remotes();
+        std::fs::write(
+            &project.manifest_path,
+            "{\n  \"scriptId\": \"abc\",\n  oops\n}",
+        )
+        .unwrap();
+        match get_clasp_config(&project.manifest_path, true) {
+            Err(ClubError::ManifestParseError(line, column, message)) => {
+                assert_eq!((line, column), (3, 3));
+                assert_eq!(message, "key must be a string");
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn manifest_with_the_wrong_shape_is_a_shape_error() {
+        let project = Project::with_remotes();
+        for manifest in [
+            serde_json::json!({ "scriptId": script_id('A'), "parentId": [] }),
+            serde_json::json!({ "scriptId": script_id('A'), "rootDir": "src", "parentId": "x" }),
+            serde_json::json!([1, 2, 3]),
+        ] {
+            project.write_manifest(manifest);
+            assert!(matches!(
+                get_clasp_config(&project.manifest_path, true),
+                Err(ClubError::ManifestShapeError(_))
+            ));
+        }
+    }
+
+    fn config_at(script: char, remotes: &[(&str, char)]) -> ClaspConfig {
+        ClaspConfig {
+            script_id: script_id(script),
+            club_remotes: Some(remotes_of(remotes)),
+            ..clean_git_config()
+        }
+    }
+
+    #[test]
+    fn active_remote_is_none_when_detached() {
+        let config = config_at('Z', &[("main", 'A'), ("staging", 'B')]);
+        assert_eq!(active_remote(&config), None);
+        assert!(!active_remote_is_ambiguous(&config));
+    }
+
+    #[test]
+    fn active_remote_finds_the_one_match() {
+        let config = config_at('B', &[("main", 'A'), ("staging", 'B')]);
+        assert_eq!(active_remote(&config).unwrap().0, "staging");
+        assert!(!active_remote_is_ambiguous(&config));
+    }
+
+    #[test]
+    fn active_remote_flags_several_matches() {
+        let mut config = config_at('A', &[("main", 'A'), ("staging", 'B'), ("prod", 'A')]);
+        assert_eq!(active_remote(&config).unwrap().0, "main");
+        assert!(active_remote_is_ambiguous(&config));
+        config.club_settings.active_remote = Some(RemoteName("prod".to_string()));
+        assert_eq!(active_remote(&config).unwrap().0, "prod");
+        config.club_settings.active_remote = Some(RemoteName("staging".to_string()));
+        assert_eq!(active_remote(&config).unwrap().0, "main");
+    }
+
+    #[test]
+    fn list_json_marks_the_active_remote() {
+        let remotes = remotes_of(&[("main", 'A'), ("staging", 'B')]);
+        let mut json = remotes_json(&remotes);
+        mark_active(&mut json, Some(&RemoteName("staging".to_string())));
+        assert_eq!(json[0]["active"], false);
+        assert_eq!(json[1]["active"], true);
+    }
+
+    /// A hook that logs the pushed remote, its ID, and the manifest's scriptId at the time.
+    const LOGGING_HOOK: &str = r#"id=$(sed -n 's/.*"scriptId": *"\([^"]*\)".*/\1/p' .clasp.json | head -n 1); echo "$CLUB_REMOTE $CLUB_SCRIPT_ID $id" >> hook.log"#;
+
+    #[cfg(unix)]
+    #[test]
+    fn after_push_hook_receives_the_pushed_remote() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        project
+            .run(&[
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "staging",
+                "--after-push",
+                LOGGING_HOOK,
+            ])
+            .unwrap();
+        let log = std::fs::read_to_string(project.path("hook.log")).unwrap();
+        assert_eq!(
+            log,
+            format!("staging {} {}\n", script_id('B'), script_id('A'))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn after_push_hook_failures_are_fatal_only_when_asked() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        project
+            .run(&["--clasp-bin", &clasp, "push", "--after-push", "exit 3"])
+            .unwrap();
+        assert!(matches!(
+            project.run(&[
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "--after-push",
+                "exit 3",
+                "--hook-fatal"
+            ]),
+            Err(ClubError::HookFailed(..))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn after_push_hook_can_be_set_in_clubrc() {
+        let project = Project::with_remotes();
+        std::fs::write(
+            project.path(SETTINGS_NAME),
+            serde_json::json!({ "afterPush": LOGGING_HOOK }).to_string(),
+        )
+        .unwrap();
+        let clasp = project.fake_clasp("exit 1");
+        assert!(project.run(&["--clasp-bin", &clasp, "push"]).is_err());
+        assert!(!project.path("hook.log").exists());
+        let clasp = project.fake_clasp("");
+        project.run(&["--clasp-bin", &clasp, "push"]).unwrap();
+        assert!(project.path("hook.log").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_pre_push_stops_before_clasp_runs() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        assert!(matches!(
+            project.run(&["--clasp-bin", &clasp, "push", "staging", "--pre-push", "exit 3"]),
+            Err(ClubError::PrePushFailed(Some(name), _)) if name.0 == "staging"
+        ));
+        assert!(matches!(
+            project.run(&[
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "--all",
+                "--pre-push",
+                "exit 3",
+                "--pre-push-once"
+            ]),
+            Err(ClubError::PrePushFailed(None, _))
+        ));
+        assert!(project.clasp_calls().is_empty());
+        assert_eq!(project.manifest()["scriptId"], script_id('A'));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pre_push_runs_in_root_dir_with_the_remote_in_its_environment() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        let hook = format!(
+            r#"echo "$CLUB_REMOTE $CLUB_SCRIPT_ID $(pwd)" >> '{}'"#,
+            project.path("pre-push.log").display()
+        );
+        project
+            .run(&["--clasp-bin", &clasp, "push", "--all", "--pre-push", &hook])
+            .unwrap();
+        let src = project.path("src");
+        assert_eq!(
+            std::fs::read_to_string(project.path("pre-push.log")).unwrap(),
+            format!(
+                "main {} {src}\nstaging {} {src}\n",
+                script_id('A'),
+                script_id('B'),
+                src = src.display()
+            )
+        );
+
+        std::fs::remove_file(project.path("pre-push.log")).unwrap();
+        project
+            .run(&[
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "--all",
+                "--pre-push",
+                &hook,
+                "--pre-push-once",
+            ])
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(project.path("pre-push.log")).unwrap(),
+            format!("  {}\n", src.display())
+        );
+        assert_eq!(project.clasp_calls().len(), 4);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pre_push_once_can_create_root_dir_and_the_into_dir() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        let into = project.path("build").to_string_lossy().into_owned();
+        project
+            .run(&[
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "--all",
+                "--pre-push",
+                "mkdir ../build",
+                "--pre-push-once",
+                "--into",
+                &into,
+            ])
+            .unwrap();
+        assert_eq!(project.clasp_calls().len(), 2);
+
+        let mut manifest = project.manifest();
+        manifest["rootDir"] = Value::String("dist".to_string());
+        project.write_manifest(manifest);
+        project
+            .run(&[
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "--pre-push",
+                "mkdir dist",
+                "--pre-push-once",
+            ])
+            .unwrap();
+        assert!(project.path("dist").is_dir());
+        assert_eq!(project.clasp_calls().len(), 3);
+    }
+
+    #[test]
+    fn init_from_existing_adds_a_remote_per_manifest() {
+        let project = Project::new(Value::Null);
+        project.write_manifest(serde_json::json!({
+            "scriptId": script_id('A'),
+            "rootDir": "src",
+            "parentId": [],
+        }));
+        std::fs::create_dir(project.path("backups")).unwrap();
+        for (file, id) in [
+            (".clasp.staging.json", script_id('B')),
+            ("prod backup.json", script_id('C')),
+            ("old-main.json", script_id('A')),
+            ("broken.json", "not an id".to_string()),
+        ] {
+            std::fs::write(
+                project.path("backups").join(file),
+                serde_json::json!({ "scriptId": id, "rootDir": "src" }).to_string(),
+            )
+            .unwrap();
+        }
+
+        project
+            .run(&["init", "--from-existing", "backups/*.json"])
+            .unwrap();
+        let club = &project.manifest()["__club__"];
+        assert_eq!(club["main"], script_id('A'));
+        assert_eq!(club["staging"], script_id('B'));
+        assert_eq!(club["prod-backup"], script_id('C'));
+        assert_eq!(club[VERSION_KEY], CLUB_SCHEMA_VERSION);
+        assert_eq!(club.as_object().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn diff_config_reports_added_and_changed_remotes() {
+        let project = Project::with_remotes();
+        let file = project.path("remotes.json");
+        std::fs::write(
+            &file,
+            serde_json::json!({
+                "main": script_id('A'),
+                "staging": script_id('C'),
+                "prod": script_id('D'),
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let before = project.manifest_text();
+
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        let remotes = config.club_remotes.unwrap();
+        let plan = ImportPlan::new(
+            &remotes,
+            read_remotes_file(&file).unwrap(),
+            true,
+            true,
+            true,
+        );
+        assert_eq!(
+            plan.diff_json(&remotes),
+            serde_json::json!({
+                "added": [{ "name": "prod", "id": script_id('D') }],
+                "changed": [{ "name": "staging", "local_id": script_id('B'), "file_id": script_id('C') }],
+                "removed": [],
+                "invalid": [],
+            })
+        );
+
+        project
+            .run(&["--json", "diff-config", file.to_str().unwrap()])
+            .unwrap();
+        assert_eq!(project.manifest_text(), before);
+    }
+
+    #[test]
+    fn path_options_expand_tilde_and_env_vars() {
+        let lookup = |name: &str| match name {
+            "HOME" => Some("/home/dev".to_string()),
+            "GAS" => Some("/srv/gas".to_string()),
+            "STAGE" => Some("dev".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            expand_path("~/projects/gas/.clasp.json", lookup).unwrap(),
+            PathBuf::from("/home/dev/projects/gas/.clasp.json")
+        );
+        assert_eq!(
+            expand_path("~", lookup).unwrap(),
+            PathBuf::from("/home/dev")
+        );
+        assert_eq!(
+            expand_path("$GAS/.clasp.${STAGE}.json", lookup).unwrap(),
+            PathBuf::from("/srv/gas/.clasp.dev.json")
+        );
+        assert_eq!(
+            expand_path("~other/a$.json", lookup).unwrap(),
+            PathBuf::from("~other/a$.json")
+        );
+        assert_eq!(
+            expand_path("$MISSING/${GAS}/a${MISSING}.json", lookup).unwrap(),
+            PathBuf::from("$MISSING//srv/gas/a${MISSING}.json")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn concurrency_safe_push_isolates_each_remote() {
+        let project = Project::with_remotes();
+        std::fs::write(project.path("src").join("Code.js"), "function main() {}").unwrap();
+        let before = project.manifest_text();
+        let dirs_log = project.path("dirs.log");
+        let clasp = project.fake_clasp(&format!(
+            "pwd >> '{}'\ntest -f root/Code.js",
+            dirs_log.display()
+        ));
+
+        project
+            .run(&["--clasp-bin", &clasp, "push", "--all", "--concurrency-safe"])
+            .unwrap();
+
+        let mut calls = project.clasp_calls();
+        calls.sort();
+        assert_eq!(
+            calls,
+            vec![
+                format!("push|{}", script_id('A')),
+                format!("push|{}", script_id('B')),
+            ]
+        );
+        let dirs: Vec<String> = std::fs::read_to_string(&dirs_log)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(dirs.len(), 2);
+        assert_ne!(dirs[0], dirs[1]);
+        for dir in &dirs {
+            assert!(!Path::new(dir).starts_with(project.dir.path()));
+            assert!(!Path::new(dir).exists());
+        }
+        assert_eq!(project.manifest_text(), before);
+    }
+
+    #[test]
+    fn a_panicking_push_thread_fails_only_its_own_remote() {
+        let results: Vec<Result<(), ClubError>> = std::thread::scope(|scope| {
+            let handles = vec![
+                scope.spawn(|| Ok(())),
+                scope.spawn(|| panic!("out of threads")),
+            ];
+            handles.into_iter().map(join_push).collect()
+        });
+        assert!(results[0].is_ok());
+        assert!(
+            matches!(&results[1], Err(ClubError::PushPanicked(err)) if err == "out of threads")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn concurrency_safe_push_links_symlinks_instead_of_following_them() {
+        let project = Project::with_remotes();
+        std::fs::write(project.path("src").join("Code.js"), "function main() {}").unwrap();
+        std::os::unix::fs::symlink(".", project.path("src").join("loop")).unwrap();
+        let clasp = project.fake_clasp("test -L root/loop && test -f root/loop/Code.js");
+        project
+            .run(&["--clasp-bin", &clasp, "push", "main", "--concurrency-safe"])
+            .unwrap();
+        assert_eq!(project.clasp_calls(), [format!("push|{}", script_id('A'))]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn concurrency_safe_push_refuses_the_project_dir_as_root_dir() {
+        let project = Project::with_remotes();
+        for root_dir in ["", "."] {
+            let mut manifest = project.manifest();
+            manifest["rootDir"] = Value::String(root_dir.to_string());
+            project.write_manifest(manifest);
+            let clasp = project.fake_clasp("");
+            assert!(matches!(
+                project.run(&["--clasp-bin", &clasp, "push", "main", "--concurrency-safe"]),
+                Err(ClubError::IsolatedPushNeedsRootDir)
+            ));
+            assert!(project.clasp_calls().is_empty());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn concurrent_report_ends_with_every_remote_done_or_failed() {
+        let project = Project::new(serde_json::json!({
+            "report-a": script_id('A'),
+            "report-b": script_id('B'),
+            "report-c": script_id('C'),
+        }));
+        let clasp = project.fake_clasp(&format!(
+            r#"[ "$id" = "{}" ] && exit 1; exit 0"#,
+            script_id('B')
+        ));
+        let config = get_clasp_config(&project.manifest_path, true).unwrap();
+        let targets = PushSelection::All(PushFilters::default())
+            .targets(&config)
+            .unwrap();
+        let mut report = ConcurrentReport::new(&targets, false);
+        let escapes = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+        let plain = |report: &ConcurrentReport| -> Vec<String> {
+            report
+                .lines()
+                .iter()
+                .map(|line| escapes.replace_all(line, "").into_owned())
+                .collect()
+        };
+        assert_eq!(
+            plain(&report),
+            [
+                "  report-a  queued",
+                "  report-b  queued",
+                "  report-c  queued"
+            ]
+        );
+
+        let settings = Settings {
+            clasp_bin: clasp,
+            ..Settings::default()
+        };
+        let result = push_isolated_all(
+            targets,
+            &config,
+            &project.manifest_path,
+            &settings,
+            &["push"],
+            None,
+            Some(&mut report),
+            &mut |_| {},
+        );
+        assert!(matches!(result, Err(ClubError::PushFailed(failed)) if failed.len() == 1));
+        assert_eq!(
+            plain(&report),
+            ["  report-a  done", "  report-b  failed", "  report-c  done"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn print_cmd_shows_the_resolved_clasp_command() {
+        let project = Project::with_manifest_name(
+            serde_json::json!({ "main": script_id('A'), "staging": script_id('B') }),
+            ".clasp.dev.json",
+        );
+        assert_eq!(
+            clasp_command_line("clasp", &project.manifest_path, &["push"]),
+            format!(
+                "cd {} && clasp -P .clasp.dev.json push",
+                project.dir.path().display()
+            )
+        );
+        assert_eq!(
+            clasp_command_line("/opt/my clasp", Path::new(".clasp.json"), &["push"]),
+            "cd . && '/opt/my clasp' push"
+        );
+
+        let before = project.manifest_text();
+        let clasp = project.fake_clasp("");
+        project
+            .run(&["--clasp-bin", &clasp, "push", "staging", "--print-cmd"])
+            .unwrap();
+        assert!(project.clasp_calls().is_empty());
+        assert_eq!(project.manifest_text(), before);
+    }
+
+    #[test]
+    fn set_pick_offers_recently_used_ids() {
+        let project = Project::with_remotes();
+        assert!(matches!(
+            project.run(&["set", "prod", "--pick"]),
+            Err(ClubError::NoRecentIds)
+        ));
+
+        std::fs::write(
+            project.path("recent_ids"),
+            format!(
+                "{}\nnot-an-id\n{}\n{}\n",
+                script_id('C'),
+                script_id('B'),
+                script_id('C')
+            ),
+        )
+        .unwrap();
+        let candidates = read_recent_ids(&project.path("recent_ids"));
+        assert_eq!(
+            candidates,
+            vec![RemoteId(script_id('C')), RemoteId(script_id('B'))]
+        );
+        assert_eq!(parse_id_choice("2", &candidates), Some(script_id('B')));
+        assert_eq!(parse_id_choice("3", &candidates), None);
+        assert_eq!(parse_id_choice("", &candidates), None);
+
+        project.run(&["set", "prod", &script_id('D')]).unwrap();
+        assert_eq!(
+            read_recent_ids(&project.path("recent_ids")),
+            vec![
+                RemoteId(script_id('D')),
+                RemoteId(script_id('C')),
+                RemoteId(script_id('B'))
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_clasp_is_reported_as_not_installed() {
+        let project = Project::with_remotes();
+        let missing = project.path("no-such-clasp");
+        let missing = missing.to_str().unwrap();
+        match project.run(&["--clasp-bin", missing, "push", "staging"]) {
+            Err(ClubError::ClaspNotInstalled(clasp_bin)) => assert_eq!(clasp_bin, missing),
+            other => panic!("expected ClaspNotInstalled, got {:?}", other),
+        }
+        assert_eq!(project.manifest()["scriptId"], script_id('A'));
+    }
+
+    #[test]
+    fn compact_json_is_one_line() {
+        let json = remotes_json(&remotes_of(&[("main", 'A'), ("staging", 'B')]));
+        let entry = |name: &str, c: char| {
+            format!(
+                r#"{{"name":"{}","id":"{}","protected":false}}"#,
+                name,
+                script_id(c)
+            )
+        };
+        assert_eq!(
+            json_text(&json, true).unwrap(),
+            format!("[{},{}]\n", entry("main", 'A'), entry("staging", 'B'))
+        );
+        let pretty = json_text(&json, false).unwrap();
+        assert!(pretty.starts_with("[\n  {\n    \"name\": "));
+        assert!(pretty.ends_with("\n]\n"));
+        assert_eq!(
+            serde_json::from_str::<Value>(&pretty).unwrap(),
+            serde_json::from_str::<Value>(&json_text(&json, true).unwrap()).unwrap()
+        );
+        assert!(ClubArgs::try_parse_from(["club", "list", "--compact"]).is_err());
+    }
+
+    #[test]
+    fn root_dir_resolves_against_the_manifest_directory() {
+        let project = Project::with_remotes();
+        let mut config = get_clasp_config(&project.manifest_path, true).unwrap();
+        let manifest_dir = get_project_dir(&project.manifest_path);
+
+        // club's working directory has no `dist`, so this only passes if resolved from the manifest.
+        std::fs::create_dir(project.path("dist")).unwrap();
+        config.root_dir = "dist".to_string();
+        assert_eq!(
+            config.resolved_root_dir(&manifest_dir),
+            project.path("dist")
+        );
+        ensure_root_dir(&config, &project.manifest_path).unwrap();
+        config.root_dir = "../dist".to_string();
+        assert_eq!(
+            config.resolved_root_dir(&manifest_dir),
+            manifest_dir.join("../dist")
+        );
+
+        let elsewhere = TempDir::new();
+        config.root_dir = elsewhere.path().to_string_lossy().into_owned();
+        assert_eq!(config.resolved_root_dir(&manifest_dir), elsewhere.path());
+        ensure_root_dir(&config, &project.manifest_path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_into_swaps_root_dir_for_the_push_and_restores_it() {
+        let project = Project::with_remotes();
+        std::fs::create_dir(project.path("dist")).unwrap();
+        let clasp = project.fake_clasp(&format!(
+            r#"sed -n 's/.*"rootDir": *"\([^"]*\)".*/\1/p' .clasp.json >> '{}'"#,
+            project.path("root_dirs.log").display()
+        ));
+        let dist = project.path("dist").to_string_lossy().into_owned();
+        project
+            .run(&["--clasp-bin", &clasp, "push", "staging", "--into", &dist])
+            .unwrap();
+        assert_eq!(
+            project.clasp_calls(),
+            vec![format!("push|{}", script_id('B'))]
+        );
+        assert_eq!(
+            std::fs::read_to_string(project.path("root_dirs.log")).unwrap(),
+            format!("{}\n", dist)
+        );
+        assert_eq!(project.manifest()["rootDir"], "src");
+        assert_eq!(project.manifest()["scriptId"], script_id('A'));
+
+        let missing = project.path("build").to_string_lossy().into_owned();
+        assert!(matches!(
+            project.run(&["--clasp-bin", &clasp, "push", "staging", "--into", &missing]),
+            Err(ClubError::IntoDirMissing(path)) if path == project.path("build")
+        ));
+        assert_eq!(project.clasp_calls().len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_fails_early_when_root_dir_is_missing() {
+        let project = Project::with_remotes();
+        std::fs::remove_dir(project.path("src")).unwrap();
+        let clasp = project.fake_clasp("");
+        assert!(matches!(
+            project.run(&["--clasp-bin", &clasp, "push", "staging"]),
+            Err(ClubError::RootDirMissing(path)) if path == project.path("src")
+        ));
+        assert!(project.clasp_calls().is_empty());
+
+        project
+            .run(&[
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "staging",
+                "--allow-missing-rootdir",
+            ])
+            .unwrap();
+        assert_eq!(project.clasp_calls().len(), 1);
+
+        let mut manifest = project.manifest();
+        manifest["rootDir"] = Value::String(String::new());
+        project.write_manifest(manifest);
+        project
+            .run(&["--clasp-bin", &clasp, "push", "staging"])
+            .unwrap();
+        assert_eq!(project.clasp_calls().len(), 2);
+    }
+
+    #[test]
+    fn touch_creates_a_club_ready_manifest() {
+        let project = Project::with_remotes();
+        std::fs::remove_file(&project.manifest_path).unwrap();
+        project
+            .run(&["touch", "--script-id", &script_id('C'), "--root-dir", "src"])
+            .unwrap();
+        let manifest = project.manifest();
+        assert_eq!(manifest["scriptId"], script_id('C'));
+        assert_eq!(manifest["rootDir"], "src");
+        assert_eq!(manifest["__club__"]["main"], script_id('C'));
+        assert_eq!(manifest["__club__"][VERSION_KEY], CLUB_SCHEMA_VERSION);
+        project.run(&["list"]).unwrap();
+    }
+
+    #[test]
+    fn touch_refuses_to_replace_a_manifest_without_force() {
+        let project = Project::with_remotes();
+        let before = project.manifest_text();
+        assert!(matches!(
+            project.run(&["touch", "--script-id", &script_id('C')]),
+            Err(ClubError::ManifestExists(_))
+        ));
+        assert_eq!(project.manifest_text(), before);
+
+        project
+            .run(&["touch", "--script-id", &script_id('C'), "--force"])
+            .unwrap();
+        assert_eq!(project.manifest()["scriptId"], script_id('C'));
+        assert!(project.manifest()["__club__"].get("staging").is_none());
+    }
+
+    #[test]
+    fn examples_show_copyable_commands() {
+        let push = examples_text(Some("push")).unwrap();
+        assert!(push
+            .lines()
+            .any(|line| line.trim().starts_with("club push")));
+        assert!(!push.contains("club set"));
+        assert!(examples_text(None).unwrap().contains("club set"));
+        assert!(matches!(
+            examples_text(Some("frobnicate")),
+            Err(ClubError::NoExamples(_))
+        ));
+    }
+
+    #[test]
+    fn strict_turns_duplicate_id_warnings_into_errors() {
+        let project = Project::with_remotes();
+        project.run(&["set", "qa", &script_id('B')]).unwrap();
+        assert_eq!(project.manifest()["__club__"]["qa"], script_id('B'));
+
+        match project.run(&["--strict", "set", "prod", &script_id('A')]) {
+            Err(ClubError::StrictWarning(message)) => {
+                assert_eq!(message, "prod has the same script ID as main.")
+            }
+            other => panic!("expected a strict warning error, got {:?}", other),
+        }
+        assert!(project.manifest()["__club__"].get("prod").is_none());
+    }
+
+    #[test]
+    fn swap_exchanges_two_remotes_ids() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "blue": { "id": script_id('B'), "protected": true },
+            "green": script_id('C'),
+        }));
+        project.run(&["swap", "blue", "green"]).unwrap();
+        let club = &project.manifest()["__club__"];
+        assert_eq!(
+            club["blue"],
+            serde_json::json!({ "id": script_id('C'), "protected": true })
+        );
+        assert_eq!(club["green"], script_id('B'));
+        assert_eq!(club["main"], script_id('A'));
+    }
+
+    #[test]
+    fn swap_requires_both_remotes() {
+        let project = Project::with_remotes();
+        let before = project.manifest_text();
+        assert!(matches!(
+            project.run(&["swap", "main", "prod"]),
+            Err(ClubError::RemoteNotFound)
+        ));
+        assert_eq!(project.manifest_text(), before);
+    }
+
+    #[test]
+    fn diff_config_lines_are_colored_by_change() {
+        let remotes = remotes_of(&[("main", 'A'), ("staging", 'B'), ("qa", 'C')]);
+        let incoming = vec![
+            ("main".to_string(), Value::String(script_id('A'))),
+            ("staging".to_string(), Value::String(script_id('D'))),
+            ("prod".to_string(), Value::String(script_id('E'))),
+        ];
+        let plan = ImportPlan::new(&remotes, incoming, true, true, true);
+        // Checked on the styles rather than rendered text, which depends on the process-wide color
+        // setting.
+        let lines = plan.lines();
+        let styled: Vec<(&str, Option<colored::Color>)> =
+            lines.iter().map(|line| (&**line, line.fgcolor())).collect();
+        let prod_line = format!("+ prod: {}", script_id('E'));
+        assert_eq!(styled[0], (prod_line.as_str(), Some(colored::Color::Green)));
+        assert!(styled[1].0.starts_with("~ staging"));
+        assert_eq!(styled[1].1, Some(colored::Color::Yellow));
+        assert_eq!(styled[2], ("  main", None));
+        assert_eq!(styled[3], ("- qa", Some(colored::Color::Red)));
+
+        let json = plan.diff_json(&remotes);
+        assert_eq!(json["added"][0]["name"], "prod");
+        assert_eq!(json["changed"][0]["name"], "staging");
+        assert_eq!(json["removed"][0]["name"], "qa");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn plan_file_records_each_attempted_push() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp(&format!(
+            "[ \"$id\" = {} ] && exit 1\nexit 0",
+            script_id('B')
+        ));
+        let plan_file = project.path("plan.json");
+        let result = project.run(&[
+            "--clasp-bin",
+            &clasp,
+            "push",
+            "--all",
+            "--plan-file",
+            plan_file.to_str().unwrap(),
+        ]);
+        assert!(result.is_err());
+
+        let plan: Value =
+            serde_json::from_str(&std::fs::read_to_string(&plan_file).unwrap()).unwrap();
+        assert!(plan["startedAt"].as_str().unwrap().ends_with('Z'));
+        let pushes = plan["pushes"].as_array().unwrap();
+        assert_eq!(pushes.len(), 2);
+        assert_eq!(
+            pushes[0],
+            serde_json::json!({ "remote": "main", "id": script_id('A'), "ok": true })
+        );
+        assert_eq!(pushes[1]["remote"], "staging");
+        assert_eq!(pushes[1]["id"], script_id('B'));
+        assert_eq!(pushes[1]["ok"], false);
+        assert_eq!(pushes[1]["error"], "Error running clasp: clasp push failed");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_scriptid_pushes_once_without_adding_a_remote() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp("");
+        let before = project.manifest();
+        project
+            .run(&["--clasp-bin", &clasp, "push", "--scriptid", &script_id('C')])
+            .unwrap();
+        assert_eq!(
+            project.clasp_calls(),
+            vec![format!("push|{}", script_id('C'))]
+        );
+        assert_eq!(project.manifest(), before);
+        assert!(matches!(
+            project.run(&["--clasp-bin", &clasp, "push", "--scriptid", "nope"]),
+            Err(ClubError::InvalidRemoteId)
+        ));
+        assert!(ClubArgs::try_parse_from([
+            "club",
+            "push",
+            "staging",
+            "--scriptid",
+            &script_id('C')
+        ])
+        .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_scriptid_and_register_adds_the_remote_after_a_successful_push() {
+        let project = Project::with_remotes();
+        let clasp = project.fake_clasp(&format!(
+            "if [ \"$id\" = {} ]; then exit 1; fi",
+            script_id('D')
+        ));
+        let push = |id: char| {
+            project.run(&[
+                "--clasp-bin",
+                &clasp,
+                "push",
+                "--scriptid",
+                &script_id(id),
+                "--and-register",
+                "qa",
+            ])
+        };
+        assert!(push('D').is_err());
+        assert_eq!(project.manifest()["__club__"].get("qa"), None);
+
+        push('C').unwrap();
+        let manifest = project.manifest();
+        assert_eq!(manifest["__club__"]["qa"], script_id('C'));
+        assert_eq!(manifest["scriptId"], script_id('A'));
+        assert!(matches!(push('C'), Err(ClubError::RemoteAlreadyExists)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_report_has_a_case_per_remote_with_clasp_errors() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "staging": script_id('B'),
+            "qa": script_id('C'),
+        }));
+        let clasp = project.fake_clasp(&format!(
+            "if [ \"$id\" = {} ]; then echo 'Push failed: <quota> exceeded' >&2; exit 1; fi",
+            script_id('B')
+        ));
+        let report = project.path("report.xml");
+        let result = project.run(&[
+            "--clasp-bin",
+            &clasp,
+            "push",
+            "--all",
+            "--keep-going",
+            "--report",
+            report.to_str().unwrap(),
+        ]);
+        assert!(matches!(result, Err(ClubError::PushFailed(ref failed)) if failed.len() == 1));
+
+        let xml = std::fs::read_to_string(&report).unwrap();
+        assert!(xml.contains(r#"<testsuite name="club push" tests="3" failures="1" skipped="0""#));
+        assert_eq!(xml.matches("<testcase ").count(), 3);
+        assert!(xml.contains(r#"<testcase classname="club.push" name="main"/>"#));
+        assert!(xml.contains(r#"<testcase classname="club.push" name="qa"/>"#));
+        assert!(xml.contains(
+            r#"<testcase classname="club.push" name="staging">
+    <failure message="Error running clasp: Push failed: &lt;quota&gt; exceeded (exit status: 1)">"#
+        ));
+    }
+
+    #[test]
+    fn remote_ids_come_from_each_url_shape() {
+        let id = script_id('A');
+        for url in [
+            format!("https://script.google.com/d/{}/edit", id),
+            format!("https://script.google.com/home/projects/{}/edit", id),
+            format!("https://script.google.com/home/projects/{}", id),
+            format!("https://script.google.com/macros/library/d/{}/3", id),
+            format!("https://script.google.com/u/1/d/{}/edit?usp=sharing", id),
+        ] {
+            assert_eq!(
+                RemoteId::from_url(&url).unwrap(),
+                RemoteId(id.clone()),
+                "{}",
+                url
+            );
+        }
+        for url in [
+            format!("https://script.google.com/macros/s/{}/exec", id),
+            "https://script.google.com/d/tooshort/edit".to_string(),
+            "not a url".to_string(),
+        ] {
+            assert!(RemoteId::from_url(&url).is_err(), "{}", url);
+        }
+
+        let project = Project::with_remotes();
+        let url = format!("https://script.google.com/d/{}/edit", script_id('C'));
+        project.run(&["set", "qa", "--from-url", &url]).unwrap();
+        assert_eq!(project.manifest()["__club__"]["qa"], script_id('C'));
+    }
+
+    #[test]
+    fn validate_url_needs_no_manifest() {
+        let dir = TempDir::new();
+        let ctx = Context::new(dir.path().join(MANIFEST_NAME), true);
+        let validate = |url: &str| {
+            let args = ClubArgs::try_parse_from(["club", "validate-url", url]).unwrap();
+            run_command(args.command, &ctx, &Settings::default())
+        };
+        let editor_url = format!(
+            "https://script.google.com/home/projects/{}/edit",
+            script_id('C')
+        );
+        validate(&editor_url).unwrap();
+        let web_app_url = format!("https://script.google.com/macros/s/{}/exec", script_id('C'));
+        assert!(matches!(
+            validate(&web_app_url),
+            Err(ClubError::InvalidScriptUrl(url)) if url == web_app_url
+        ));
+    }
+
+    #[test]
+    fn validate_name_applies_the_remote_name_rules() {
+        let dir = TempDir::new();
+        let ctx = Context::new(dir.path().join(MANIFEST_NAME), true);
+        let validate = |name: &str| {
+            let args = ClubArgs::try_parse_from(["club", "validate-name", name]).unwrap();
+            run_command(args.command, &ctx, &Settings::default())
+        };
+        validate("staging-2_eu").unwrap();
+        assert!(matches!(
+            validate(GROUPS_KEY),
+            Err(ClubError::ReservedRemoteName)
+        ));
+        assert!(matches!(
+            validate("my staging"),
+            Err(ClubError::InvalidRemoteName)
+        ));
+        assert!(matches!(validate(""), Err(ClubError::InvalidRemoteName)));
+    }
+
+    #[test]
+    fn existing_off_format_names_are_still_read_but_new_ones_are_refused() {
+        let project = Project::new(serde_json::json!({
+            "main": script_id('A'),
+            "qa.eu": script_id('B'),
+        }));
+        project.run(&["list"]).unwrap();
+        assert!(matches!(
+            project.run(&["--strict", "list"]),
+            Err(ClubError::StrictWarning(_))
+        ));
+        assert!(matches!(
+            project.run(&["set", "prod.eu", &script_id('C')]),
+            Err(ClubError::InvalidRemoteName)
+        ));
+        assert!(matches!(
+            project.run(&["rename", "main", "main.eu"]),
+            Err(ClubError::InvalidRemoteName)
+        ));
+        project.run(&["rename", "qa.eu", "qa-eu"]).unwrap();
+        assert_eq!(project.manifest()["__club__"]["qa-eu"], script_id('B'));
+        project.run(&["--strict", "list"]).unwrap();
+    }
+
+    #[test]
+    fn gc_prunes_invalid_and_duplicate_ids_but_keeps_main() {
+        let project = Project::new(serde_json::json!({
+            "beta": script_id('A'),
+            "main": script_id('A'),
+            "qa": script_id('B'),
+            "qa-copy": script_id('B'),
+            "legacy": "tooshort",
+            "prod": { "id": "alsotooshort", "protected": true },
+            "__groups__": { "test": ["qa-copy", "qa", "legacy"] },
+        }));
+        project.run(&["gc", "--yes"]).unwrap();
+
+        let club = &project.manifest()["__club__"];
+        let mut names: Vec<&String> = club.as_object().unwrap().keys().collect();
+        names.sort();
+        // beta comes first, so main is the duplicate, but main is never pruned without --force.
+        assert_eq!(names, ["__groups__", "beta", "main", "prod", "qa"]);
+        assert_eq!(club["__groups__"]["test"], serde_json::json!(["qa"]));
+        assert_eq!(club["main"], script_id('A'));
+    }
+
+    #[test]
+    fn list_tree_nests_group_members() {
+        let mut remotes = remotes_of(&[("main", 'A'), ("staging", 'B'), ("qa", 'C')]);
+        remotes[&RemoteName("staging".to_string())].protected = true;
+        let groups = IndexMap::from([(
+            "release".to_string(),
+            vec![
+                RemoteName("staging".to_string()),
+                RemoteName("qa".to_string()),
+            ],
+        )]);
+        let escapes = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+        let lines: Vec<String> =
+            remotes_tree(&remotes, &groups, Some(Some(&RemoteName("qa".to_string()))))
+                .iter()
+                .map(|line| escapes.replace_all(line, "").into_owned())
+                .collect();
+        assert_eq!(
+            lines,
+            vec![
+                format!("  main: {}", script_id('A')),
+                "  release".to_string(),
+                format!("  |-- staging: {} (protected)", script_id('B')),
+                format!("* `-- qa: {}", script_id('C')),
+            ]
+        );
+    }
+
+    #[test]
+    fn list_table_aligns_columns() {
+        let mut remotes = remotes_of(&[("main", 'A'), ("long-staging-name", 'B'), ("qa", 'C')]);
+        remotes[&RemoteName("qa".to_string())].protected = true;
+        let escapes = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+        let lines: Vec<String> =
+            remotes_table(&remotes, Some(Some(&RemoteName("qa".to_string()))), true)
+                .iter()
+                .map(|line| escapes.replace_all(line, "").into_owned())
+                .collect();
+
+        let pad = " ".repeat("long-staging-name".len() - "main".len());
+        assert_eq!(
+            lines,
+            vec![
+                format!(
+                    "  NAME{}  SCRIPT ID{}  PROTECTED",
+                    " ".repeat(13),
+                    " ".repeat(48)
+                ),
+                format!("  main{}  {}", pad, script_id('A')),
+                format!("  long-staging-name  {}", script_id('B')),
+                format!("* qa{}  {}  yes", " ".repeat(15), script_id('C')),
+            ]
+        );
+        let id_column = lines[1].find(&script_id('A')).unwrap();
+        assert_eq!(lines[2].find(&script_id('B')), Some(id_column));
+        assert_eq!(lines[3].find(&script_id('C')), Some(id_column));
+        assert_eq!(lines[0].find("SCRIPT ID"), Some(id_column));
     }
 }
@@ -0,0 +1,84 @@
+//! The scratch project the integration tests run the built binary in.
+
+// Each test binary uses a different part of this.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub fn script_id(c: char) -> String {
+    c.to_string().repeat(57)
+}
+
+/// A clasp project with `main` and `staging` remotes, in a scratch directory that also serves as
+/// HOME.
+pub struct Project(PathBuf);
+
+impl Project {
+    pub fn new() -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "club-it-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        let manifest = format!(
+            r#"{{"scriptId": "{a}", "rootDir": "src", "parentId": [], "__club__": {{"main": "{a}", "staging": "{b}"}}}}"#,
+            a = script_id('A'),
+            b = script_id('B')
+        );
+        std::fs::write(dir.join(".clasp.json"), manifest).unwrap();
+        Project(dir)
+    }
+
+    /// A project whose clasp, `fake-clasp` in the project, succeeds silently.
+    #[cfg(unix)]
+    pub fn with_fake_clasp() -> Self {
+        let project = Project::new();
+        let clasp = project.path().join("fake-clasp");
+        std::fs::write(&clasp, "#!/bin/sh\nexit 0\n").unwrap();
+        std::fs::set_permissions(&clasp, std::os::unix::fs::PermissionsExt::from_mode(0o755))
+            .unwrap();
+        project
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn manifest_text(&self) -> String {
+        std::fs::read_to_string(self.0.join(".clasp.json")).unwrap()
+    }
+
+    /// Runs club in the project, with the project's fake clasp if it has one.
+    pub fn output(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_club"))
+            .arg("--clasp-bin")
+            .arg(self.0.join("fake-clasp"))
+            .args(args)
+            .current_dir(&self.0)
+            .env("HOME", &self.0)
+            .env_remove("CLUB_REMOTES")
+            .env_remove("CLUB_CLASP_BIN")
+            .output()
+            .unwrap()
+    }
+
+    /// Runs club in the project, returning everything it printed.
+    pub fn club(&self, args: &[&str]) -> String {
+        let output = self.output(args);
+        format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        )
+    }
+}
+
+impl Drop for Project {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,48 @@
+//! Runs the built binary to check that `rename --dry-run`, `remove --dry-run` and `--no-write` report
+//! the change without writing the manifest.
+
+mod common;
+
+use common::{script_id, Project};
+
+#[test]
+fn rename_dry_run_reports_without_writing() {
+    let project = Project::new();
+    let before = project.manifest_text();
+    assert!(project
+        .club(&["rename", "staging", "qa", "--dry-run"])
+        .contains("Would rename staging -> qa"));
+    assert!(project
+        .club(&["rename", "staging", "main", "--dry-run"])
+        .contains("already exists"));
+    assert!(project
+        .club(&["rename", "ghost", "qa", "--dry-run"])
+        .contains("Remote not found"));
+    assert_eq!(project.manifest_text(), before);
+}
+
+#[test]
+fn remove_dry_run_reports_without_writing() {
+    let project = Project::new();
+    let before = project.manifest_text();
+    assert!(project
+        .club(&["remove", "staging", "--dry-run"])
+        .contains("Would remove staging"));
+    assert!(project
+        .club(&["remove", "ghost", "--dry-run"])
+        .contains("Remote not found"));
+    assert_eq!(project.manifest_text(), before);
+}
+
+#[test]
+fn no_write_prints_only_the_manifest_it_would_write() {
+    let project = Project::new();
+    let before = project.manifest_text();
+    let output = project.output(&["--no-write", "set", "qa", &script_id('C')]);
+    assert!(output.status.success());
+    let preview: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(preview["__club__"]["qa"], script_id('C'));
+    assert_eq!(preview["__club__"]["staging"], script_id('B'));
+    assert_eq!(preview["scriptId"], script_id('A'));
+    assert_eq!(project.manifest_text(), before);
+}
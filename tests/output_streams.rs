@@ -0,0 +1,32 @@
+//! Runs the built binary to check which stream its output goes to: data on stdout, and progress,
+//! prompts, and errors on stderr.
+
+#![cfg(unix)]
+
+mod common;
+
+use common::{script_id, Project};
+
+#[test]
+fn push_progress_and_errors_go_to_stderr() {
+    let project = Project::with_fake_clasp();
+    let output = project.output(&["push", "staging"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Pushing to staging"));
+
+    let output = project.output(&["push", "qa"]);
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Remote not found"));
+}
+
+#[test]
+fn list_data_goes_to_stdout() {
+    let project = Project::with_fake_clasp();
+    let output = project.output(&["list"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(&format!("staging: {}", script_id('B'))));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}